@@ -12,6 +12,7 @@ use diem_backup_cli::{
     utils::read_record_bytes::ReadRecordBytes,
 };
 use diem_types::transaction::Version;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use tokio::{fs::OpenOptions, io::AsyncRead};
@@ -88,32 +89,35 @@ pub async fn open_for_read(
     Ok(Box::new(file))
 }
 
-/// Tokio async parsing of state snapshot into blob
-pub async fn v5_accounts_from_snapshot_backup(
+/// Tokio async parsing of state snapshot into a stream of blobs.
+/// Each V5 record holds a whole account, so no grouping is needed: chunks
+/// are read concurrently, and their blobs yielded in snapshot order.
+pub fn v5_accounts_from_snapshot_backup(
     manifest: StateSnapshotBackupV5,
     archive_path: &Path,
-) -> Result<Vec<AccountStateBlob>, Error> {
-    // parse AccountStateBlob from chunks of the archive
-    let mut account_state_blobs: Vec<AccountStateBlob> = Vec::new();
-
-    for chunk in manifest.chunks {
-        let records = read_account_state_chunk(chunk.blobs, archive_path).await?;
-
-        for rec in records {
-            account_state_blobs.push(rec.1)
-        }
-    }
-
-    Ok(account_state_blobs)
+) -> BoxStream<'static, Result<AccountStateBlob>> {
+    let archive_path = archive_path.to_owned();
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    stream::iter(manifest.chunks)
+        .map(move |chunk| {
+            let archive_path = archive_path.clone();
+            async move { read_account_state_chunk(chunk.blobs, &archive_path).await }
+        })
+        .buffered(workers)
+        .map_ok(|records| stream::iter(records.into_iter().map(|rec| Ok(rec.1))))
+        .try_flatten()
+        .boxed()
 }
 
 /// one step extraction of account state blobs from a manifest path
-pub async fn v5_accounts_from_manifest_path(manifest_file: &Path) -> Result<Vec<AccountStateBlob>> {
+pub fn v5_accounts_from_manifest_path(
+    manifest_file: &Path,
+) -> Result<BoxStream<'static, Result<AccountStateBlob>>> {
     let archive_path = manifest_file
         .parent()
         .context("could not get archive path from manifest file")?;
     let manifest = v5_read_from_snapshot_manifest(manifest_file)?;
-    v5_accounts_from_snapshot_backup(manifest, archive_path).await
+    Ok(v5_accounts_from_snapshot_backup(manifest, archive_path))
 }
 
 #[test]
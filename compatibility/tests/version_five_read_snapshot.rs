@@ -1,3 +1,4 @@
+use futures::TryStreamExt;
 use std::path::PathBuf;
 
 use libra_backwards_compatibility::version_five::{
@@ -31,7 +32,9 @@ async fn read_full_snapshot() -> anyhow::Result<()> {
 
     let man = v5_read_from_snapshot_manifest(&p)?;
     let archive_path = fixtures_path();
-    let accts = v5_accounts_from_snapshot_backup(man, &archive_path).await?;
+    let accts: Vec<_> = v5_accounts_from_snapshot_backup(man, &archive_path)
+        .try_collect()
+        .await?;
 
     assert!(accts.len() == 17339);
 
@@ -1,7 +1,10 @@
 use crate::{
     config_wizard,
     get_genesis_artifacts::{download_genesis, get_genesis_waypoint},
-    interactive::options::{fix_config, FixOptions},
+    interactive::{
+        options::{fix_config, FixOptions},
+        profiles::add_derived_profiles,
+    },
     make_yaml_public_fullnode::init_fullnode_yaml,
     validator_config::{validator_dialogue, vfn_dialogue},
 };
@@ -9,11 +12,13 @@ use anyhow::{bail, Context, Result};
 use clap::Parser;
 use dialoguer::Select;
 use libra_types::{
-    core_types::network_playlist::NetworkPlaylist,
+    core_types::{app_cfg::AppCfg, network_playlist::NetworkPlaylist},
     exports::{AccountAddress, AuthenticationKey, NamedChain},
     global_config_dir, ol_progress,
 };
-use libra_wallet::{utils::read_operator_file, validator_files::OPERATOR_FILE};
+use libra_wallet::{
+    account_keys::get_keys_from_prompt, utils::read_operator_file, validator_files::OPERATOR_FILE,
+};
 use std::path::PathBuf;
 use url::Url;
 
@@ -70,6 +75,21 @@ enum ConfigSub {
     },
     /// Show the addresses and configs on this device
     View {},
+    /// Add profiles for accounts derived from the mnemonic past the reserved children 0-5
+    AddDerived {
+        /// first child number to derive
+        #[clap(short, long, default_value_t = libra_wallet::derive::FIRST_UNRESERVED_CHILD)]
+        index: u64,
+        /// how many accounts to derive
+        #[clap(short('n'), long, default_value = "1")]
+        count: u64,
+        /// also add accounts which do not exist on chain yet
+        #[clap(long)]
+        include_missing: bool,
+        /// prompt for the BIP-39 style passphrase the mnemonic was set up with
+        #[clap(long)]
+        passphrase: bool,
+    },
 
     // COMMIT NOTE: we haven't used vendor tooling configs for anything.
     /// Generate validators' config file
@@ -143,6 +163,36 @@ impl ConfigCli {
                 Ok(())
             }
 
+            Some(ConfigSub::AddDerived {
+                index,
+                count,
+                include_missing,
+                passphrase,
+            }) => {
+                let mut cfg = AppCfg::load(self.path.clone())?;
+                let keys = get_keys_from_prompt()?;
+                let passphrase = if *passphrase {
+                    rpassword::read_password_from_tty(Some("Mnemonic passphrase: "))?
+                } else {
+                    String::new()
+                };
+                let (added, replaced) = add_derived_profiles(
+                    &mut cfg,
+                    &keys.mnemonic,
+                    &passphrase,
+                    *index,
+                    *count,
+                    *include_missing,
+                    self.chain_name,
+                )
+                .await?;
+                if added + replaced > 0 {
+                    cfg.save_file()?;
+                }
+                println!("added {added} profiles, replaced {replaced}");
+                Ok(())
+            }
+
             // Initialize validator configuration
             Some(ConfigSub::ValidatorInit { vfn }) => {
                 let home_dir = self.path.clone().unwrap_or_else(global_config_dir);
@@ -44,6 +44,57 @@ pub async fn reset_address_profile(
     cfg.get_profile_mut(Some(account_address.to_hex_literal()))
 }
 
+/// Derive accounts past the reserved children of the mnemonic, with its
+/// passphrase if it was set up with one, and add those found on chain as
+/// profiles. With `include_missing` accounts which
+/// are not yet on chain are added too. Returns how many profiles were added,
+/// and how many replaced a profile of the same account.
+pub async fn add_derived_profiles(
+    cfg: &mut AppCfg,
+    mnemonic: &str,
+    passphrase: &str,
+    index: u64,
+    count: u64,
+    include_missing: bool,
+    chain_name: Option<NamedChain>,
+) -> Result<(usize, usize)> {
+    let mut accounts = libra_wallet::derive::derive_accounts(mnemonic, passphrase, index, count)?;
+    let client = Client::new(cfg.pick_url(chain_name)?);
+    libra_wallet::derive::check_on_chain(&client, &mut accounts).await?;
+
+    let mut added = 0;
+    let mut replaced = 0;
+    for a in accounts {
+        let account = match (a.on_chain, include_missing) {
+            (Some(addr), _) => addr,
+            (None, true) => a.keys.account,
+            (None, false) => {
+                println!(
+                    "child {}: {} not on chain, skipping",
+                    a.child_number, a.keys.account
+                );
+                continue;
+            }
+        };
+        let profile = app_cfg::Profile::new(a.keys.auth_key, account);
+        let exists = cfg.user_profiles.iter().any(|p| p.account == account);
+        println!(
+            "child {}: {} profile {} for {}",
+            a.child_number,
+            if exists { "replacing" } else { "adding" },
+            profile.nickname,
+            account
+        );
+        cfg.maybe_add_profile(profile)?;
+        if exists {
+            replaced += 1;
+        } else {
+            added += 1;
+        }
+    }
+    Ok((added, replaced))
+}
+
 /// Remove a profile from the configuration
 pub fn remove_profile(cfg: &mut AppCfg, profile_identifier: &str) {
     // First check if the profile exists and if it's the default
@@ -74,6 +74,28 @@ pub fn add_peers_to_yaml(
 
     Ok(())
 }
+
+/// Turn on the ledger, state merkle, and epoch snapshot pruners of a node
+/// YAML, keeping `prune_window` versions. Used after pruning a DB offline,
+/// so the node keeps the DB at the same size.
+pub fn set_pruner_window_in_yaml(path: &Path, prune_window: u64) -> anyhow::Result<()> {
+    let string = std::fs::read_to_string(path)?;
+    let mut parsed: NodeConfig = serde_yaml::from_str(&string)?;
+
+    let pruner = &mut parsed.storage.storage_pruner_config;
+    pruner.ledger_pruner_config.enable = true;
+    pruner.ledger_pruner_config.prune_window = prune_window;
+    pruner.state_merkle_pruner_config.enable = true;
+    pruner.state_merkle_pruner_config.prune_window = prune_window;
+    pruner.epoch_snapshot_pruner_config.enable = true;
+    pruner.epoch_snapshot_pruner_config.prune_window = prune_window;
+
+    let ser = serde_yaml::to_string(&parsed)?;
+    std::fs::write(path, ser)?;
+
+    Ok(())
+}
+
 /// get seed peers from an upstream url
 pub async fn fetch_seed_addresses(
     url: Option<&str>,
@@ -266,3 +288,30 @@ async fn get_yaml() {
 
     let _text = std::fs::read_to_string(&p).unwrap();
 }
+
+#[test]
+fn archive_yaml_to_pruned() {
+    use std::str::FromStr;
+    // keep the TempPath, it removes the file when dropped
+    let temp = diem_temppath::TempPath::new();
+    let p = temp.path().to_owned();
+
+    let y = make_fullnode_archive_yaml(
+        Some(p.clone()),
+        Waypoint::from_str("0:95023f4d6a7e24cac3e52cad29697184db260214210b57aef3f1031ad4d8c02c")
+            .unwrap(),
+    )
+    .unwrap();
+    std::fs::write(&p, y).unwrap();
+
+    set_pruner_window_in_yaml(&p, 5_000_000).unwrap();
+
+    let parsed: NodeConfig = serde_yaml::from_str(&std::fs::read_to_string(&p).unwrap()).unwrap();
+    let pruner = parsed.storage.storage_pruner_config;
+    assert!(pruner.ledger_pruner_config.enable);
+    assert_eq!(pruner.ledger_pruner_config.prune_window, 5_000_000);
+    assert!(pruner.state_merkle_pruner_config.enable);
+    assert_eq!(pruner.epoch_snapshot_pruner_config.prune_window, 5_000_000);
+    // the rest of the config is kept
+    assert_eq!(parsed.full_node_networks.len(), 1);
+}
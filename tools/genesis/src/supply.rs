@@ -1,67 +1,45 @@
 use indicatif::ProgressBar;
 use libra_backwards_compatibility::legacy_recovery_v6::LegacyRecoveryV6;
+pub use libra_types::core_types::supply::Supply;
 use libra_types::ol_progress::OLProgress;
 use std::time::Duration;
 
-#[derive(Debug, Clone, Default)]
-pub struct Supply {
-    pub total: f64,
-    pub normal: f64,
-    pub validator: f64, // will overlap with slow wallet
-    pub slow_total: f64,
-    pub slow_locked: f64,
-    pub slow_validator_locked: f64,
-    pub slow_unlocked: f64,
-    pub donor_voice: f64,
-    pub make_whole: f64,
-    // which will compute later
-    pub split_factor: f64,
-    pub escrow_pct: f64,
-    pub epoch_reward_base_case: f64,
-    pub expected_user_balance: f64,
-    pub expected_user_ratio: f64,
-    pub expected_circulating: f64,
-    pub expected_circulating_ratio: f64,
-}
-
-impl Supply {
-    fn inc_supply(&mut self, r: &LegacyRecoveryV6) -> &mut Self {
-        // get balances
-        let user_total: f64 = match &r.balance {
-            Some(b) => b.coin as f64,
-            None => 0.0,
-        };
-        self.total += user_total;
+/// add one legacy account's balance to the supply stats
+fn inc_supply(supply: &mut Supply, r: &LegacyRecoveryV6) {
+    // get balances
+    let user_total: f64 = match &r.balance {
+        Some(b) => b.coin as f64,
+        None => 0.0,
+    };
+    supply.total += user_total;
 
-        // sum all accounts
-        if let Some(sl) = &r.slow_wallet {
-            // is it a slow wallet?
-            self.slow_total += user_total;
-            if sl.unlocked > 0 {
-                // safety check, the unlocked should always be lower than total balance
-                if user_total > sl.unlocked as f64 {
-                    self.slow_unlocked += sl.unlocked as f64;
-                    // Note: the validator may have transferred everything out, and the unlocked may not have changed
-                    let locked = user_total - sl.unlocked as f64;
-                    self.slow_locked += locked;
-                    // if this is the special case of a validator account with slow locked balance
-                    if r.val_cfg.is_some() {
-                        self.validator += user_total;
-                        self.slow_validator_locked += locked;
-                    }
-                } else {
-                    // we shouldn't have more unlocked coins than the actual balance
-                    self.slow_unlocked += user_total;
+    // sum all accounts
+    if let Some(sl) = &r.slow_wallet {
+        // is it a slow wallet?
+        supply.slow_total += user_total;
+        if sl.unlocked > 0 {
+            // safety check, the unlocked should always be lower than total balance
+            if user_total > sl.unlocked as f64 {
+                supply.slow_unlocked += sl.unlocked as f64;
+                // Note: the validator may have transferred everything out, and the unlocked may not have changed
+                let locked = user_total - sl.unlocked as f64;
+                supply.slow_locked += locked;
+                // if this is the special case of a validator account with slow locked balance
+                if r.val_cfg.is_some() {
+                    supply.validator += user_total;
+                    supply.slow_validator_locked += locked;
                 }
+            } else {
+                // we shouldn't have more unlocked coins than the actual balance
+                supply.slow_unlocked += user_total;
             }
-        } else if r.cumulative_deposits.is_some() {
-            // catches the cases of any dd wallets that were mapped to slow wallets
-            self.slow_locked += user_total;
-            self.slow_total += user_total;
-        } else {
-            self.normal += user_total;
         }
-        self
+    } else if r.cumulative_deposits.is_some() {
+        // catches the cases of any dd wallets that were mapped to slow wallets
+        supply.slow_locked += user_total;
+        supply.slow_total += user_total;
+    } else {
+        supply.normal += user_total;
     }
 }
 
@@ -73,27 +51,10 @@ pub fn populate_supply_stats_from_legacy(rec: &[LegacyRecoveryV6]) -> anyhow::Re
         .with_style(OLProgress::spinner())
         .with_message("calculating coin supply");
     pb.enable_steady_tick(Duration::from_millis(100));
-    let mut supply = Supply {
-        total: 0.0,
-        normal: 0.0,
-        validator: 0.0,
-        slow_total: 0.0,
-        slow_locked: 0.0,
-        slow_validator_locked: 0.0,
-        slow_unlocked: 0.0,
-        donor_voice: 0.0,
-        make_whole: 0.0,
-        split_factor: 0.0,
-        escrow_pct: 0.0,
-        epoch_reward_base_case: 0.0,
-        expected_user_balance: 0.0,
-        expected_user_ratio: 0.0,
-        expected_circulating: 0.0,
-        expected_circulating_ratio: 0.0,
-    };
+    let mut supply = Supply::default();
 
     rec.iter().for_each(|r| {
-        supply.inc_supply(r);
+        inc_supply(&mut supply, r);
     });
     pb.finish_and_clear();
     Ok(supply)
@@ -1,4 +1,5 @@
 //! Helper functions for querying account-related data using the Diem SDK client.
+//! Where a `version` is taken, state is read at that ledger version, `None` reads the latest state.
 
 use diem_sdk::{
     rest_client::{
@@ -32,6 +33,7 @@ pub struct AccountVouchReportData {
 pub async fn get_account_balance_libra(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<SlowWalletBalance> {
     let slow_balance_id = entry_function_id("ol_account", "balance")?;
     let request = ViewRequest {
@@ -40,7 +42,7 @@ pub async fn get_account_balance_libra(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
 
     SlowWalletBalance::from_value(res)
 }
@@ -49,8 +51,11 @@ pub async fn get_account_balance_libra(
 pub async fn get_val_config(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<ValidatorConfig> {
-    client.get_move_resource::<ValidatorConfig>(account).await
+    client
+        .get_move_resource_at_version::<ValidatorConfig>(account, version)
+        .await
 }
 
 /// Retrieves events associated with a given account.
@@ -98,6 +103,7 @@ pub async fn get_transactions(
 pub async fn is_community_wallet_migrated(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<serde_json::Value> {
     let community_wallet_migrated_id = entry_function_id("community_wallet", "qualifies")?;
     let request = ViewRequest {
@@ -106,7 +112,7 @@ pub async fn is_community_wallet_migrated(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
     Ok(json!(res))
 }
 
@@ -114,6 +120,7 @@ pub async fn is_community_wallet_migrated(
 pub async fn community_wallet_signers(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<serde_json::Value> {
     //they are empty for now
     let community_wallet_migrated_id = entry_function_id("multi_action", "get_authorities")?;
@@ -123,7 +130,7 @@ pub async fn community_wallet_signers(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
     Ok(json!(res))
 }
 
@@ -131,8 +138,11 @@ pub async fn community_wallet_signers(
 pub async fn community_wallet_scheduled_transactions(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<TxSchedule> {
-    client.get_move_resource::<TxSchedule>(account).await
+    client
+        .get_move_resource_at_version::<TxSchedule>(account, version)
+        .await
 }
 
 /// Retrieves all multi_auth actions (pending, approved, expired) for a given multi_auth account.
@@ -154,6 +164,7 @@ pub async fn multi_auth_ballots(
 pub async fn page_rank_calculate_score(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<(u64, u64, u64)> {
     let calculate_score_id = entry_function_id("page_rank_lazy", "calculate_score")?;
     let request = ViewRequest {
@@ -162,7 +173,7 @@ pub async fn page_rank_calculate_score(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
 
     // Parse the tuple response (score, max_depth_reached, accounts_processed)
     if res.len() != 3 {
@@ -209,6 +220,7 @@ pub async fn page_rank_calculate_score(
 pub async fn page_rank_get_cached_score(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<u64> {
     let get_cached_score_id = entry_function_id("page_rank_lazy", "get_cached_score")?;
     let request = ViewRequest {
@@ -217,7 +229,7 @@ pub async fn page_rank_get_cached_score(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
 
     // Parse the single u64 response
     if res.is_empty() {
@@ -240,6 +252,7 @@ pub async fn page_rank_get_cached_score(
 pub async fn vouch_limits_calculate_score_limit(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<u64> {
     let calculate_score_limit_id = entry_function_id("vouch_limits", "calculate_score_limit")?;
     let request = ViewRequest {
@@ -248,7 +261,7 @@ pub async fn vouch_limits_calculate_score_limit(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
 
     // Parse the single u64 response
     if res.is_empty() {
@@ -274,6 +287,7 @@ pub async fn vouch_limits_calculate_score_limit(
 pub async fn vouch_limits_get_vouch_limit(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<u64> {
     let get_vouch_limit_id = entry_function_id("vouch_limits", "get_vouch_limit")?;
     let request = ViewRequest {
@@ -282,7 +296,7 @@ pub async fn vouch_limits_get_vouch_limit(
         arguments: vec![account.to_string().into()],
     };
 
-    let res = client.view(&request, None).await?.into_inner();
+    let res = client.view(&request, version).await?.into_inner();
 
     // Parse the single u64 response
     if res.is_empty() {
@@ -305,11 +319,12 @@ pub async fn vouch_limits_get_vouch_limit(
 pub async fn account_vouch_report(
     client: &Client,
     account: AccountAddress,
+    version: Option<u64>,
 ) -> anyhow::Result<AccountVouchReportData> {
     let mut errors = Vec::new();
 
     // Get page rank scores
-    let cached_score = match page_rank_get_cached_score(client, account).await {
+    let cached_score = match page_rank_get_cached_score(client, account, version).await {
         Ok(score) => Some(score),
         Err(e) => {
             errors.push(format!("cached_score: {}", e));
@@ -318,7 +333,7 @@ pub async fn account_vouch_report(
     };
 
     let (fresh_score, max_depth_reached, accounts_processed) =
-        match page_rank_calculate_score(client, account).await {
+        match page_rank_calculate_score(client, account, version).await {
             Ok((score, max_depth_reached, accounts_processed)) => (
                 Some(score),
                 Some(max_depth_reached),
@@ -331,21 +346,23 @@ pub async fn account_vouch_report(
         };
 
     // Get vouch limits
-    let max_vouches_by_score = match vouch_limits_calculate_score_limit(client, account).await {
-        Ok(limit) => Some(limit),
-        Err(e) => {
-            errors.push(format!("max_vouches_by_score: {}", e));
-            None
-        }
-    };
+    let max_vouches_by_score =
+        match vouch_limits_calculate_score_limit(client, account, version).await {
+            Ok(limit) => Some(limit),
+            Err(e) => {
+                errors.push(format!("max_vouches_by_score: {}", e));
+                None
+            }
+        };
 
-    let remaining_vouches_available = match vouch_limits_get_vouch_limit(client, account).await {
-        Ok(limit) => Some(limit),
-        Err(e) => {
-            errors.push(format!("remaining_vouches_available: {}", e));
-            None
-        }
-    };
+    let remaining_vouches_available =
+        match vouch_limits_get_vouch_limit(client, account, version).await {
+            Ok(limit) => Some(limit),
+            Err(e) => {
+                errors.push(format!("remaining_vouches_available: {}", e));
+                None
+            }
+        };
 
     Ok(AccountVouchReportData {
         account: account.to_string(),
@@ -365,7 +382,7 @@ pub async fn account_vouch_report_console(
     client: &Client,
     account: AccountAddress,
 ) -> anyhow::Result<()> {
-    let report = account_vouch_report(client, account).await?;
+    let report = account_vouch_report(client, account, None).await?;
 
     println!("=== Account Vouch Report for {} ===\n", account);
 
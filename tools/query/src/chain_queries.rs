@@ -1,16 +1,17 @@
 //! chain queries
 
-use crate::query_view::{self, get_view};
-use anyhow::Context;
-use diem_sdk::rest_client::Client;
+use crate::query_view::{self, get_view, get_view_at_version};
+use anyhow::{bail, Context};
+use diem_sdk::{rest_client::Client, types::account_address::AccountAddress};
 
-/// Retrieves the current epoch from the blockchain.
-pub async fn get_epoch(client: &Client) -> anyhow::Result<u64> {
-    let res = get_view(
+/// Retrieves the epoch from the blockchain, optionally at a past ledger version.
+pub async fn get_epoch(client: &Client, version: Option<u64>) -> anyhow::Result<u64> {
+    let res = get_view_at_version(
         client,
         "0x1::reconfiguration::get_current_epoch",
         None,
         None,
+        version,
     )
     .await?;
 
@@ -49,7 +50,10 @@ pub async fn can_gov_proposal_resolve(client: &Client, id: u64) -> anyhow::Resul
     )
     .await?;
 
-    serde_json::from_value::<bool>(query_res).context("cannot parse api res")
+    serde_json::from_value::<Vec<bool>>(query_res)?
+        .into_iter()
+        .next()
+        .context("could not get a response from view function get_can_resolve")
 }
 
 // TODO: code duplication
@@ -80,12 +84,23 @@ pub async fn get_gov_proposal_votes(client: &Client, id: u64) -> anyhow::Result<
     )
     .await?;
 
-    Ok(serde_json::from_value::<Vec<u128>>(query_res)?)
+    // u128 values are returned as strings
+    serde_json::from_value::<Vec<String>>(query_res)?
+        .iter()
+        .map(|v| v.parse::<u128>().context("cannot parse vote count"))
+        .collect()
 }
 
-/// Retrieves the current blockchain height.
-pub async fn get_height(client: &Client) -> anyhow::Result<u64> {
-    let res = get_view(client, "0x1::block::get_current_block_height", None, None).await?;
+/// Retrieves the blockchain height, optionally at a past ledger version.
+pub async fn get_height(client: &Client, version: Option<u64>) -> anyhow::Result<u64> {
+    let res = get_view_at_version(
+        client,
+        "0x1::block::get_current_block_height",
+        None,
+        None,
+        version,
+    )
+    .await?;
 
     let value: Vec<String> = serde_json::from_value(res)?;
     let height = value.first().unwrap().parse::<u64>()?;
@@ -101,3 +116,63 @@ pub async fn epoch_over_can_trigger(client: &Client) -> anyhow::Result<bool> {
 
     Ok(value[0])
 }
+
+/// Finds the last ledger version of an epoch. The reconfiguration which emits
+/// the NewEpochEvent of the following epoch has already crossed the boundary,
+/// so the epoch's final state is at the version just before it.
+pub async fn get_epoch_ending_version(client: &Client, epoch: u64) -> anyhow::Result<u64> {
+    // genesis emits the event for epoch 1 with sequence number 0, so the event
+    // which closes epoch N has sequence number N.
+    let events = client
+        .get_account_events(
+            AccountAddress::ONE,
+            "0x1::reconfiguration::Configuration",
+            "events",
+            Some(epoch),
+            Some(1),
+        )
+        .await?
+        .into_inner();
+
+    let event = events.first().with_context(|| {
+        format!("no reconfiguration event found closing epoch {epoch}, has the epoch ended?")
+    })?;
+
+    let next_epoch: u64 = event
+        .data
+        .get("epoch")
+        .and_then(|e| e.as_str())
+        .context("cannot parse NewEpochEvent")?
+        .parse()?;
+
+    if next_epoch != epoch + 1 {
+        bail!(
+            "reconfiguration event sequence does not match epochs, expected epoch {} but found {}",
+            epoch + 1,
+            next_epoch
+        );
+    }
+
+    let reconfig_version: u64 = event.version.into();
+    reconfig_version
+        .checked_sub(1)
+        .with_context(|| format!("epoch {epoch} ended at genesis, it has no state of its own"))
+}
+
+/// Checks that the node still holds state for a ledger version.
+/// Errors if the version was pruned, or is ahead of the node.
+pub async fn check_version_available(client: &Client, version: u64) -> anyhow::Result<()> {
+    let index = client.get_index().await?.into_inner();
+    let oldest: u64 = index.oldest_ledger_version.into();
+    let latest: u64 = index.ledger_version.into();
+
+    if version < oldest {
+        bail!(
+            "version {version} has been pruned on this node, the oldest version available is {oldest}. Use an archive node to query historical state"
+        );
+    }
+    if version > latest {
+        bail!("version {version} is ahead of this node's latest version {latest}");
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Inspect on-chain governance proposals, and check compiled proposal
+//! scripts against the execution hash voters are approving.
+
+use crate::chain_queries::{
+    can_gov_proposal_resolve, get_next_governance_proposal_id, is_gov_proposal_resolved,
+};
+use anyhow::{bail, Context};
+use diem_sdk::{crypto::HashValue, rest_client::Client, types::account_address::AccountAddress};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+const VOTING_FORUM: &str = "0x1::voting::VotingForum<0x1::governance_proposal::GovernanceProposal>";
+const PROPOSAL: &str = "0x1::voting::Proposal<0x1::governance_proposal::GovernanceProposal>";
+
+/// A governance proposal as stored in the voting forum
+#[derive(Debug, Serialize)]
+pub struct ProposalInfo {
+    pub id: u64,
+    pub proposer: AccountAddress,
+    pub yes_votes: u128,
+    pub no_votes: u128,
+    pub min_vote_threshold: u128,
+    pub early_resolution_vote_threshold: Option<u128>,
+    /// whether enough votes were cast in total to reach the threshold
+    pub meets_threshold: bool,
+    pub creation_time_secs: u64,
+    pub expiration_secs: u64,
+    pub metadata_location: Option<String>,
+    pub metadata_hash: Option<String>,
+    /// hash of the script which can resolve the proposal
+    pub execution_hash: String,
+    pub is_resolved: bool,
+    pub can_resolve: bool,
+}
+
+/// Result of checking a local compiled proposal against the chain
+#[derive(Debug, Serialize)]
+pub struct ScriptCheck {
+    /// sha3 of the compiled script.mv
+    pub script_hash: String,
+    /// contents of the script_sha3 file written at build time, if any
+    pub script_sha3_file: Option<String>,
+    pub onchain_execution_hash: String,
+    pub matches_onchain: bool,
+}
+
+/// Lists every proposal in the governance voting forum.
+pub async fn list_proposals(client: &Client) -> anyhow::Result<Vec<ProposalInfo>> {
+    let next_id = get_next_governance_proposal_id(client).await?;
+    let handle = proposals_table_handle(client).await?;
+    let mut list = vec![];
+    for id in 0..next_id {
+        list.push(read_proposal(client, handle, id).await?);
+    }
+    Ok(list)
+}
+
+/// Reads a proposal from the voting forum table, and its resolution status.
+pub async fn get_proposal(client: &Client, id: u64) -> anyhow::Result<ProposalInfo> {
+    let handle = proposals_table_handle(client).await?;
+    read_proposal(client, handle, id).await
+}
+
+/// The handle of the voting forum's table of proposals
+async fn proposals_table_handle(client: &Client) -> anyhow::Result<AccountAddress> {
+    let forum = client
+        .get_account_resource(AccountAddress::ONE, VOTING_FORUM)
+        .await?
+        .into_inner()
+        .context("governance voting forum not found")?;
+
+    serde_json::from_value(forum.data["proposals"]["handle"].clone())
+        .context("cannot parse proposals table handle")
+}
+
+async fn read_proposal(
+    client: &Client,
+    handle: AccountAddress,
+    id: u64,
+) -> anyhow::Result<ProposalInfo> {
+    let p = client
+        .get_table_item(handle, "u64", PROPOSAL, id.to_string())
+        .await
+        .with_context(|| format!("proposal {id} not found"))?
+        .into_inner();
+
+    let yes_votes = parse_num::<u128>(&p["yes_votes"])?;
+    let no_votes = parse_num::<u128>(&p["no_votes"])?;
+    let min_vote_threshold = parse_num::<u128>(&p["min_vote_threshold"])?;
+
+    // move Option is a vector of zero or one items
+    let early = &p["early_resolution_vote_threshold"]["vec"];
+    let early_resolution_vote_threshold = match early.as_array().and_then(|v| v.first()) {
+        Some(v) => Some(parse_num::<u128>(v)?),
+        None => None,
+    };
+
+    Ok(ProposalInfo {
+        id,
+        proposer: serde_json::from_value(p["proposer"].clone())?,
+        yes_votes,
+        no_votes,
+        min_vote_threshold,
+        early_resolution_vote_threshold,
+        meets_threshold: yes_votes + no_votes >= min_vote_threshold,
+        creation_time_secs: parse_num(&p["creation_time_secs"])?,
+        expiration_secs: parse_num(&p["expiration_secs"])?,
+        metadata_location: metadata_value(&p, "metadata_location"),
+        metadata_hash: metadata_value(&p, "metadata_hash"),
+        execution_hash: p["execution_hash"]
+            .as_str()
+            .context("cannot parse execution hash")?
+            .trim_start_matches("0x")
+            .to_string(),
+        is_resolved: is_gov_proposal_resolved(client, id).await?,
+        can_resolve: can_gov_proposal_resolve(client, id).await?,
+    })
+}
+
+/// Hashes the script.mv in a compiled proposal directory, and compares it
+/// with the execution hash registered on chain.
+pub fn check_proposal_script(
+    proposal_script_dir: &Path,
+    onchain_execution_hash: &str,
+) -> anyhow::Result<ScriptCheck> {
+    let script_path = proposal_script_dir.join("script.mv");
+    if !script_path.exists() {
+        bail!("cannot find compiled script at {}", script_path.display());
+    }
+    let bytes = std::fs::read(&script_path)?;
+    let script_hash = HashValue::sha3_256_of(&bytes).to_hex();
+
+    let script_sha3_file = std::fs::read_to_string(proposal_script_dir.join("script_sha3"))
+        .ok()
+        .map(|s| s.trim().to_string());
+
+    Ok(ScriptCheck {
+        matches_onchain: script_hash == onchain_execution_hash,
+        script_hash,
+        script_sha3_file,
+        onchain_execution_hash: onchain_execution_hash.to_string(),
+    })
+}
+
+/// SimpleMap<String, vector<u8>> is a list of key and hex encoded value pairs
+fn metadata_value(proposal: &Value, key: &str) -> Option<String> {
+    let entry = proposal["metadata"]["data"]
+        .as_array()?
+        .iter()
+        .find(|e| e["key"].as_str() == Some(key))?;
+    let bytes = hex::decode(entry["value"].as_str()?.trim_start_matches("0x")).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// the API returns u64 and u128 as strings
+fn parse_num<T: std::str::FromStr>(v: &Value) -> anyhow::Result<T> {
+    v.as_str()
+        .and_then(|s| s.parse::<T>().ok())
+        .with_context(|| format!("cannot parse number from {v}"))
+}
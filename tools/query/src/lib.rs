@@ -1,5 +1,9 @@
 pub mod account_queries;
 pub mod chain_queries;
+pub mod governance_queries;
 pub mod query_cli;
 pub mod query_type;
 pub mod query_view;
+pub mod supply;
+pub mod vouch_graph;
+pub mod whois;
@@ -1,8 +1,11 @@
 use std::path::PathBuf;
 
-use crate::query_type::QueryType;
+use crate::{
+    chain_queries::{check_version_available, get_epoch_ending_version},
+    query_type::QueryType,
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 use libra_types::{
     core_types::app_cfg::AppCfg, exports::Client, type_extensions::client_ext::ClientExt,
@@ -26,10 +29,25 @@ pub struct QueryCli {
     /// Otherwise will default to what is in the config file
     #[clap(short, long)]
     pub url: Option<Url>,
+
+    /// optional, query the state at this ledger version instead of the latest.
+    /// Requires a node which has not pruned that version, e.g. an archive node
+    #[clap(long, global = true, conflicts_with = "at_epoch")]
+    pub at_version: Option<u64>,
+
+    /// optional, query the state at the last version of this epoch,
+    /// just before the reconfiguration into the next one
+    #[clap(long, global = true)]
+    pub at_epoch: Option<u64>,
 }
 
 impl QueryCli {
     pub async fn run(&self) -> Result<()> {
+        if (self.at_version.is_some() || self.at_epoch.is_some()) && !self.subcommand.is_versioned()
+        {
+            bail!("--at-version and --at-epoch cannot be used with events, transactions or governance queries");
+        }
+
         // Query requires a URL for upstream
         // the user should set one explicitly
         // Otherwise the tool will try to fetch the libra config from the
@@ -48,7 +66,22 @@ impl QueryCli {
             Client::default().await?
         };
 
-        let res = self.subcommand.query_to_json(&client).await?;
+        let version = if let Some(v) = self.at_version {
+            Some(v)
+        } else if let Some(e) = self.at_epoch {
+            Some(get_epoch_ending_version(&client, e).await?)
+        } else {
+            None
+        };
+
+        if let Some(v) = version {
+            check_version_available(&client, v).await?;
+        }
+
+        let res = self
+            .subcommand
+            .query_to_json_at_version(&client, version)
+            .await?;
         let pretty_json = serde_json::to_string_pretty(&res)?;
         println!("{}", pretty_json);
 
@@ -4,8 +4,12 @@ use crate::{
         get_account_balance_libra, get_events, get_transactions, get_val_config,
         is_community_wallet_migrated,
     },
-    chain_queries::{get_epoch, get_height},
-    query_view::get_view,
+    chain_queries::{get_epoch, get_epoch_ending_version, get_height},
+    governance_queries::{check_proposal_script, get_proposal, list_proposals},
+    query_view::get_view_at_version,
+    supply::{compare_supply, get_supply},
+    vouch_graph::explore_vouch_graph,
+    whois::whois,
 };
 use anyhow::{bail, Context, Result};
 use diem_api_types::Transaction;
@@ -14,6 +18,7 @@ use diem_sdk::{rest_client::Client, types::account_address::AccountAddress};
 use indoc::indoc;
 use libra_types::{exports::AuthenticationKey, type_extensions::client_ext::ClientExt};
 use serde_json::json;
+use std::path::PathBuf;
 
 #[derive(Debug, clap::Subcommand)]
 pub enum QueryType {
@@ -79,6 +84,20 @@ pub enum QueryType {
     LookupAddress {
         auth_key: AuthenticationKey, // we use account address to parse, because that's the format needed to lookup users. AuthKeys and AccountAddress are the same formats.
     },
+    /// Look up an account by legacy address, address, authentication key, or public key
+    Whois {
+        /// a 16 byte legacy address, or a 32 byte address, auth key, or ed25519 public key, in hex
+        identifier: String,
+    },
+    /// Coin supply breakdown: total, circulating, locked, community wallets, escrow, pledges and burns
+    Supply {
+        #[clap(long)]
+        /// optional, compare with the supply at the end of this epoch, to show inflation and burn rates
+        compare_epoch: Option<u64>,
+    },
+    /// Inspect governance proposals
+    #[clap(subcommand)]
+    Governance(GovernanceQuery),
     /// Network block height
     BlockHeight,
     /// How far behind the local is from the upstream nodes
@@ -130,13 +149,65 @@ pub enum QueryType {
         /// account to generate vouch report for
         account: AccountAddress,
     },
+    /// Walk the vouch graph around an account, showing inbound and outbound vouches and the shortest paths to the root of trust
+    VouchGraph {
+        /// account to walk the vouch graph from
+        account: AccountAddress,
+        #[clap(short, long, default_value = "2")]
+        /// how many hops away from the account to walk
+        depth: u64,
+        #[clap(short, long, default_value = "5")]
+        /// flag vouches which expire within this many epochs
+        expiring_within: u64,
+        #[clap(long)]
+        /// optional, also write the graph to a Graphviz DOT file
+        dot_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum GovernanceQuery {
+    /// List all proposals with their votes and status
+    List,
+    /// Show a proposal's votes versus threshold, expiry, metadata and execution hash
+    Show {
+        /// On-chain ID of the proposal
+        proposal_id: u64,
+        #[clap(short = 'd', long)]
+        /// optional, path to a compiled proposal script directory to check against the execution hash
+        proposal_script_dir: Option<PathBuf>,
+    },
 }
 
 impl QueryType {
     pub async fn query_to_json(&self, client: &Client) -> Result<serde_json::Value> {
+        self.query_to_json_at_version(client, None).await
+    }
+
+    /// Whether the query reads state which can be pinned to a ledger version.
+    /// Events, transaction history and governance proposals cannot.
+    pub fn is_versioned(&self) -> bool {
+        !matches!(
+            self,
+            QueryType::Events { .. } | QueryType::Txs { .. } | QueryType::Governance(_)
+        )
+    }
+
+    /// Runs the query against the state at a given ledger version.
+    /// A `None` version queries the latest state. Errors if a version is
+    /// given for a query which is not versioned.
+    pub async fn query_to_json_at_version(
+        &self,
+        client: &Client,
+        version: Option<u64>,
+    ) -> Result<serde_json::Value> {
+        if version.is_some() && !self.is_versioned() {
+            bail!("events, transactions and governance queries cannot be run at a past version");
+        }
+
         match self {
             QueryType::Balance { account } => {
-                let res = get_account_balance_libra(client, *account).await?;
+                let res = get_account_balance_libra(client, *account, version).await?;
                 Ok(json!(res.scaled()))
             }
             QueryType::View {
@@ -144,13 +215,19 @@ impl QueryType {
                 type_args,
                 args,
             } => {
-                let res =
-                    get_view(client, function_id, type_args.to_owned(), args.to_owned()).await?;
+                let res = get_view_at_version(
+                    client,
+                    function_id,
+                    type_args.to_owned(),
+                    args.to_owned(),
+                    version,
+                )
+                .await?;
                 let json = json!({ "body": res });
                 Ok(json)
             }
             QueryType::Epoch => {
-                let num = get_epoch(client).await?;
+                let num = get_epoch(client, version).await?;
                 let json = json!({
                   "epoch": num,
                 });
@@ -163,13 +240,26 @@ impl QueryType {
 
                 Ok(json!({ "address": addr }))
             }
+            QueryType::Whois { identifier } => {
+                let report = whois(client, identifier, version).await?;
+                Ok(json!(report))
+            }
             QueryType::Resource {
                 account,
                 resource_path_string,
             } => {
-                let res = client
-                    .get_account_resource(*account, resource_path_string)
-                    .await?;
+                let res = match version {
+                    Some(v) => {
+                        client
+                            .get_account_resource_at_version(*account, resource_path_string, v)
+                            .await?
+                    }
+                    None => {
+                        client
+                            .get_account_resource(*account, resource_path_string)
+                            .await?
+                    }
+                };
 
                 if let Some(r) = res.inner() {
                     Ok(r.data.clone())
@@ -178,7 +268,7 @@ impl QueryType {
                 }
             }
             QueryType::ValConfig { account } => {
-                let res = get_val_config(client, *account).await?;
+                let res = get_val_config(client, *account, version).await?;
 
                 // make this readable, turn the network address into a string
                 Ok(json!({
@@ -188,8 +278,36 @@ impl QueryType {
                   "validator_index": res.validator_index,
                 }))
             }
+            QueryType::Supply { compare_epoch } => {
+                if let Some(e) = compare_epoch {
+                    let from_version = get_epoch_ending_version(client, *e).await?;
+                    let res = compare_supply(client, from_version, version).await?;
+                    Ok(json!(res))
+                } else {
+                    let res = get_supply(client, version).await?;
+                    Ok(json!(res))
+                }
+            }
+            QueryType::Governance(GovernanceQuery::List) => {
+                let res = list_proposals(client).await?;
+                Ok(json!({ "proposals": res }))
+            }
+            QueryType::Governance(GovernanceQuery::Show {
+                proposal_id,
+                proposal_script_dir,
+            }) => {
+                let proposal = get_proposal(client, *proposal_id).await?;
+                let script_check = match proposal_script_dir {
+                    Some(dir) => Some(check_proposal_script(dir, &proposal.execution_hash)?),
+                    None => None,
+                };
+                Ok(json!({
+                    "proposal": proposal,
+                    "script_check": script_check,
+                }))
+            }
             QueryType::BlockHeight => {
-                let height = get_height(client).await?;
+                let height = get_height(client, version).await?;
                 Ok(json!({ "BlockHeight": height }))
             }
             QueryType::Events {
@@ -227,22 +345,26 @@ impl QueryType {
                 Ok(json!({ "transactions": prune_res }))
             }
             QueryType::ComWalletMigrated { account } => {
-                let res = is_community_wallet_migrated(client, *account).await?;
+                let res = is_community_wallet_migrated(client, *account, version).await?;
                 Ok(json!({ "migrated": res }))
             }
             QueryType::ComWalletSigners { account } => {
                 // Wont work at the moment as there is no community wallet that with governace structure
-                let _res = community_wallet_signers(client, *account).await?;
+                let _res = community_wallet_signers(client, *account, version).await?;
                 Ok(json!({ "signers": "None"}))
             }
             QueryType::ComWalletPendTransactions { account } => {
                 // Wont work at the moment as there is no community wallet migrated
-                let _res = community_wallet_scheduled_transactions(client, *account).await?;
+                let _res =
+                    community_wallet_scheduled_transactions(client, *account, version).await?;
                 Ok(json!({ "pending_transactions": "None" }))
             }
             QueryType::Annotate { account } => {
                 let dbgger = DiemDebugger::rest_client(client.clone())?;
-                let version = dbgger.get_latest_version().await?;
+                let version = match version {
+                    Some(v) => v,
+                    None => dbgger.get_latest_version().await?,
+                };
                 let blob = dbgger
                     .annotate_account_state_at_version(account.to_owned(), version)
                     .await?;
@@ -254,10 +376,24 @@ impl QueryType {
             }
             QueryType::VouchReport { account } => {
                 // Get the structured vouch report data
-                let report_data = account_vouch_report(client, *account).await?;
+                let report_data = account_vouch_report(client, *account, version).await?;
                 // Return the data as JSON
                 Ok(json!(report_data))
             }
+            QueryType::VouchGraph {
+                account,
+                depth,
+                expiring_within,
+                dot_file,
+            } => {
+                let graph =
+                    explore_vouch_graph(client, *account, *depth, *expiring_within, version)
+                        .await?;
+                if let Some(p) = dot_file {
+                    std::fs::write(p, graph.to_dot())?;
+                }
+                Ok(json!(graph))
+            }
             _ => {
                 bail!(
                     "Not implemented for type: {:?}\n Ground control to Major Tom.",
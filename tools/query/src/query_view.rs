@@ -12,6 +12,20 @@ pub async fn get_view(
     client.view_ext(function_id, type_args, args).await
 }
 
+/// Executes a view function against the state at a given ledger version.
+/// A `None` version uses the latest state.
+pub async fn get_view_at_version(
+    client: &Client,
+    function_id: &str,
+    type_args: Option<String>,
+    args: Option<String>,
+    version: Option<u64>,
+) -> Result<Value> {
+    client
+        .view_ext_at_version(function_id, type_args, args, version)
+        .await
+}
+
 // helper to turn a serde_json value to string
 // TODO: must be a better way
 pub fn display_view(res: Vec<Value>) -> Result<String> {
@@ -0,0 +1,136 @@
+//! Coin supply breakdown from the on-chain supply module.
+
+use crate::{chain_queries::get_epoch, query_view::get_view_at_version};
+use anyhow::{bail, Context};
+use diem_sdk::rest_client::Client;
+use libra_types::core_types::supply::Supply;
+use serde::{Deserialize, Serialize};
+
+/// The supply at one point of the chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupplyReport {
+    pub epoch: u64,
+    /// the ledger version queried, `None` is the latest state
+    pub version: Option<u64>,
+    pub supply: Supply,
+}
+
+/// How the supply moved between two epochs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupplyComparison {
+    pub from: SupplyReport,
+    pub to: SupplyReport,
+    pub epochs_elapsed: u64,
+    pub total_change: f64,
+    pub circulating_change: f64,
+    pub burned: f64,
+    pub total_change_per_epoch: f64,
+    pub circulating_change_per_epoch: f64,
+    pub burned_per_epoch: f64,
+}
+
+/// Collects the supply breakdown from the supply, pledge and escrow views.
+pub async fn get_supply(client: &Client, version: Option<u64>) -> anyhow::Result<SupplyReport> {
+    // the pledge amount is pledge_accounts::get_pledge_supply
+    let stats = view_u64s(client, "0x1::supply::get_stats", version).await?;
+    let [total, slow_locked, donor_voice, pledge, all_unlocked] = stats[..] else {
+        bail!(
+            "expected five values from supply::get_stats, got {}",
+            stats.len()
+        );
+    };
+
+    let burn = view_u64s(client, "0x1::supply::get_lifetime_burn", version).await?;
+    let [burned, recycled] = burn[..] else {
+        bail!("expected two values from supply::get_lifetime_burn");
+    };
+
+    let circulating = view_u64(client, "0x1::supply::get_circulating", version).await?;
+    let max_supply = view_u64(client, "0x1::supply::get_max_supply", version).await?;
+    let infra_escrow = view_u64(client, "0x1::infra_escrow::infra_escrow_balance", version).await?;
+
+    let supply = Supply {
+        total: total as f64,
+        slow_locked: slow_locked as f64,
+        donor_voice: donor_voice as f64,
+        pledge: pledge as f64,
+        all_unlocked: all_unlocked as f64,
+        burned: burned as f64,
+        recycled: recycled as f64,
+        circulating: circulating as f64,
+        max_supply: max_supply as f64,
+        infra_escrow: infra_escrow as f64,
+        ..Default::default()
+    };
+
+    Ok(SupplyReport {
+        epoch: get_epoch(client, version).await?,
+        version,
+        supply,
+    })
+}
+
+/// Compares the supply at two ledger versions, and the rates of change per epoch.
+pub async fn compare_supply(
+    client: &Client,
+    from_version: u64,
+    to_version: Option<u64>,
+) -> anyhow::Result<SupplyComparison> {
+    let from = get_supply(client, Some(from_version)).await?;
+    let to = get_supply(client, to_version).await?;
+
+    if to.epoch < from.epoch {
+        bail!(
+            "cannot compare backwards, epoch {} is before epoch {}",
+            to.epoch,
+            from.epoch
+        );
+    }
+
+    let epochs_elapsed = to.epoch - from.epoch;
+    let total_change = to.supply.total - from.supply.total;
+    let circulating_change = to.supply.circulating - from.supply.circulating;
+    let burned = to.supply.burned - from.supply.burned;
+    let per_epoch = |v: f64| {
+        if epochs_elapsed == 0 {
+            0.0
+        } else {
+            v / epochs_elapsed as f64
+        }
+    };
+
+    Ok(SupplyComparison {
+        epochs_elapsed,
+        total_change,
+        circulating_change,
+        burned,
+        total_change_per_epoch: per_epoch(total_change),
+        circulating_change_per_epoch: per_epoch(circulating_change),
+        burned_per_epoch: per_epoch(burned),
+        from,
+        to,
+    })
+}
+
+/// views return u64 values as strings
+async fn view_u64s(
+    client: &Client,
+    function_id: &str,
+    version: Option<u64>,
+) -> anyhow::Result<Vec<u64>> {
+    let res = get_view_at_version(client, function_id, None, None, version).await?;
+    let values: Vec<String> = serde_json::from_value(res)
+        .with_context(|| format!("cannot parse response from {function_id}"))?;
+    values
+        .iter()
+        .map(|v| v.parse::<u64>().map_err(anyhow::Error::from))
+        .collect()
+}
+
+async fn view_u64(client: &Client, function_id: &str, version: Option<u64>) -> anyhow::Result<u64> {
+    view_u64s(client, function_id, version)
+        .await?
+        .first()
+        .copied()
+        .with_context(|| format!("no value returned from {function_id}"))
+}
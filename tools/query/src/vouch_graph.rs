@@ -0,0 +1,306 @@
+//! Explore the vouch graph around an account.
+//! Walks received and given vouches outward to a depth, and finds the
+//! shortest chains of vouches which connect the account to the root of trust.
+
+use crate::{chain_queries::get_epoch, query_view::get_view_at_version};
+use anyhow::Context;
+use diem_sdk::{rest_client::Client, types::account_address::AccountAddress};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Nominal vouch lifetime, mirrors EXPIRATION_ELAPSED_EPOCHS in vouch.move
+pub const VOUCH_EXPIRATION_EPOCHS: u64 = 45;
+
+/// A vouch given by `from` to `to`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VouchEdge {
+    pub from: AccountAddress,
+    pub to: AccountAddress,
+    pub epoch_vouched: u64,
+    pub expires_epoch: u64,
+}
+
+/// An account found while walking the graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VouchNode {
+    pub address: AccountAddress,
+    /// hops from the account the graph was built around
+    pub depth: u64,
+    pub is_root_of_trust: bool,
+}
+
+/// The neighbourhood of vouches around one account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VouchGraph {
+    pub account: AccountAddress,
+    pub current_epoch: u64,
+    pub max_depth: u64,
+    pub roots_of_trust: Vec<AccountAddress>,
+    pub nodes: Vec<VouchNode>,
+    pub edges: Vec<VouchEdge>,
+    /// vouches received by the account
+    pub inbound: Vec<VouchEdge>,
+    /// vouches given by the account
+    pub outbound: Vec<VouchEdge>,
+    /// vouches to or from the account which expire within the warning window
+    pub expiring_soon: Vec<VouchEdge>,
+    /// shortest chains of vouches from a root of trust down to the account,
+    /// each path starts at a root and ends at the account
+    pub paths_to_root: Vec<Vec<AccountAddress>>,
+}
+
+/// Walks the vouch graph outward from `account` up to `max_depth` hops.
+/// Vouches whose nominal expiry is within `expiring_within` epochs are flagged.
+pub async fn explore_vouch_graph(
+    client: &Client,
+    account: AccountAddress,
+    max_depth: u64,
+    expiring_within: u64,
+    version: Option<u64>,
+) -> anyhow::Result<VouchGraph> {
+    let current_epoch = get_epoch(client, version).await?;
+    let roots = get_roots_of_trust(client, version).await?;
+    let root_set: BTreeSet<AccountAddress> = roots.iter().cloned().collect();
+
+    let mut depths: BTreeMap<AccountAddress, u64> = BTreeMap::new();
+    let mut edges: BTreeSet<VouchEdge> = BTreeSet::new();
+    let mut queue = VecDeque::new();
+
+    depths.insert(account, 0);
+    queue.push_back(account);
+
+    while let Some(addr) = queue.pop_front() {
+        let depth = depths[&addr];
+        // the outer ring of accounts is listed but not expanded
+        if depth >= max_depth {
+            continue;
+        }
+
+        let (received, received_epochs) = get_received_vouches(client, addr, version).await?;
+        let (given, given_epochs) = get_given_vouches(client, addr, version).await?;
+
+        let inbound = received
+            .into_iter()
+            .zip(received_epochs)
+            .map(|(from, e)| make_edge(from, addr, e));
+        let outbound = given
+            .into_iter()
+            .zip(given_epochs)
+            .map(|(to, e)| make_edge(addr, to, e));
+
+        for edge in inbound.chain(outbound) {
+            let neighbour = if edge.from == addr {
+                edge.to
+            } else {
+                edge.from
+            };
+            if !depths.contains_key(&neighbour) {
+                depths.insert(neighbour, depth + 1);
+                queue.push_back(neighbour);
+            }
+            edges.insert(edge);
+        }
+    }
+
+    let edges: Vec<VouchEdge> = edges.into_iter().collect();
+
+    let inbound: Vec<VouchEdge> = edges.iter().filter(|e| e.to == account).cloned().collect();
+    let outbound: Vec<VouchEdge> = edges
+        .iter()
+        .filter(|e| e.from == account)
+        .cloned()
+        .collect();
+    let expiring_soon = inbound
+        .iter()
+        .chain(outbound.iter())
+        .filter(|e| e.expires_epoch <= current_epoch + expiring_within)
+        .cloned()
+        .collect();
+
+    let paths_to_root = shortest_paths_to_roots(account, &edges, &root_set);
+
+    let nodes = depths
+        .into_iter()
+        .map(|(address, depth)| VouchNode {
+            address,
+            depth,
+            is_root_of_trust: root_set.contains(&address),
+        })
+        .collect();
+
+    Ok(VouchGraph {
+        account,
+        current_epoch,
+        max_depth,
+        roots_of_trust: roots,
+        nodes,
+        edges,
+        inbound,
+        outbound,
+        expiring_soon,
+        paths_to_root,
+    })
+}
+
+impl VouchGraph {
+    /// Renders the graph in Graphviz DOT format.
+    /// Roots of trust are filled, the queried account is outlined.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph vouches {\n  rankdir=LR;\n");
+        for n in &self.nodes {
+            let mut attrs = vec![format!("label=\"{}\"", n.address.short_str_lossless())];
+            if n.is_root_of_trust {
+                attrs.push("style=filled".to_string());
+                attrs.push("fillcolor=gold".to_string());
+            }
+            if n.address == self.account {
+                attrs.push("penwidth=3".to_string());
+            }
+            out.push_str(&format!("  \"{}\" [{}];\n", n.address, attrs.join(", ")));
+        }
+        for e in &self.edges {
+            let mut attrs = vec![format!("label=\"{}\"", e.epoch_vouched)];
+            if self.expiring_soon.contains(e) {
+                attrs.push("color=red".to_string());
+            }
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\" [{}];\n",
+                e.from,
+                e.to,
+                attrs.join(", ")
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn make_edge(from: AccountAddress, to: AccountAddress, epoch_vouched: u64) -> VouchEdge {
+    VouchEdge {
+        from,
+        to,
+        epoch_vouched,
+        expires_epoch: epoch_vouched + VOUCH_EXPIRATION_EPOCHS,
+    }
+}
+
+/// Follows received vouches backwards from the account, breadth first, and
+/// returns the shortest path from each reachable root of trust.
+fn shortest_paths_to_roots(
+    account: AccountAddress,
+    edges: &[VouchEdge],
+    roots: &BTreeSet<AccountAddress>,
+) -> Vec<Vec<AccountAddress>> {
+    let mut vouchers_of: HashMap<AccountAddress, Vec<AccountAddress>> = HashMap::new();
+    for e in edges {
+        vouchers_of.entry(e.to).or_default().push(e.from);
+    }
+
+    // each visited account points to the account it vouched for on the way down
+    let mut next_hop: HashMap<AccountAddress, AccountAddress> = HashMap::new();
+    let mut queue = VecDeque::from([account]);
+    let mut found = vec![];
+
+    while let Some(addr) = queue.pop_front() {
+        if roots.contains(&addr) {
+            found.push(addr);
+        }
+        for v in vouchers_of.get(&addr).into_iter().flatten() {
+            if *v != account && !next_hop.contains_key(v) {
+                next_hop.insert(*v, addr);
+                queue.push_back(*v);
+            }
+        }
+    }
+
+    found
+        .into_iter()
+        .map(|root| {
+            let mut path = vec![root];
+            let mut cursor = root;
+            while let Some(n) = next_hop.get(&cursor) {
+                path.push(*n);
+                cursor = *n;
+            }
+            path
+        })
+        .collect()
+}
+
+/// The current root of trust set, as published at the framework registry.
+pub async fn get_roots_of_trust(
+    client: &Client,
+    version: Option<u64>,
+) -> anyhow::Result<Vec<AccountAddress>> {
+    let res = get_view_at_version(
+        client,
+        "0x1::root_of_trust::get_current_roots_at_registry",
+        None,
+        Some("0x1".to_string()),
+        version,
+    )
+    .await?;
+
+    let (roots,): (Vec<AccountAddress>,) =
+        serde_json::from_value(res).context("cannot parse root of trust list")?;
+    Ok(roots)
+}
+
+/// Vouches received by an account, with the epoch each was given.
+pub async fn get_received_vouches(
+    client: &Client,
+    account: AccountAddress,
+    version: Option<u64>,
+) -> anyhow::Result<(Vec<AccountAddress>, Vec<u64>)> {
+    let res = get_view_at_version(
+        client,
+        "0x1::vouch::get_received_vouches",
+        None,
+        Some(account.to_string()),
+        version,
+    )
+    .await?;
+    parse_vouch_list(res)
+}
+
+/// Vouches given by an account, with the epoch each was given.
+/// Accounts without vouch state have given none.
+pub async fn get_given_vouches(
+    client: &Client,
+    account: AccountAddress,
+    version: Option<u64>,
+) -> anyhow::Result<(Vec<AccountAddress>, Vec<u64>)> {
+    let is_init = get_view_at_version(
+        client,
+        "0x1::vouch::is_init",
+        None,
+        Some(account.to_string()),
+        version,
+    )
+    .await?;
+    let (is_init,): (bool,) = serde_json::from_value(is_init)?;
+    if !is_init {
+        return Ok((vec![], vec![]));
+    }
+
+    let res = get_view_at_version(
+        client,
+        "0x1::vouch::get_given_vouches",
+        None,
+        Some(account.to_string()),
+        version,
+    )
+    .await?;
+    parse_vouch_list(res)
+}
+
+/// views return the u64 epochs as strings
+fn parse_vouch_list(res: serde_json::Value) -> anyhow::Result<(Vec<AccountAddress>, Vec<u64>)> {
+    let (addrs, epochs): (Vec<AccountAddress>, Vec<String>) =
+        serde_json::from_value(res).context("cannot parse vouch list")?;
+    let epochs = epochs
+        .iter()
+        .map(|e| e.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((addrs, epochs))
+}
@@ -0,0 +1,199 @@
+//! Look up an account from any identifier a user may hold: a legacy (v5)
+//! 16 byte address, a full address, an authentication key, or an ed25519
+//! public key.
+
+use crate::{account_queries::get_account_balance_libra, query_view::get_view_at_version};
+use anyhow::{bail, Context};
+use diem_sdk::{rest_client::Client, types::account_address::AccountAddress};
+use libra_types::{
+    exports::{AuthenticationKey, Ed25519PublicKey},
+    move_resource::gas_coin::LibraBalanceDisplay,
+    type_extensions::client_ext::is_api_error,
+};
+use serde::Serialize;
+
+/// How the input was interpreted when it resolved to an account
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentifierKind {
+    LegacyAddress,
+    Address,
+    AuthKey,
+    PublicKey,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhoisReport {
+    /// the identifier as given
+    pub input: String,
+    /// the interpretation of the input which found an account
+    pub matched_as: Option<IdentifierKind>,
+    pub exists: bool,
+    pub address: Option<AccountAddress>,
+    /// the address in the 16 byte format used before V7, if it has one
+    pub legacy_address: Option<String>,
+    pub authentication_key: Option<String>,
+    /// whether the current authentication key differs from the address
+    pub rotated: Option<bool>,
+    pub is_slow_wallet: Option<bool>,
+    pub balance: Option<LibraBalanceDisplay>,
+}
+
+/// Normalizes the input and resolves the originating address on chain.
+/// A 32 byte input is tried as an address, then an authentication key,
+/// then a public key.
+pub async fn whois(
+    client: &Client,
+    input: &str,
+    version: Option<u64>,
+) -> anyhow::Result<WhoisReport> {
+    let trimmed = input.trim().trim_start_matches("0x");
+    let bytes = hex::decode(trimmed).context("identifier is not valid hex")?;
+
+    let mut report = WhoisReport {
+        input: input.to_string(),
+        matched_as: None,
+        exists: false,
+        address: None,
+        legacy_address: None,
+        authentication_key: None,
+        rotated: None,
+        is_slow_wallet: None,
+        balance: None,
+    };
+
+    let resolved = match bytes.len() {
+        // legacy addresses were zero padded to 32 bytes in the V7 migration
+        16 => {
+            let addr = AccountAddress::from_hex_literal(&format!("0x{}", trimmed))?;
+            if account_exists(client, addr, version).await? {
+                Some((IdentifierKind::LegacyAddress, addr))
+            } else {
+                None
+            }
+        }
+        32 => resolve_32_bytes(client, &bytes, version).await?,
+        n => bail!("identifier must be 16 or 32 bytes, got {n}"),
+    };
+
+    let Some((kind, addr)) = resolved else {
+        return Ok(report);
+    };
+
+    report.matched_as = Some(kind);
+    report.exists = true;
+    report.address = Some(addr);
+
+    if addr[..16].iter().all(|b| *b == 0) {
+        report.legacy_address = Some(hex::encode(&addr[16..]));
+    }
+
+    let auth_key = get_authentication_key(client, addr, version).await?;
+    report.rotated = Some(auth_key != addr.to_vec());
+    report.authentication_key = Some(hex::encode(auth_key));
+
+    let is_slow = get_view_at_version(
+        client,
+        "0x1::slow_wallet::is_slow",
+        None,
+        Some(addr.to_string()),
+        version,
+    )
+    .await?;
+    let (is_slow,): (bool,) = serde_json::from_value(is_slow)?;
+    report.is_slow_wallet = Some(is_slow);
+
+    report.balance = Some(
+        get_account_balance_libra(client, addr, version)
+            .await?
+            .scaled(),
+    );
+
+    Ok(report)
+}
+
+/// A 32 byte identifier may be an address, a current authentication key,
+/// or a public key whose authentication key is looked up.
+async fn resolve_32_bytes(
+    client: &Client,
+    bytes: &[u8],
+    version: Option<u64>,
+) -> anyhow::Result<Option<(IdentifierKind, AccountAddress)>> {
+    let addr = AccountAddress::from_bytes(bytes)?;
+    if account_exists(client, addr, version).await? {
+        return Ok(Some((IdentifierKind::Address, addr)));
+    }
+
+    let auth_key = AuthenticationKey::try_from(bytes)?;
+    if let Some(addr) = lookup_existing(client, auth_key, version).await? {
+        return Ok(Some((IdentifierKind::AuthKey, addr)));
+    }
+
+    if let Ok(pk) = Ed25519PublicKey::try_from(bytes) {
+        let auth_key = AuthenticationKey::ed25519(&pk);
+        if let Some(addr) = lookup_existing(client, auth_key, version).await? {
+            return Ok(Some((IdentifierKind::PublicKey, addr)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Looks up the originating address of an authentication key. The view
+/// aborts on unmapped keys, which is not an error for our purposes, but a
+/// node that can't be reached is.
+async fn lookup_existing(
+    client: &Client,
+    auth_key: AuthenticationKey,
+    version: Option<u64>,
+) -> anyhow::Result<Option<AccountAddress>> {
+    let res = get_view_at_version(
+        client,
+        "0x1::account::get_originating_address",
+        None,
+        Some(auth_key.to_string()),
+        version,
+    )
+    .await;
+    let value = match res {
+        Ok(v) => v,
+        Err(e) if is_api_error(&e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let (addr,): (AccountAddress,) = serde_json::from_value(value)?;
+    Ok(account_exists(client, addr, version).await?.then_some(addr))
+}
+
+async fn account_exists(
+    client: &Client,
+    addr: AccountAddress,
+    version: Option<u64>,
+) -> anyhow::Result<bool> {
+    let res = get_view_at_version(
+        client,
+        "0x1::account::exists_at",
+        None,
+        Some(addr.to_string()),
+        version,
+    )
+    .await?;
+    let (exists,): (bool,) = serde_json::from_value(res)?;
+    Ok(exists)
+}
+
+async fn get_authentication_key(
+    client: &Client,
+    addr: AccountAddress,
+    version: Option<u64>,
+) -> anyhow::Result<Vec<u8>> {
+    let res = get_view_at_version(
+        client,
+        "0x1::account::get_authentication_key",
+        None,
+        Some(addr.to_string()),
+        version,
+    )
+    .await?;
+    let (key,): (String,) = serde_json::from_value(res)?;
+    Ok(hex::decode(key.trim_start_matches("0x"))?)
+}
@@ -1,4 +1,6 @@
+use libra_query::chain_queries::{get_epoch, get_epoch_ending_version};
 use libra_query::query_type::QueryType;
+use libra_query::whois::{whois, IdentifierKind};
 use libra_smoke_tests::libra_smoke::LibraSmoke;
 use libra_types::move_resource::gas_coin::LibraBalanceDisplay;
 
@@ -42,3 +44,99 @@ async fn account_annotate_test() {
     println!("{:#}", &res.as_str().unwrap());
     assert!(res.as_str().unwrap().contains("drop"));
 }
+
+/// test querying state at a past ledger version
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn query_at_version_test() {
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start swarm");
+    let val_acct = s.first_account.address();
+
+    let c = s.client();
+    let version: u64 = c
+        .get_index()
+        .await
+        .unwrap()
+        .into_inner()
+        .ledger_version
+        .into();
+
+    let q = QueryType::Balance { account: val_acct };
+    let v = q.query_to_json_at_version(&c, Some(version)).await.unwrap();
+    let b: LibraBalanceDisplay = serde_json::from_value(v).unwrap();
+    assert_eq!(b.total, 1000.0);
+
+    // versions ahead of the node cannot be queried
+    libra_query::chain_queries::check_version_available(&c, version + 1_000_000)
+        .await
+        .expect_err("future version should not be available");
+}
+
+/// an epoch's ending version is the last version before the reconfiguration
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn epoch_ending_version_test() {
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start swarm");
+    let val_acct = s.first_account.address();
+
+    let c = s.client();
+
+    // smoke tests start in epoch 2, so epoch 1 has ended
+    let v = get_epoch_ending_version(&c, 1).await.unwrap();
+    assert_eq!(get_epoch(&c, Some(v)).await.unwrap(), 1);
+    assert_eq!(get_epoch(&c, Some(v + 1)).await.unwrap(), 2);
+
+    // epoch 0 ends at genesis, there is no version before it
+    assert!(get_epoch_ending_version(&c, 0).await.is_err());
+
+    // events are not versioned
+    let q = QueryType::Events {
+        account: val_acct,
+        withdrawn_or_deposited: true,
+        seq_start: None,
+    };
+    assert!(q.query_to_json_at_version(&c, Some(v)).await.is_err());
+}
+
+/// look up an account by its address and by its public key
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn whois_test() {
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start swarm");
+    let val_acct = s.first_account.address();
+    let pubkey = hex::encode(s.first_account.public_key().to_bytes());
+
+    let c = s.client();
+
+    let by_address = whois(&c, &val_acct.to_hex_literal(), None).await.unwrap();
+    assert!(by_address.exists);
+    assert_eq!(by_address.matched_as, Some(IdentifierKind::Address));
+    assert_eq!(by_address.rotated, Some(false));
+
+    let by_pubkey = whois(&c, &pubkey, None).await.unwrap();
+    assert_eq!(by_pubkey.address, Some(val_acct));
+
+    // an unmapped key is not an account, nor an error
+    let unknown = whois(&c, &"ab".repeat(32), None).await.unwrap();
+    assert!(!unknown.exists);
+
+    // short addresses like 0x1 are not legacy addresses
+    assert!(whois(&c, "0x01", None).await.is_err());
+}
+
+/// supply breakdown from the supply module views
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn supply_test() {
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start swarm");
+
+    let c = s.client();
+
+    let report = libra_query::supply::get_supply(&c, None).await.unwrap();
+    assert!(report.supply.total > 0.0);
+    assert!(report.supply.max_supply >= report.supply.total);
+}
@@ -0,0 +1,24 @@
+use libra_query::vouch_graph::explore_vouch_graph;
+use libra_smoke_tests::libra_smoke::LibraSmoke;
+
+/// walk the vouch graph of a genesis validator
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn vouch_graph_test() {
+    let mut s = LibraSmoke::new(Some(2), None)
+        .await
+        .expect("could not start swarm");
+    let val_acct = s.first_account.address();
+
+    let c = s.client();
+
+    let graph = explore_vouch_graph(&c, val_acct, 2, 5, None)
+        .await
+        .expect("could not walk vouch graph");
+
+    assert_eq!(graph.account, val_acct);
+    assert!(graph
+        .nodes
+        .iter()
+        .any(|n| n.address == val_acct && n.depth == 0));
+    assert!(graph.to_dot().starts_with("digraph vouches"));
+}
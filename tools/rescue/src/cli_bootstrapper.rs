@@ -155,7 +155,7 @@ impl BootstrapOpts {
     }
 }
 
-fn load_genesis_txn(path: &Path) -> Result<Transaction> {
+pub(crate) fn load_genesis_txn(path: &Path) -> Result<Transaction> {
     let mut file = File::open(path)?;
     let mut buffer = vec![];
     file.read_to_end(&mut buffer)?;
@@ -198,7 +198,9 @@ fn test_bootstrap_db() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn check_rescue_bootstraps(db_path: &Path, blob_path: &Path) -> Result<()> {
+/// Checks the blob bootstraps the DB, without committing it. Returns the
+/// waypoint the DB would have.
+pub fn check_rescue_bootstraps(db_path: &Path, blob_path: &Path) -> Result<Waypoint> {
     let b = BootstrapOpts {
         db_dir: db_path.to_owned(),
         genesis_txn_file: blob_path.to_owned(),
@@ -208,10 +210,9 @@ pub fn check_rescue_bootstraps(db_path: &Path, blob_path: &Path) -> Result<()> {
 
         info: false,
     };
-    if let Some(wp) = b.run()? {
-        println!("Rescue tx verified. Bootstrap with waypoint: {:?}", wp);
-    }
-    Ok(())
+    let wp = b.run()?.context("no waypoint from bootstrap check")?;
+    println!("Rescue tx verified. Bootstrap with waypoint: {:?}", wp);
+    Ok(wp)
 }
 
 /// helper to apply a single step upgrade to a db
@@ -2,13 +2,20 @@
 //! database bootstrapping, and debugging twin states.
 use crate::{
     cli_bootstrapper::{check_rescue_bootstraps, BootstrapOpts},
+    inspect_blob::{inspect_blob, print_report},
     node_config::post_rescue_node_file_updates,
+    rescue_manifest::{
+        check_signing_key, manifest_path, verify_manifests, write_manifest, ManifestFile,
+    },
     transaction_factory::{register_vals, run_script_tx, save_rescue_blob, upgrade_tx},
 };
 
+use anyhow::bail;
 use clap::{Parser, Subcommand};
-use diem_types::waypoint::Waypoint;
+use diem_crypto::PrivateKey;
+use diem_types::{transaction::authenticator::AuthenticationKey, waypoint::Waypoint};
 use libra_types::exports::AccountAddress;
+use libra_wallet::{account_keys::get_keys_from_prompt, keystore::read_private_key_file};
 use std::{path::PathBuf, time::Duration};
 
 /// Constants for blob file names
@@ -77,6 +84,37 @@ pub enum Sub {
         /// directory to read/write or the rescue.blob
         script_path: Option<PathBuf>,
     },
+    /// Decodes a rescue blob, showing its writes against the reference DB,
+    /// its events, and any change to framework, validators, chain id or features
+    Inspect {
+        /// rescue blob path
+        blob: PathBuf,
+        #[clap(long)]
+        /// print the report as JSON
+        json: bool,
+    },
+    /// Signs the manifest of a rescue blob with your owner key
+    SignManifest {
+        /// rescue blob path, its manifest is next to it
+        blob: PathBuf,
+        #[clap(long)]
+        /// optional, the owner account if its key was rotated
+        account: Option<AccountAddress>,
+        #[clap(long)]
+        /// optional, read the hex encoded private key from a file instead of
+        /// the mnemonic, e.g. after a key rotation
+        private_key_file: Option<PathBuf>,
+    },
+    /// Checks rescue manifests from other validators match, the blob is the
+    /// one they describe, and their attestations are valid
+    VerifyManifests {
+        #[clap(long)]
+        /// manifest files, e.g. one collected from each validator
+        manifest: Vec<PathBuf>,
+        #[clap(long)]
+        /// optional, the rescue blob to check against the manifests
+        blob: Option<PathBuf>,
+    },
 }
 
 impl RescueCli {
@@ -98,7 +136,8 @@ impl RescueCli {
                     .unwrap_or(self.db_path.clone())
                     .join(REPLACE_VALIDATORS_BLOB);
                 let p = save_rescue_blob(tx, &out_file)?;
-                check_rescue_bootstraps(&self.db_path, &p)?;
+                let wp = check_rescue_bootstraps(&self.db_path, &p)?;
+                write_manifest(&self.db_path, &p, upgrade_mrb.as_deref(), operator_yaml, wp)?;
             }
             Sub::UpgradeFramework {
                 upgrade_mrb,
@@ -112,17 +151,20 @@ impl RescueCli {
                     .join(UPGRADE_FRAMEWORK_BLOB);
 
                 let p = save_rescue_blob(tx, &out_dir)?;
-                check_rescue_bootstraps(&self.db_path, &p)?;
+                let wp = check_rescue_bootstraps(&self.db_path, &p)?;
+                write_manifest(&self.db_path, &p, Some(upgrade_mrb), &[], wp)?;
             }
             Sub::RunScript { script_path } => {
-                let tx = run_script_tx(script_path.as_ref().unwrap())?;
+                let script_path = script_path.as_ref().unwrap();
+                let tx = run_script_tx(script_path)?;
                 let out_dir = self
                     .blob_path
                     .clone()
                     .unwrap_or(self.db_path.clone())
                     .join(RUN_SCRIPT_BLOB);
                 let p = save_rescue_blob(tx, &out_dir)?;
-                check_rescue_bootstraps(&self.db_path, &p)?;
+                let wp = check_rescue_bootstraps(&self.db_path, &p)?;
+                write_manifest(&self.db_path, &p, None, &[script_path.clone()], wp)?;
             }
             Sub::PatchSafetyRules {
                 config_path,
@@ -131,6 +173,69 @@ impl RescueCli {
             } => {
                 post_rescue_node_file_updates(config_path, *waypoint, blob_path)?;
             }
+            Sub::Inspect { blob, json } => {
+                let report = inspect_blob(&self.db_path, blob)?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                } else {
+                    print_report(&report);
+                }
+            }
+            Sub::SignManifest {
+                blob,
+                account,
+                private_key_file,
+            } => {
+                let path = manifest_path(blob);
+                let mut file = ManifestFile::load(&path)?;
+                let (pri_key, derived) = match private_key_file {
+                    Some(key_file) => {
+                        let key = read_private_key_file(key_file)?;
+                        let derived =
+                            AuthenticationKey::ed25519(&key.public_key()).derived_address();
+                        (key, derived)
+                    }
+                    None => {
+                        let keys = get_keys_from_prompt()?.child_0_owner;
+                        (keys.pri_key, keys.account)
+                    }
+                };
+                let account = account.unwrap_or(derived);
+                // fail now rather than hand out an attestation verify_manifests rejects
+                check_signing_key(
+                    &self.db_path,
+                    file.manifest.db_version,
+                    account,
+                    &pri_key.public_key(),
+                )?;
+                file.sign(&pri_key, account)?;
+                file.save(&path)?;
+                println!("signed {} as {}", path.display(), account);
+            }
+            Sub::VerifyManifests { manifest, blob } => {
+                let (m, checks) = verify_manifests(&self.db_path, manifest, blob.as_deref())?;
+                println!("blob sha256: {}", m.blob_sha256);
+                println!("waypoint: {}", m.waypoint);
+                let mut valid = 0;
+                for c in &checks {
+                    match &c.error {
+                        Some(e) => println!("INVALID {}: {}", c.account, e),
+                        None => {
+                            valid += 1;
+                            let role = if c.in_validator_set {
+                                "validator"
+                            } else {
+                                "not in validator set"
+                            };
+                            println!("valid {} ({})", c.account, role);
+                        }
+                    }
+                }
+                println!("{valid} of {} attestations valid", checks.len());
+                if valid < checks.len() {
+                    bail!("some attestations are not valid");
+                }
+            }
         }
         // hack. let the DB close before exiting
         // TODO: fix in Diem or place in thread
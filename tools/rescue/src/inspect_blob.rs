@@ -0,0 +1,356 @@
+//! Shows what a rescue blob would do to the reference DB before anyone
+//! bootstraps with it: every write with its value before and after, the
+//! events it emits, and whether it touches the framework, the validator set,
+//! the chain id or the feature flags.
+
+use crate::cli_bootstrapper::load_genesis_txn;
+use anyhow::{bail, Context, Result};
+use diem_gas::{ChangeSetConfigs, LATEST_GAS_FEATURE_VERSION};
+use diem_resource_viewer::DiemValueAnnotator;
+use diem_storage_interface::{
+    state_view::{DbStateView, DbStateViewAtVersion},
+    DbReaderWriter,
+};
+use diem_types::{
+    access_path::Path as AccessPathKind,
+    state_store::state_key::{StateKey, StateKeyInner},
+    transaction::{ChangeSet, Script, Transaction, WriteSetPayload},
+};
+use diem_vm::move_vm_ext::{MoveVmExt, SessionId};
+use libra_storage::inspect::open_read_only;
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{StructTag, CORE_CODE_ADDRESS},
+    value::{serialize_values, MoveValue},
+};
+use move_vm_types::gas::UnmeteredGasMeter;
+use serde::Serialize;
+use std::{collections::BTreeSet, path::Path};
+
+/// What a write is sensitive for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flag {
+    FrameworkCode,
+    ValidatorSet,
+    ChainId,
+    FeatureFlags,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WriteEntry {
+    pub key: String,
+    /// `create`, `modify` or `delete`
+    pub op: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub flags: Vec<Flag>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventEntry {
+    pub type_tag: String,
+    pub data: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlobReport {
+    /// `direct` for a change set, `script` for a script run at bootstrap
+    pub payload: &'static str,
+    /// the signer of a script payload
+    pub execute_as: Option<String>,
+    /// the reference DB version the before values are read at
+    pub db_version: u64,
+    pub flags: BTreeSet<Flag>,
+    pub writes: Vec<WriteEntry>,
+    pub events: Vec<EventEntry>,
+}
+
+/// The last byte of the highest address reserved for framework packages,
+/// 0x1 to 0xa. The framework is published at 0x1 and the token package at
+/// 0x3, the rest are reserved for later packages.
+const LAST_FRAMEWORK_ADDRESS: u8 = 0xa;
+
+/// Whether `address` is one of the reserved framework addresses
+fn is_framework_address(address: &AccountAddress) -> bool {
+    let bytes = address.as_ref();
+    let (last, rest) = bytes.split_last().expect("addresses are not empty");
+    rest.iter().all(|b| *b == 0) && (1..=LAST_FRAMEWORK_ADDRESS).contains(last)
+}
+
+/// The sensitive parts of the state a write to `key` changes
+pub fn flags_of_key(key: &StateKey) -> Vec<Flag> {
+    let StateKeyInner::AccessPath(ap) = key.inner() else {
+        return vec![];
+    };
+    if !is_framework_address(&ap.address) {
+        return vec![];
+    }
+    let AccessPathKind::Resource(tag) = ap.get_path() else {
+        // a module
+        return vec![Flag::FrameworkCode];
+    };
+    match (tag.module.as_str(), tag.name.as_str()) {
+        ("code", "PackageRegistry") => vec![Flag::FrameworkCode],
+        ("stake", "ValidatorSet") => vec![Flag::ValidatorSet],
+        ("chain_id", "ChainId") => vec![Flag::ChainId],
+        ("features", "Features") => vec![Flag::FeatureFlags],
+        _ => vec![],
+    }
+}
+
+fn key_to_string(key: &StateKey) -> String {
+    match key.inner() {
+        StateKeyInner::AccessPath(ap) => {
+            if let AccessPathKind::Resource(tag) = ap.get_path() {
+                format!("{}/{}", ap.address.to_hex_literal(), tag)
+            } else if let AccessPathKind::Code(module) = ap.get_path() {
+                format!("{} (module)", module)
+            } else {
+                format!("{:?}", ap)
+            }
+        }
+        StateKeyInner::TableItem { handle, key } => {
+            format!(
+                "table {} item 0x{}",
+                handle.0.to_hex_literal(),
+                hex::encode(key)
+            )
+        }
+        StateKeyInner::Raw(bytes) => format!("raw 0x{}", hex::encode(bytes)),
+    }
+}
+
+/// Runs the script of a script payload in a session on the reference DB,
+/// as the VM does at bootstrap, and returns the change set it produces.
+fn script_change_set(
+    view: &DbStateView,
+    execute_as: &AccountAddress,
+    script: &Script,
+) -> Result<ChangeSet> {
+    let dvm = diem_vm::DiemVM::new(view);
+    let adapter = dvm.as_move_resolver(view);
+    let mvm: &MoveVmExt = dvm.internals().move_vm();
+    let mut session = mvm.new_session(
+        &adapter,
+        SessionId::genesis(diem_crypto::HashValue::zero()),
+        false,
+    );
+
+    let mut args = vec![MoveValue::Signer(*execute_as)];
+    args.extend(script.args().iter().cloned().map(MoveValue::from));
+    session
+        .execute_script(
+            script.code(),
+            script.ty_args().to_vec(),
+            serialize_values(&args),
+            &mut UnmeteredGasMeter,
+        )
+        .context("script failed on the reference db")?;
+
+    let vmc = session.finish(
+        &mut (),
+        &ChangeSetConfigs::unlimited_at_gas_feature_version(LATEST_GAS_FEATURE_VERSION),
+    )?;
+    crate::session_tools::unpack_to_changeset(vmc)
+}
+
+/// Decodes the rescue blob at `blob_path` against the DB at `db_path`
+pub fn inspect_blob(db_path: &Path, blob_path: &Path) -> Result<BlobReport> {
+    let Transaction::GenesisTransaction(payload) = load_genesis_txn(blob_path)
+        .with_context(|| format!("cannot read rescue blob {}", blob_path.display()))?
+    else {
+        bail!("{} is not a rescue blob", blob_path.display());
+    };
+
+    let db = DbReaderWriter::new(open_read_only(db_path)?).reader;
+    let db_version = db.get_latest_version()?;
+    let view = db.state_view_at_version(Some(db_version))?;
+
+    let (kind, execute_as, change_set) = match payload {
+        WriteSetPayload::Direct(cs) => ("direct", None, cs),
+        WriteSetPayload::Script { execute_as, script } => (
+            "script",
+            Some(execute_as.to_hex_literal()),
+            script_change_set(&view, &execute_as, &script)?,
+        ),
+    };
+
+    let dvm = diem_vm::DiemVM::new(&view);
+    let adapter = dvm.as_move_resolver(&view);
+    let annotator = DiemValueAnnotator::new(&adapter);
+    let decode = |key: &StateKey, bytes: &[u8]| -> String {
+        let StateKeyInner::AccessPath(ap) = key.inner() else {
+            return format!("0x{}", hex::encode(bytes));
+        };
+        if let AccessPathKind::Code(_) = ap.get_path() {
+            return format!("module of {} bytes", bytes.len());
+        }
+        let tag = match ap.get_path() {
+            AccessPathKind::Resource(tag) => Some(tag),
+            _ => None,
+        };
+        tag.and_then(|tag| annotator.view_resource(&tag, bytes).ok())
+            .map(|r| format!("{:#}", r))
+            .unwrap_or_else(|| format!("0x{}", hex::encode(bytes)))
+    };
+
+    let mut flags = BTreeSet::new();
+    let mut writes = vec![];
+    for (key, op) in change_set.write_set().iter() {
+        let before = db
+            .get_state_value_by_version(key, db_version)?
+            .map(|v| decode(key, v.bytes()));
+        let after = op.extract_raw_bytes().map(|b| decode(key, &b));
+        let op = match (&before, &after) {
+            (_, None) => "delete",
+            (None, Some(_)) => "create",
+            (Some(_), Some(_)) => "modify",
+        };
+        let key_flags = flags_of_key(key);
+        flags.extend(key_flags.iter().copied());
+        writes.push(WriteEntry {
+            key: key_to_string(key),
+            op,
+            before,
+            after,
+            flags: key_flags,
+        });
+    }
+
+    let events = change_set
+        .events()
+        .iter()
+        .map(|e| EventEntry {
+            type_tag: e.type_tag().to_string(),
+            data: annotator
+                .view_value(e.type_tag(), e.event_data())
+                .map(|v| format!("{}", v))
+                .unwrap_or_else(|_| format!("0x{}", hex::encode(e.event_data()))),
+        })
+        .collect();
+
+    Ok(BlobReport {
+        payload: kind,
+        execute_as,
+        db_version,
+        flags,
+        writes,
+        events,
+    })
+}
+
+/// Prints the report for a reviewer
+pub fn print_report(report: &BlobReport) {
+    match &report.execute_as {
+        Some(signer) => println!("payload: script, executed as {signer}"),
+        None => println!("payload: direct change set"),
+    }
+    println!("reference db version: {}", report.db_version);
+    for flag in &report.flags {
+        println!("WARNING: changes {:?}", flag);
+    }
+
+    println!("\n{} writes:", report.writes.len());
+    for w in &report.writes {
+        let flags = if w.flags.is_empty() {
+            String::new()
+        } else {
+            format!(" {:?}", w.flags)
+        };
+        println!("[{}] {}{}", w.op, w.key, flags);
+        if let Some(before) = &w.before {
+            println!("  before: {before}");
+        }
+        if let Some(after) = &w.after {
+            println!("  after: {after}");
+        }
+    }
+
+    println!("\n{} events:", report.events.len());
+    for e in &report.events {
+        println!("{}: {}", e.type_tag, e.data);
+    }
+}
+
+#[test]
+fn flag_sensitive_keys() {
+    use diem_types::{
+        access_path::AccessPath,
+        on_chain_config::{OnChainConfig, ValidatorSet},
+    };
+    use move_core_types::language_storage::ModuleId;
+
+    let resource = |address: AccountAddress, tag: &str| {
+        let tag: StructTag = tag.parse().unwrap();
+        StateKey::access_path(AccessPath::resource_access_path(address, tag).unwrap())
+    };
+
+    let vals = StateKey::access_path(ValidatorSet::access_path().unwrap());
+    assert_eq!(flags_of_key(&vals), vec![Flag::ValidatorSet]);
+    assert_eq!(
+        flags_of_key(&resource(CORE_CODE_ADDRESS, "0x1::chain_id::ChainId")),
+        vec![Flag::ChainId]
+    );
+    assert_eq!(
+        flags_of_key(&resource(CORE_CODE_ADDRESS, "0x1::features::Features")),
+        vec![Flag::FeatureFlags]
+    );
+    assert_eq!(
+        flags_of_key(&resource(CORE_CODE_ADDRESS, "0x1::code::PackageRegistry")),
+        vec![Flag::FrameworkCode]
+    );
+    let module = ModuleId::new(CORE_CODE_ADDRESS, "stake".parse().unwrap());
+    let code = StateKey::access_path(AccessPath::code_access_path(module));
+    assert_eq!(flags_of_key(&code), vec![Flag::FrameworkCode]);
+
+    // the token package is framework code too
+    let token = AccountAddress::from_hex_literal("0x3").unwrap();
+    let module = ModuleId::new(token, "token".parse().unwrap());
+    let code = StateKey::access_path(AccessPath::code_access_path(module));
+    assert_eq!(flags_of_key(&code), vec![Flag::FrameworkCode]);
+    assert_eq!(
+        flags_of_key(&resource(token, "0x1::code::PackageRegistry")),
+        vec![Flag::FrameworkCode]
+    );
+
+    // the same resource on a user account is not sensitive
+    let alice = AccountAddress::from_hex_literal("0xb").unwrap();
+    assert!(flags_of_key(&resource(alice, "0x1::features::Features")).is_empty());
+    assert!(flags_of_key(&resource(
+        CORE_CODE_ADDRESS,
+        "0x1::coin::CoinInfo<0x1::libra_coin::LibraCoin>"
+    ))
+    .is_empty());
+}
+
+#[test]
+/// Decodes an upgrade blob built from the db_339 fixture
+fn inspect_upgrade_on_v7() -> anyhow::Result<()> {
+    use crate::cli_main::{RescueCli, Sub, UPGRADE_FRAMEWORK_BLOB};
+    use libra_framework::release::ReleaseTarget;
+
+    let dir = crate::test_support::setup_v7_reference_twin_db()?;
+    let upgrade_mrb = ReleaseTarget::Head
+        .find_bundle_path()
+        .expect("cannot find head.mrb");
+    RescueCli {
+        db_path: dir.clone(),
+        blob_path: Some(dir.clone()),
+        command: Sub::UpgradeFramework {
+            upgrade_mrb,
+            set_validators: None,
+        },
+    }
+    .run()?;
+
+    let report = inspect_blob(&dir, &dir.join(UPGRADE_FRAMEWORK_BLOB))?;
+    assert_eq!(report.payload, "direct");
+    assert!(report.flags.contains(&Flag::FrameworkCode));
+    // the modules of the framework are already in the reference db
+    assert!(report
+        .writes
+        .iter()
+        .any(|w| w.op == "modify" && w.flags.contains(&Flag::FrameworkCode)));
+    Ok(())
+}
@@ -1,6 +1,8 @@
 pub mod cli_bootstrapper;
 pub mod cli_main;
+pub mod inspect_blob;
 pub mod node_config;
+pub mod rescue_manifest;
 pub mod session_tools;
 pub mod test_support;
 pub mod transaction_factory;
@@ -0,0 +1,379 @@
+//! A manifest written next to each rescue blob, so validators who build the
+//! blob independently can check they got the same bytes from the same DB,
+//! and attest to it with their owner key without a running chain.
+
+use anyhow::{bail, Context, Result};
+use diem_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey};
+use diem_db::DiemDB;
+use diem_storage_interface::DbReader;
+use diem_types::{
+    access_path::AccessPath,
+    account_config::AccountResource,
+    on_chain_config::{OnChainConfig, ValidatorSet},
+    state_store::state_key::StateKey,
+    transaction::{authenticator::AuthenticationKey, Version},
+    waypoint::Waypoint,
+};
+use libra_storage::inspect::open_read_only;
+use libra_types::exports::AccountAddress;
+use libra_wallet::message::{sign_message, SignedMessage};
+use move_core_types::move_resource::MoveStructType;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// appended to the blob's file name
+pub const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// A file the blob was built from. Only the file name is kept, since paths
+/// differ between validators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFile {
+    pub name: String,
+    pub sha256: String,
+}
+
+/// What a rescue blob was built from, and what it bootstraps to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RescueManifest {
+    pub blob_sha256: String,
+    /// latest version of the reference DB
+    pub db_version: Version,
+    /// transaction accumulator root hash of the reference DB
+    pub db_root_hash: String,
+    pub mrb_sha256: Option<String>,
+    /// operator files or Move scripts
+    pub inputs: Vec<InputFile>,
+    /// waypoint of the reference DB with the blob applied
+    pub waypoint: Waypoint,
+}
+
+/// The manifest file: the manifest, and the attestations collected for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub manifest: RescueManifest,
+    #[serde(default)]
+    pub attestations: Vec<SignedMessage>,
+}
+
+/// One attestation, as checked by `verify_manifests`
+#[derive(Debug)]
+pub struct AttestationCheck {
+    pub account: AccountAddress,
+    /// why the attestation is not valid
+    pub error: Option<String>,
+    /// in the active validator set of the reference DB
+    pub in_validator_set: bool,
+}
+
+/// Where the manifest of `blob_path` goes
+pub fn manifest_path(blob_path: &Path) -> PathBuf {
+    let mut name = blob_path.as_os_str().to_owned();
+    name.push(MANIFEST_SUFFIX);
+    PathBuf::from(name)
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    Ok(hex::encode(Sha256::digest(&bytes)))
+}
+
+impl RescueManifest {
+    /// Describes `blob_path`, built from the DB at `db_path`. The DB must
+    /// not have the blob applied yet.
+    pub fn new(
+        db_path: &Path,
+        blob_path: &Path,
+        mrb_path: Option<&Path>,
+        inputs: &[PathBuf],
+        waypoint: Waypoint,
+    ) -> Result<Self> {
+        let (db_version, root_hash) = {
+            let db = open_read_only(db_path)?;
+            let trees = db.get_latest_executed_trees()?;
+            let version = trees.version().context("reference db is empty")?;
+            (version, trees.txn_accumulator().root_hash)
+        };
+        let inputs = inputs
+            .iter()
+            .map(|p| {
+                Ok(InputFile {
+                    name: p
+                        .file_name()
+                        .with_context(|| format!("{} is not a file", p.display()))?
+                        .to_string_lossy()
+                        .to_string(),
+                    sha256: sha256_file(p)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            blob_sha256: sha256_file(blob_path)?,
+            db_version,
+            db_root_hash: root_hash.to_hex_literal(),
+            mrb_sha256: mrb_path.map(sha256_file).transpose()?,
+            inputs,
+            waypoint,
+        })
+    }
+
+    /// The message attestations sign: the hash of the manifest's JSON
+    pub fn attestation_message(&self) -> Result<String> {
+        let hash = hex::encode(Sha256::digest(&serde_json::to_vec(self)?));
+        Ok(format!("libra rescue manifest sha256:{hash}"))
+    }
+}
+
+impl ManifestFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("cannot read manifest {}", path.display()))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Adds the attestation of `account`, replacing any earlier one
+    pub fn sign(&mut self, key: &Ed25519PrivateKey, account: AccountAddress) -> Result<()> {
+        let signed = sign_message(key, account, &self.manifest.attestation_message()?);
+        self.attestations.retain(|a| a.account != account);
+        self.attestations.push(signed);
+        Ok(())
+    }
+}
+
+/// Writes the manifest of a blob freshly built from `db_path`, returns its path
+pub fn write_manifest(
+    db_path: &Path,
+    blob_path: &Path,
+    mrb_path: Option<&Path>,
+    inputs: &[PathBuf],
+    waypoint: Waypoint,
+) -> Result<PathBuf> {
+    let manifest = RescueManifest::new(db_path, blob_path, mrb_path, inputs, waypoint)?;
+    let path = manifest_path(blob_path);
+    ManifestFile {
+        manifest,
+        attestations: vec![],
+    }
+    .save(&path)?;
+    println!("rescue manifest written to {}", path.display());
+    Ok(path)
+}
+
+/// The account's authentication key in the reference DB
+fn auth_key_in_db(db: &DiemDB, account: AccountAddress, version: Version) -> Result<Vec<u8>> {
+    let ap = AccessPath::resource_access_path(account, AccountResource::struct_tag())?;
+    let value = db
+        .get_state_value_by_version(&StateKey::access_path(ap), version)?
+        .with_context(|| format!("account {account} not found in the reference db"))?;
+    let resource: AccountResource = bcs::from_bytes(value.bytes())?;
+    Ok(resource.authentication_key().to_vec())
+}
+
+/// Checks `public_key` is the key of an account whose authentication key in
+/// the reference DB is `auth_key`. A key that derives the account address
+/// is not enough, it may have been rotated away.
+fn check_key(auth_key: &[u8], public_key: &Ed25519PublicKey) -> Result<()> {
+    if AuthenticationKey::ed25519(public_key).to_vec() != auth_key {
+        bail!("signing key is not the account's key in the reference db");
+    }
+    Ok(())
+}
+
+/// Checks, before signing, that `public_key` is the key of `account` in the
+/// reference DB at `db_path`, at `version`, as `verify_manifests` will
+pub fn check_signing_key(
+    db_path: &Path,
+    version: Version,
+    account: AccountAddress,
+    public_key: &Ed25519PublicKey,
+) -> Result<()> {
+    let db = open_read_only(db_path)?;
+    check_key(&auth_key_in_db(&db, account, version)?, public_key)
+}
+
+/// Checks one attestation of `manifest`. The key must match the account's
+/// authentication key in the reference DB at the manifest's version.
+fn check_attestation(
+    manifest: &RescueManifest,
+    attestation: &SignedMessage,
+    db: &DiemDB,
+) -> Result<()> {
+    if attestation.message != manifest.attestation_message()? {
+        bail!("attestation is for a different manifest");
+    }
+    attestation.verify_signature()?;
+    let auth_key = auth_key_in_db(db, attestation.account, manifest.db_version)?;
+    check_key(&auth_key, &attestation.public_key)
+}
+
+/// Checks that the manifests at `paths` describe the same blob, that the
+/// blob at `blob_path`, if given, is that blob, and checks every attestation
+/// they carry against the reference DB at `db_path`.
+pub fn verify_manifests(
+    db_path: &Path,
+    paths: &[PathBuf],
+    blob_path: Option<&Path>,
+) -> Result<(RescueManifest, Vec<AttestationCheck>)> {
+    let Some((first, rest)) = paths.split_first() else {
+        bail!("no manifest to verify");
+    };
+    let first_file = ManifestFile::load(first)?;
+    let manifest = first_file.manifest.clone();
+    let mut attestations = first_file.attestations;
+    for path in rest {
+        let file = ManifestFile::load(path)?;
+        if file.manifest != manifest {
+            bail!(
+                "manifest {} differs from {}:\n{:#?}\n{:#?}",
+                path.display(),
+                first.display(),
+                file.manifest,
+                manifest
+            );
+        }
+        for a in file.attestations {
+            if !attestations.iter().any(|b| b.account == a.account) {
+                attestations.push(a);
+            }
+        }
+    }
+
+    if let Some(blob) = blob_path {
+        let hash = sha256_file(blob)?;
+        if hash != manifest.blob_sha256 {
+            bail!(
+                "blob {} has sha256 {hash}, the manifest expects {}",
+                blob.display(),
+                manifest.blob_sha256
+            );
+        }
+    }
+
+    let db = open_read_only(db_path)?;
+    let validators: Vec<AccountAddress> = match db.get_state_value_by_version(
+        &StateKey::access_path(ValidatorSet::access_path()?),
+        manifest.db_version,
+    )? {
+        Some(v) => bcs::from_bytes::<ValidatorSet>(v.bytes())?
+            .active_validators()
+            .iter()
+            .map(|v| *v.account_address())
+            .collect(),
+        None => vec![],
+    };
+
+    let checks = attestations
+        .iter()
+        .map(|a| AttestationCheck {
+            account: a.account,
+            error: check_attestation(&manifest, a, &db)
+                .err()
+                .map(|e| e.to_string()),
+            in_validator_set: validators.contains(&a.account),
+        })
+        .collect();
+    Ok((manifest, checks))
+}
+
+#[test]
+fn sign_and_check_manifest() {
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let alice = libra_wallet::account_keys::get_keys_from_mnem(alice_mnem.to_string())
+        .unwrap()
+        .child_0_owner;
+
+    let manifest = RescueManifest {
+        blob_sha256: "ab".repeat(32),
+        db_version: 100,
+        db_root_hash: format!("0x{}", "cd".repeat(32)),
+        mrb_sha256: None,
+        inputs: vec![InputFile {
+            name: "operator.yaml".to_owned(),
+            sha256: "ef".repeat(32),
+        }],
+        waypoint: format!("0:{}", "00".repeat(32)).parse().unwrap(),
+    };
+    let mut file = ManifestFile {
+        manifest,
+        attestations: vec![],
+    };
+    file.sign(&alice.pri_key, alice.account).unwrap();
+    // signing again replaces the attestation
+    file.sign(&alice.pri_key, alice.account).unwrap();
+    assert_eq!(file.attestations.len(), 1);
+
+    // the manifest and attestation survive the file
+    let temp = diem_temppath::TempPath::new();
+    file.save(temp.path()).unwrap();
+    let loaded = ManifestFile::load(temp.path()).unwrap();
+    assert_eq!(loaded.manifest, file.manifest);
+    let signed = &loaded.attestations[0];
+    assert_eq!(
+        signed.message,
+        loaded.manifest.attestation_message().unwrap()
+    );
+    signed.verify_offline().unwrap();
+
+    // an attestation doesn't carry over to another blob
+    let mut other = loaded.manifest.clone();
+    other.blob_sha256 = "00".repeat(32);
+    assert_ne!(signed.message, other.attestation_message().unwrap());
+
+    assert_eq!(
+        manifest_path(Path::new("/tmp/run_script_rescue.blob")),
+        PathBuf::from("/tmp/run_script_rescue.blob.manifest.json")
+    );
+}
+
+#[test]
+fn rotated_key_cannot_sign() {
+    use diem_crypto::PrivateKey;
+
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let keys = libra_wallet::account_keys::get_keys_from_mnem(alice_mnem.to_string()).unwrap();
+    let original = keys.child_0_owner.pri_key.public_key();
+    let rotated = keys.child_1_operator.pri_key.public_key();
+    assert_eq!(
+        AuthenticationKey::ed25519(&original).derived_address(),
+        keys.child_0_owner.account
+    );
+
+    // before rotation the original key is the account's key
+    let auth_key = AuthenticationKey::ed25519(&original).to_vec();
+    check_key(&auth_key, &original).unwrap();
+    assert!(check_key(&auth_key, &rotated).is_err());
+
+    // after rotation the original key still derives the address, but can
+    // no longer sign
+    let auth_key = AuthenticationKey::ed25519(&rotated).to_vec();
+    check_key(&auth_key, &rotated).unwrap();
+    assert!(check_key(&auth_key, &original).is_err());
+}
+
+#[test]
+/// Uses a database fixture extracted from `./rescue/fixtures/db_339.tar.gz`
+fn signing_key_checked_against_db() -> anyhow::Result<()> {
+    use diem_crypto::PrivateKey;
+
+    let dir = crate::test_support::setup_v7_reference_twin_db()?;
+    let version = open_read_only(&dir)?.get_latest_version()?;
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let alice =
+        libra_wallet::account_keys::get_keys_from_mnem(alice_mnem.to_string())?.child_0_owner;
+    let public_key = alice.pri_key.public_key();
+
+    // not the key of the framework account
+    let framework = AccountAddress::from_hex_literal("0x1")?;
+    assert!(check_signing_key(&dir, version, framework, &public_key).is_err());
+    // nor of an account missing from the db, even the one it derives
+    assert!(check_signing_key(&dir, version, alice.account, &public_key).is_err());
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use libra_rescue::{
     cli_bootstrapper::BootstrapOpts,
     cli_main::{RescueCli, Sub, RUN_SCRIPT_BLOB},
+    rescue_manifest::{manifest_path, ManifestFile},
     test_support,
 };
 use libra_smoke_tests::libra_smoke::LibraSmoke;
@@ -49,6 +50,8 @@ async fn test_valid_genesis() -> anyhow::Result<()> {
 
     let file = blob_path.path().join(RUN_SCRIPT_BLOB);
     assert!(file.exists());
+    let manifest = ManifestFile::load(&manifest_path(&file))?.manifest;
+    assert_eq!(manifest.inputs.len(), 1);
 
     // hack, adding sleep here since we get db lock issue in CI.
     std::thread::sleep(std::time::Duration::from_secs(1));
@@ -67,6 +70,8 @@ async fn test_valid_genesis() -> anyhow::Result<()> {
     };
 
     let wp = boot.run()?;
+    // the manifest records the waypoint the blob bootstraps to
+    assert_eq!(wp, Some(manifest.waypoint));
 
     // hack, adding sleep here since we get db lock issue in CI.
     std::thread::sleep(std::time::Duration::from_secs(1));
@@ -0,0 +1,394 @@
+//! Compare two state snapshots, e.g. of the epochs around an incident.
+//! The records of a snapshot are sorted by key hash across its chunks, so
+//! both snapshots are walked side by side, a few chunks of each in memory.
+//! Changes are written to the JSON report as they are found.
+
+use crate::{
+    export_snapshot::{decode, decode_resource, Decoder},
+    snapshot_reader::{ChunkRecords, SnapshotReader},
+};
+use anyhow::Result;
+use diem_crypto::{hash::CryptoHash, HashValue};
+use diem_logger::warn;
+use diem_types::{
+    access_path::Path as AccessPathKind,
+    account_config::AccountResource,
+    state_store::{
+        state_key::{StateKey, StateKeyInner},
+        state_value::StateValue,
+    },
+};
+use futures::{stream::BoxStream, TryStreamExt};
+use libra_types::move_resource::jail::JailResource;
+use move_core_types::{language_storage::StructTag, move_resource::MoveStructType};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Tables the diff decodes besides those of the export
+const DIFF_TABLES: &[(&str, Decoder)] = &[("jail", decode::<JailResource>)];
+
+/// Decodes a resource of the export or diff tables, returning the table
+/// name and the resource as JSON.
+fn decode_diff_resource(tag: &StructTag, bytes: &[u8]) -> Option<(&'static str, Result<Value>)> {
+    decode_resource(tag, bytes).or_else(|| {
+        DIFF_TABLES
+            .iter()
+            .find_map(|(name, f)| f(tag, bytes).map(|r| (*name, r)))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One entry of the report
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub address: String,
+    /// `account` for the account itself, otherwise the resource table
+    pub resource: &'static str,
+    pub change: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+    /// for coin stores, the balance after less the balance before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_delta: Option<i64>,
+}
+
+/// Totals of the report
+#[derive(Debug, Default, Serialize)]
+pub struct DiffSummary {
+    pub version_a: u64,
+    pub version_b: u64,
+    pub keys_unchanged: u64,
+    pub keys_added: u64,
+    pub keys_removed: u64,
+    pub keys_modified: u64,
+    pub accounts_added: u64,
+    pub accounts_removed: u64,
+    pub balances_changed: u64,
+    pub total_balance_delta: i64,
+    /// changed keys per resource table, or per struct for resources outside
+    /// the export tables
+    pub resource_changes: BTreeMap<String, u64>,
+}
+
+/// Reads the records of a snapshot in order, one chunk at a time
+struct SnapshotStream {
+    chunks: BoxStream<'static, Result<ChunkRecords>>,
+    records: VecDeque<(HashValue, StateKey, StateValue)>,
+}
+
+impl SnapshotStream {
+    fn new(manifest_path: &Path) -> Result<(Self, u64)> {
+        let reader = SnapshotReader::new(manifest_path)?;
+        let stream = Self {
+            chunks: reader.chunks(),
+            records: VecDeque::new(),
+        };
+        Ok((stream, reader.manifest().version))
+    }
+
+    /// the next record, without taking it
+    async fn peek(&mut self) -> Result<Option<&(HashValue, StateKey, StateValue)>> {
+        while self.records.is_empty() {
+            let Some(records) = self.chunks.try_next().await? else {
+                return Ok(None);
+            };
+            self.records = records.into_iter().map(|(k, v)| (k.hash(), k, v)).collect();
+        }
+        Ok(self.records.front())
+    }
+
+    fn pop(&mut self) -> Option<(HashValue, StateKey, StateValue)> {
+        self.records.pop_front()
+    }
+}
+
+/// Writes the changes as a JSON list as they come, and the summary last
+struct Report {
+    out: BufWriter<File>,
+    first: bool,
+    summary: DiffSummary,
+}
+
+impl Report {
+    fn new(out_path: &Path, version_a: u64, version_b: u64) -> Result<Self> {
+        let mut out = BufWriter::new(File::create(out_path)?);
+        write!(out, "{{\"changes\":[")?;
+        Ok(Self {
+            out,
+            first: true,
+            summary: DiffSummary {
+                version_a,
+                version_b,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn push(&mut self, change: &Change) -> Result<()> {
+        if !self.first {
+            write!(self.out, ",")?;
+        }
+        self.first = false;
+        writeln!(self.out)?;
+        serde_json::to_writer(&mut self.out, change)?;
+        Ok(())
+    }
+
+    /// Records a key that differs between the snapshots
+    fn key_changed(
+        &mut self,
+        key: &StateKey,
+        before: Option<&StateValue>,
+        after: Option<&StateValue>,
+    ) -> Result<()> {
+        let change = match (before, after) {
+            (None, Some(_)) => ChangeKind::Added,
+            (Some(_), None) => ChangeKind::Removed,
+            _ => ChangeKind::Modified,
+        };
+        match change {
+            ChangeKind::Added => self.summary.keys_added += 1,
+            ChangeKind::Removed => self.summary.keys_removed += 1,
+            ChangeKind::Modified => self.summary.keys_modified += 1,
+        }
+
+        let StateKeyInner::AccessPath(ap) = key.inner() else {
+            return Ok(());
+        };
+        let AccessPathKind::Resource(tag) = ap.get_path() else {
+            *self
+                .summary
+                .resource_changes
+                .entry("modules".to_owned())
+                .or_default() += 1;
+            return Ok(());
+        };
+        let address = ap.address.to_hex_literal();
+
+        if tag == AccountResource::struct_tag() && change != ChangeKind::Modified {
+            if change == ChangeKind::Added {
+                self.summary.accounts_added += 1;
+            } else {
+                self.summary.accounts_removed += 1;
+            }
+            self.push(&Change {
+                address: address.clone(),
+                resource: "account",
+                change,
+                before: None,
+                after: None,
+                balance_delta: None,
+            })?;
+        }
+
+        // a resource that doesn't decode is reported as its raw bytes
+        let decode = |v: Option<&StateValue>| -> Option<(&'static str, Value)> {
+            let v = v?;
+            let (table, r) = decode_diff_resource(&tag, v.bytes())?;
+            let json = r.unwrap_or_else(|e| {
+                warn!("cannot decode {table} of account {address}: {e}");
+                Value::String(format!("0x{}", hex::encode(v.bytes())))
+            });
+            Some((table, json))
+        };
+        let before = decode(before);
+        let after = decode(after);
+        let Some(table) = before.as_ref().or(after.as_ref()).map(|(t, _)| *t) else {
+            let name = format!("{}::{}", tag.module, tag.name);
+            *self.summary.resource_changes.entry(name).or_default() += 1;
+            return Ok(());
+        };
+        *self
+            .summary
+            .resource_changes
+            .entry(table.to_owned())
+            .or_default() += 1;
+
+        let before = before.map(|(_, v)| v);
+        let after = after.map(|(_, v)| v);
+        let balance_delta = (table == "coin_store").then(|| {
+            let coin = |v: &Option<Value>| {
+                v.as_ref()
+                    .and_then(|v| v["coin"].as_u64())
+                    .unwrap_or_default() as i64
+            };
+            coin(&after) - coin(&before)
+        });
+        if let Some(delta) = balance_delta {
+            self.summary.balances_changed += 1;
+            self.summary.total_balance_delta += delta;
+        }
+        self.push(&Change {
+            address,
+            resource: table,
+            change,
+            before,
+            after,
+            balance_delta,
+        })
+    }
+
+    fn finish(mut self) -> Result<DiffSummary> {
+        write!(self.out, "\n],\"summary\":")?;
+        serde_json::to_writer_pretty(&mut self.out, &self.summary)?;
+        writeln!(self.out, "}}")?;
+        self.out.flush()?;
+        Ok(self.summary)
+    }
+}
+
+/// Walks both streams in key hash order, reporting every key that differs.
+async fn diff_streams(
+    a: &mut SnapshotStream,
+    b: &mut SnapshotStream,
+    report: &mut Report,
+) -> Result<()> {
+    loop {
+        let order = match (a.peek().await?, b.peek().await?) {
+            (None, None) => return Ok(()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((ha, ..)), Some((hb, ..))) => ha.cmp(hb),
+        };
+        match order {
+            Ordering::Less => {
+                let (_, key, value) = a.pop().expect("peeked");
+                report.key_changed(&key, Some(&value), None)?;
+            }
+            Ordering::Greater => {
+                let (_, key, value) = b.pop().expect("peeked");
+                report.key_changed(&key, None, Some(&value))?;
+            }
+            Ordering::Equal => {
+                let (_, key, before) = a.pop().expect("peeked");
+                let (_, _, after) = b.pop().expect("peeked");
+                if before == after {
+                    report.summary.keys_unchanged += 1;
+                } else {
+                    report.key_changed(&key, Some(&before), Some(&after))?;
+                }
+            }
+        }
+    }
+}
+
+/// Compares the snapshots of `manifest_a` and `manifest_b`, writing a JSON
+/// report of the changes from A to B to `out_path`.
+pub async fn diff_snapshots(
+    manifest_a: &Path,
+    manifest_b: &Path,
+    out_path: &Path,
+) -> Result<DiffSummary> {
+    let (mut a, version_a) = SnapshotStream::new(manifest_a)?;
+    let (mut b, version_b) = SnapshotStream::new(manifest_b)?;
+    let mut report = Report::new(out_path, version_a, version_b)?;
+    diff_streams(&mut a, &mut b, &mut report).await?;
+    report.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diem_types::{access_path::AccessPath, account_address::AccountAddress};
+    use futures::StreamExt;
+
+    fn record<T: MoveStructType + Serialize>(
+        address: AccountAddress,
+        resource: &T,
+    ) -> (HashValue, StateKey, StateValue) {
+        let ap = AccessPath::resource_access_path(address, T::struct_tag()).unwrap();
+        let key = StateKey::access_path(ap);
+        let value = StateValue::from(bcs::to_bytes(resource).unwrap());
+        (key.hash(), key, value)
+    }
+
+    fn stream(mut records: Vec<(HashValue, StateKey, StateValue)>) -> SnapshotStream {
+        records.sort_by_key(|(h, ..)| *h);
+        SnapshotStream {
+            chunks: futures::stream::empty().boxed(),
+            records: records.into(),
+        }
+    }
+
+    fn jail(is_jailed: bool) -> JailResource {
+        JailResource {
+            is_jailed,
+            lifetime_jailed: 1,
+            lifetime_vouchees_jailed: 0,
+            consecutive_failure_to_rejoin: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn diff_jail_changes() {
+        let alice = AccountAddress::from_hex_literal("0xa").unwrap();
+        let bob = AccountAddress::from_hex_literal("0xb").unwrap();
+        let carol = AccountAddress::from_hex_literal("0xc").unwrap();
+        let mut a = stream(vec![
+            record(alice, &jail(false)),
+            record(bob, &jail(true)),
+            record(carol, &jail(true)),
+        ]);
+        let mut b = stream(vec![record(alice, &jail(true)), record(bob, &jail(true))]);
+
+        let temp = diem_temppath::TempPath::new();
+        let mut report = Report::new(temp.path(), 1, 2).unwrap();
+        diff_streams(&mut a, &mut b, &mut report).await.unwrap();
+        let summary = report.finish().unwrap();
+        assert_eq!(summary.keys_unchanged, 1);
+        assert_eq!(summary.keys_modified, 1);
+        assert_eq!(summary.keys_removed, 1);
+        assert_eq!(summary.resource_changes["jail"], 2);
+
+        // the report is one JSON document
+        let report: Value =
+            serde_json::from_str(&std::fs::read_to_string(temp.path()).unwrap()).unwrap();
+        let changes = report["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 2);
+        let alice_change = changes
+            .iter()
+            .find(|c| c["address"] == alice.to_hex_literal())
+            .unwrap();
+        assert_eq!(alice_change["change"], "modified");
+        assert_eq!(alice_change["before"]["is_jailed"], false);
+        assert_eq!(alice_change["after"]["is_jailed"], true);
+        assert_eq!(report["summary"]["version_b"], 2);
+    }
+
+    #[tokio::test]
+    async fn undecodable_resource_as_hex() {
+        let alice = AccountAddress::from_hex_literal("0xa").unwrap();
+        let (hash, key, _) = record(alice, &jail(false));
+        let mut a = stream(vec![(hash, key.clone(), StateValue::from(vec![7]))]);
+        let mut b = stream(vec![record(alice, &jail(true))]);
+
+        let temp = diem_temppath::TempPath::new();
+        let mut report = Report::new(temp.path(), 1, 2).unwrap();
+        diff_streams(&mut a, &mut b, &mut report).await.unwrap();
+        let summary = report.finish().unwrap();
+        assert_eq!(summary.keys_modified, 1);
+
+        let report: Value =
+            serde_json::from_str(&std::fs::read_to_string(temp.path()).unwrap()).unwrap();
+        let change = &report["changes"][0];
+        assert_eq!(change["resource"], "jail");
+        assert_eq!(change["before"], "0x07");
+        assert_eq!(change["after"]["is_jailed"], true);
+    }
+}
@@ -1,43 +1,16 @@
-use crate::parse_folder_names::{
-    parse_epoch_ending_number, parse_state_epoch_info, parse_transaction_number,
+use crate::{
+    archive_source::{download_folder, ArchiveSource},
+    parse_folder_names::{
+        parse_epoch_ending_number, parse_state_epoch_info, parse_transaction_number,
+    },
 };
 use anyhow::bail;
 use anyhow::{Context, Result};
 use diem_logger::info;
-use reqwest::Client;
-use serde::Deserialize;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-// Update GitHubContent structure for contents API
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-struct GitHubContent {
-    download_url: Option<String>,
-    #[serde(rename = "type")]
-    content_type: String,
-    name: String,
-}
-
-// Add TreeResponse structures for the tree API
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-struct TreeItem {
-    path: String,
-    mode: String,
-    #[serde(rename = "type")]
-    item_type: String,
-    sha: String,
-    url: Option<String>,
-}
-
-#[derive(Deserialize, Debug)]
-struct TreeResponse {
-    tree: Vec<TreeItem>,
-    truncated: bool,
-}
-
 // Rest of the original data structures
 #[derive(Debug)]
 pub struct EpochFolders {
@@ -99,51 +72,20 @@ fn find_closest_transaction_folder(
 }
 
 pub async fn find_closest_epoch_folder(
-    client: &Client,
-    owner: &str,
-    repo: &str,
-    branch: &str,
+    source: &dyn ArchiveSource,
     target_epoch: u64,
 ) -> Result<EpochFolders> {
-    // Update to use the Git Tree API instead of Contents API
-    let api_url = format!(
-        "https://api.github.com/repos/{}/{}/git/trees/{}:snapshots",
-        owner, repo, branch
-    );
-
-    let response = client
-        .get(&api_url)
-        .header("User-Agent", "libra-framework-downloader")
-        .send()
+    let folder_names = source
+        .list_folders()
         .await
         .context("Failed to list snapshots directory")?;
 
-    let tree_response: TreeResponse = response
-        .json()
-        .await
-        .context("Failed to parse snapshots directory contents")?;
-
-    if tree_response.truncated {
-        info!("Warning: GitHub Tree API response is truncated. Some folders might be missing.");
-    }
-
     // Separate folders by type
     let mut epoch_ending_folders: Vec<(u64, String)> = Vec::new();
     let mut state_epoch_folders: Vec<(u64, String)> = Vec::new();
     let mut transaction_folders: Vec<(u64, String)> = Vec::new();
 
-    for item in tree_response.tree {
-        // Only consider tree items (directories)
-        if item.item_type != "tree" {
-            continue;
-        }
-
-        // Extract just the folder name from the path
-        let folder_name = match Path::new(&item.path).file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => continue,
-        };
-
+    for folder_name in folder_names {
         if let Some(epoch) = parse_epoch_ending_number(&folder_name) {
             epoch_ending_folders.push((epoch, folder_name));
         } else if let Some((epoch, _version)) = parse_state_epoch_info(&folder_name) {
@@ -216,94 +158,8 @@ fn find_matching_state_epoch(folders: &[(u64, String)], target: u64) -> Result<S
     }
 }
 
-pub async fn download_github_folder(
-    owner: &str,
-    repo: &str,
-    path: &str,
-    branch: &str,
-    output_dir: &str,
-) -> Result<()> {
-    let client = reqwest::Client::new();
-
-    // Create the root output directory first
-    fs::create_dir_all(output_dir)?;
-
-    // Use the Git Tree API with recursive flag to get all contents at once
-    let api_url = format!(
-        "https://api.github.com/repos/{}/{}/git/trees/{}:{}?recursive=1",
-        owner, repo, branch, path
-    );
-
-    info!("Downloading tree from: {}", api_url);
-
-    let response = client
-        .get(&api_url)
-        .header("User-Agent", "libra-framework-downloader")
-        .send()
-        .await
-        .context("Failed to send tree request")?;
-
-    let tree_response: TreeResponse = response
-        .json()
-        .await
-        .context("Failed to parse JSON tree response")?;
-
-    if tree_response.truncated {
-        info!("Warning: Response was truncated, not all files will be downloaded");
-    }
-
-    // Get the base path to properly handle nested directories
-    let base_path = Path::new(path)
-        .file_name()
-        .map_or(String::new(), |name| name.to_string_lossy().to_string());
-    let base_dir = Path::new(output_dir).join(base_path);
-    fs::create_dir_all(&base_dir)?;
-
-    // Process files from the tree
-    for item in tree_response.tree {
-        // Skip if not a blob (file)
-        if item.item_type != "blob" {
-            continue;
-        }
-
-        // The path in tree response is relative to the requested path
-        let relative_path = item.path;
-
-        // Compute where to save the file
-        let output_path = base_dir.join(&relative_path);
-
-        // Create parent directory if it doesn't exist
-        if let Some(parent) = output_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        // Download the file content using raw GitHub URL
-        let content_url = format!(
-            "https://raw.githubusercontent.com/{}/{}/{}/{}/{}",
-            owner, repo, branch, path, relative_path
-        );
-
-        let content = client
-            .get(&content_url)
-            .header("User-Agent", "libra-framework-downloader")
-            .send()
-            .await
-            .with_context(|| format!("Failed to download file: {}", relative_path))?
-            .bytes()
-            .await
-            .with_context(|| format!("Failed to read bytes from: {}", relative_path))?;
-
-        fs::write(&output_path, content)
-            .with_context(|| format!("Failed to write file: {}", relative_path))?;
-    }
-
-    Ok(())
-}
-
 pub async fn download_restore_bundle(
-    owner: &str,
-    repo: &str,
-    branch: &str,
+    source: &dyn ArchiveSource,
     epoch_num: &u64,
     destination: &Path,
 ) -> Result<PathBuf> {
@@ -314,9 +170,8 @@ pub async fn download_restore_bundle(
         fs::create_dir_all(&bundle_dir)?;
     }
 
-    let client = reqwest::Client::new();
-
-    let folders = find_closest_epoch_folder(&client, owner, repo, branch, *epoch_num).await?;
+    println!("downloading from {}", source.describe());
+    let folders = find_closest_epoch_folder(source, *epoch_num).await?;
 
     // Download all three folders
     for folder in [
@@ -324,15 +179,7 @@ pub async fn download_restore_bundle(
         &folders.state_epoch,
         &folders.transaction,
     ] {
-        let snapshot_path = format!("snapshots/{}", folder);
-        download_github_folder(
-            owner,
-            repo,
-            &snapshot_path,
-            branch,
-            bundle_dir.to_str().unwrap(),
-        )
-        .await?;
+        download_folder(source, folder, &bundle_dir).await?;
     }
 
     println!(
@@ -0,0 +1,311 @@
+//! Export a V7 state snapshot, decoding the 0L resources of every account.
+//! Snapshot chunks are ordered by key hash, so one account's resources are
+//! spread over all chunks. Rather than holding every `AccountState` in
+//! memory, each resource is decoded and written as soon as its chunk is read.
+
+use crate::snapshot_reader::SnapshotReader;
+use anyhow::{Context, Result};
+use diem_types::{
+    access_path::Path as AccessPathKind, account_address::AccountAddress,
+    state_store::state_key::StateKeyInner,
+};
+use futures::TryStreamExt;
+use libra_types::move_resource::{
+    ancestry::AncestryResource,
+    cumulative_deposits::CumulativeDepositResource,
+    donor_voice::RegistryResource,
+    donor_voice_txs::TxScheduleResource,
+    libra_coin::LibraCoinStoreResource,
+    pledge_account::MyPledgesResource,
+    receipts::ReceiptsResource,
+    vouch::MyVouchesResource,
+    wallet::{CommunityWalletsResource, SlowWalletResource},
+};
+use move_core_types::{
+    language_storage::StructTag,
+    move_resource::{MoveResource, MoveStructType},
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Output format of the export
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// one JSON object per resource, in `snapshot.ndjson`
+    #[default]
+    Ndjson,
+    /// one CSV table per resource type, `<resource>.csv`
+    Csv,
+}
+
+/// What was exported
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub accounts: usize,
+    /// rows written per resource table
+    pub tables: BTreeMap<&'static str, u64>,
+    /// resources of types not in the export
+    pub skipped: u64,
+}
+
+pub(crate) type Decoder = fn(&StructTag, &[u8]) -> Option<Result<Value>>;
+
+pub(crate) fn decode<T: MoveResource + Serialize>(
+    tag: &StructTag,
+    bytes: &[u8],
+) -> Option<Result<Value>> {
+    if *tag != T::struct_tag() {
+        return None;
+    }
+    Some(
+        bcs::from_bytes::<T>(bytes)
+            .map_err(anyhow::Error::from)
+            .and_then(|r| Ok(serde_json::to_value(r)?)),
+    )
+}
+
+/// The resource tables in the export
+const TABLES: &[(&str, Decoder)] = &[
+    ("coin_store", decode::<LibraCoinStoreResource>),
+    ("slow_wallet", decode::<SlowWalletResource>),
+    ("vouch", decode::<MyVouchesResource>),
+    ("ancestry", decode::<AncestryResource>),
+    ("receipts", decode::<ReceiptsResource>),
+    ("pledges", decode::<MyPledgesResource>),
+    ("donor_voice_registry", decode::<RegistryResource>),
+    ("donor_voice_tx_schedule", decode::<TxScheduleResource>),
+    ("community_wallet", decode::<CommunityWalletsResource>),
+    ("cumulative_deposits", decode::<CumulativeDepositResource>),
+];
+
+/// Decodes a resource if it is one of the exported types, returning the
+/// table name and the resource as JSON.
+pub fn decode_resource(tag: &StructTag, bytes: &[u8]) -> Option<(&'static str, Result<Value>)> {
+    TABLES
+        .iter()
+        .find_map(|(name, f)| f(tag, bytes).map(|r| (*name, r)))
+}
+
+/// One CSV table. Resources of one type don't all flatten to the same
+/// columns, e.g. an empty option or a map, so rows are spilled to a file
+/// until the export ends and the header holds the union of their columns.
+struct CsvTable {
+    spill_path: PathBuf,
+    spill: BufWriter<File>,
+    columns: BTreeSet<String>,
+}
+
+enum Writer {
+    Ndjson(BufWriter<File>),
+    Csv {
+        dir: PathBuf,
+        tables: HashMap<&'static str, CsvTable>,
+    },
+}
+
+impl Writer {
+    fn new(format: ExportFormat, out_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(out_dir)?;
+        Ok(match format {
+            ExportFormat::Ndjson => Writer::Ndjson(BufWriter::new(File::create(
+                out_dir.join("snapshot.ndjson"),
+            )?)),
+            ExportFormat::Csv => Writer::Csv {
+                dir: out_dir.to_owned(),
+                tables: HashMap::new(),
+            },
+        })
+    }
+
+    fn write(&mut self, table: &'static str, address: AccountAddress, value: Value) -> Result<()> {
+        match self {
+            Writer::Ndjson(w) => {
+                let line = json!({
+                    "address": address.to_hex_literal(),
+                    "resource": table,
+                    "value": value,
+                });
+                writeln!(w, "{line}")?;
+            }
+            Writer::Csv { dir, tables } => {
+                let mut row = BTreeMap::new();
+                flatten("", &value, &mut row);
+                if !tables.contains_key(table) {
+                    let spill_path = dir.join(format!(".{table}.rows"));
+                    tables.insert(
+                        table,
+                        CsvTable {
+                            spill: BufWriter::new(File::create(&spill_path)?),
+                            spill_path,
+                            columns: BTreeSet::new(),
+                        },
+                    );
+                }
+                let t = tables.get_mut(table).expect("table was just added");
+                t.columns.extend(row.keys().cloned());
+                let line = serde_json::to_string(&(address.to_hex_literal(), row))?;
+                writeln!(t.spill, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Writer::Ndjson(mut w) => w.flush()?,
+            Writer::Csv { dir, tables } => {
+                for (table, t) in tables {
+                    drop(t.spill.into_inner()?);
+                    let mut w = csv::Writer::from_path(dir.join(format!("{table}.csv")))?;
+                    w.write_record(
+                        std::iter::once("address").chain(t.columns.iter().map(|c| c.as_str())),
+                    )?;
+                    let spill = BufReader::new(File::open(&t.spill_path)?);
+                    for line in spill.lines() {
+                        let (address, row): (String, BTreeMap<String, String>) =
+                            serde_json::from_str(&line?)?;
+                        w.write_record(
+                            std::iter::once(address.as_str()).chain(
+                                t.columns
+                                    .iter()
+                                    .map(|c| row.get(c).map(|s| s.as_str()).unwrap_or_default()),
+                            ),
+                        )?;
+                    }
+                    w.flush()?;
+                    fs::remove_file(&t.spill_path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Flattens nested objects into dotted column names. Lists stay JSON, since
+/// their length varies between rows.
+fn flatten(prefix: &str, value: &Value, row: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                let key = if prefix.is_empty() {
+                    k.to_owned()
+                } else {
+                    format!("{prefix}.{k}")
+                };
+                flatten(&key, v, row);
+            }
+        }
+        Value::String(s) => {
+            row.insert(prefix.to_owned(), s.to_owned());
+        }
+        other => {
+            row.insert(prefix.to_owned(), other.to_string());
+        }
+    }
+}
+
+/// Exports the snapshot at `manifest_path` to `out_dir`, one chunk at a time.
+pub async fn export_snapshot(
+    manifest_path: &Path,
+    out_dir: &Path,
+    format: ExportFormat,
+) -> Result<ExportSummary> {
+    let mut chunks = SnapshotReader::new(manifest_path)?.chunks();
+    let mut writer = Writer::new(format, out_dir)?;
+    let mut summary = ExportSummary::default();
+    let mut accounts = BTreeSet::new();
+
+    while let Some(records) = chunks.try_next().await? {
+        for (key, value) in records {
+            let StateKeyInner::AccessPath(ap) = key.inner() else {
+                continue;
+            };
+            accounts.insert(ap.address);
+            let AccessPathKind::Resource(tag) = ap.get_path() else {
+                continue;
+            };
+            match decode_resource(&tag, value.bytes()) {
+                Some((table, res)) => {
+                    let json = res.with_context(|| {
+                        format!("cannot decode {table} of account {}", ap.address)
+                    })?;
+                    writer.write(table, ap.address, json)?;
+                    *summary.tables.entry(table).or_default() += 1;
+                }
+                None => summary.skipped += 1,
+            }
+        }
+    }
+
+    writer.finish()?;
+    summary.accounts = accounts.len();
+    Ok(summary)
+}
+
+#[test]
+fn decode_known_resources() {
+    let slow = SlowWalletResource {
+        unlocked: 10,
+        transferred: 3,
+    };
+    let bytes = bcs::to_bytes(&slow).unwrap();
+    let (table, value) = decode_resource(&SlowWalletResource::struct_tag(), &bytes).unwrap();
+    assert_eq!(table, "slow_wallet");
+    assert_eq!(value.unwrap()["unlocked"], 10);
+
+    // other types are skipped
+    let other = StructTag {
+        address: AccountAddress::ONE,
+        module: "account".parse().unwrap(),
+        name: "Account".parse().unwrap(),
+        type_params: vec![],
+    };
+    assert!(decode_resource(&other, &bytes).is_none());
+
+    // bytes that don't match the type are an error, not a skip
+    let (_, value) = decode_resource(&SlowWalletResource::struct_tag(), &[1]).unwrap();
+    assert!(value.is_err());
+}
+
+#[test]
+fn flatten_nested_rows() {
+    let value = json!({
+        "coin": 5,
+        "deposit_events": { "counter": 2, "guid": { "id": { "addr": "0x1" } } },
+        "list": ["0x1", "0x2"],
+    });
+    let mut row = BTreeMap::new();
+    flatten("", &value, &mut row);
+    assert_eq!(row["coin"], "5");
+    assert_eq!(row["deposit_events.counter"], "2");
+    assert_eq!(row["deposit_events.guid.id.addr"], "0x1");
+    assert_eq!(row["list"], r#"["0x1","0x2"]"#);
+}
+
+#[test]
+fn csv_header_has_every_column() {
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let mut w = Writer::new(ExportFormat::Csv, dir.path()).unwrap();
+    w.write("pledges", AccountAddress::ONE, json!({ "a": 1 }))
+        .unwrap();
+    w.write(
+        "pledges",
+        AccountAddress::TWO,
+        json!({ "a": 2, "b": { "c": 3 } }),
+    )
+    .unwrap();
+    w.finish().unwrap();
+
+    let csv = fs::read_to_string(dir.path().join("pledges.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines, vec!["address,a,b.c", "0x1,1,", "0x2,2,3"]);
+    // only the tables are left
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
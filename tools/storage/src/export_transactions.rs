@@ -0,0 +1,416 @@
+//! Export a transaction backup archive to tables for analytics warehouses.
+//! User transactions are decoded into their entry function calls and events
+//! are flattened into a separate table, so the data can be loaded without
+//! running a node. Each archive chunk is written as soon as it is read.
+
+use crate::read_tx_chunk::{load_chunk, load_tx_chunk_manifest, TransactionArchiveChunk};
+use anyhow::{Context, Result};
+use arrow::{
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    json::ReaderBuilder,
+};
+use diem_types::transaction::{Transaction, TransactionPayload};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+/// rows per parquet record batch
+const BATCH_SIZE: usize = 1024;
+
+/// Output format of the export
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TxExportFormat {
+    /// `transactions.ndjson` and `events.ndjson`
+    #[default]
+    Ndjson,
+    /// `transactions.parquet` and `events.parquet`, snappy compressed
+    Parquet,
+}
+
+/// One row per transaction
+#[derive(Debug, Serialize)]
+pub struct TransactionRow {
+    pub version: u64,
+    /// of the latest block metadata, none before the first block of the export
+    pub timestamp_usecs: Option<u64>,
+    pub tx_hash: String,
+    /// user, block_metadata, state_checkpoint, genesis, or other
+    pub kind: String,
+    pub sender: Option<String>,
+    pub sequence_number: Option<u64>,
+    /// entry_function, script, or other, for user transactions
+    pub payload_kind: Option<String>,
+    /// <ADDRESS>::<MODULE>::<FUNCTION> of an entry function call
+    pub function: Option<String>,
+    /// JSON list of type arguments
+    pub type_args: Option<String>,
+    /// JSON list of hex encoded BCS arguments
+    pub args: Option<String>,
+    pub gas_used: u64,
+    pub gas_unit_price: Option<u64>,
+    pub max_gas_amount: Option<u64>,
+    pub success: bool,
+    pub status: String,
+}
+
+impl TransactionRow {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("version", DataType::UInt64, false),
+            Field::new("timestamp_usecs", DataType::UInt64, true),
+            Field::new("tx_hash", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("sender", DataType::Utf8, true),
+            Field::new("sequence_number", DataType::UInt64, true),
+            Field::new("payload_kind", DataType::Utf8, true),
+            Field::new("function", DataType::Utf8, true),
+            Field::new("type_args", DataType::Utf8, true),
+            Field::new("args", DataType::Utf8, true),
+            Field::new("gas_used", DataType::UInt64, false),
+            Field::new("gas_unit_price", DataType::UInt64, true),
+            Field::new("max_gas_amount", DataType::UInt64, true),
+            Field::new("success", DataType::Boolean, false),
+            Field::new("status", DataType::Utf8, false),
+        ]))
+    }
+}
+
+/// One row per event, joined to transactions by version
+#[derive(Debug, Serialize)]
+pub struct EventRow {
+    pub version: u64,
+    /// position of the event in the transaction
+    pub event_index: u64,
+    pub type_tag: String,
+    /// hex encoded BCS of the event
+    pub data: String,
+}
+
+impl EventRow {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("version", DataType::UInt64, false),
+            Field::new("event_index", DataType::UInt64, false),
+            Field::new("type_tag", DataType::Utf8, false),
+            Field::new("data", DataType::Utf8, false),
+        ]))
+    }
+}
+
+/// What was exported
+#[derive(Debug, Default)]
+pub struct TxExportSummary {
+    pub first_version: u64,
+    pub last_version: u64,
+    pub transactions: u64,
+    pub user_transactions: u64,
+    pub events: u64,
+}
+
+/// Flattens one archive chunk into transaction and event rows.
+/// `timestamp_usecs` is that of the latest block metadata, carried from one
+/// chunk to the next, so only rows before the first block metadata of the
+/// whole export have no timestamp.
+pub fn chunk_to_rows(
+    chunk: &TransactionArchiveChunk,
+    timestamp_usecs: &mut Option<u64>,
+) -> (Vec<TransactionRow>, Vec<EventRow>) {
+    let mut txs = vec![];
+    let mut events = vec![];
+
+    for (i, (txn, info)) in chunk.txns.iter().zip(&chunk.txn_infos).enumerate() {
+        let version = chunk.manifest.first_version + i as u64;
+        let mut row = TransactionRow {
+            version,
+            timestamp_usecs: *timestamp_usecs,
+            tx_hash: info.transaction_hash().to_hex_literal(),
+            kind: "other".to_owned(),
+            sender: None,
+            sequence_number: None,
+            payload_kind: None,
+            function: None,
+            type_args: None,
+            args: None,
+            gas_used: info.gas_used(),
+            gas_unit_price: None,
+            max_gas_amount: None,
+            success: info.status().is_success(),
+            status: format!("{:?}", info.status()),
+        };
+
+        if let Transaction::UserTransaction(signed) = txn {
+            row.kind = "user".to_owned();
+            row.sender = Some(signed.sender().to_hex_literal());
+            row.sequence_number = Some(signed.sequence_number());
+            row.gas_unit_price = Some(signed.gas_unit_price());
+            row.max_gas_amount = Some(signed.max_gas_amount());
+            if let TransactionPayload::EntryFunction(ef) = signed.payload() {
+                row.payload_kind = Some("entry_function".to_owned());
+                row.function = Some(format!(
+                    "{}::{}",
+                    ef.module().short_str_lossless(),
+                    ef.function()
+                ));
+                let type_args: Vec<String> = ef.ty_args().iter().map(|t| t.to_string()).collect();
+                row.type_args = serde_json::to_string(&type_args).ok();
+                let args: Vec<String> = ef.args().iter().map(hex::encode).collect();
+                row.args = serde_json::to_string(&args).ok();
+            } else if matches!(signed.payload(), TransactionPayload::Script(_)) {
+                row.payload_kind = Some("script".to_owned());
+            } else {
+                row.payload_kind = Some("other".to_owned());
+            }
+        } else if let Transaction::BlockMetadata(block) = txn {
+            row.kind = "block_metadata".to_owned();
+            *timestamp_usecs = Some(block.timestamp_usecs());
+            row.timestamp_usecs = *timestamp_usecs;
+        } else if matches!(txn, Transaction::StateCheckpoint(_)) {
+            row.kind = "state_checkpoint".to_owned();
+        } else if matches!(txn, Transaction::GenesisTransaction(_)) {
+            row.kind = "genesis".to_owned();
+        }
+        txs.push(row);
+
+        for (event_index, event) in chunk.event_vecs[i].iter().enumerate() {
+            events.push(EventRow {
+                version,
+                event_index: event_index as u64,
+                type_tag: event.type_tag().to_string(),
+                data: hex::encode(event.event_data()),
+            });
+        }
+    }
+    (txs, events)
+}
+
+/// Writes rows of one table, in either format
+enum TableWriter {
+    Ndjson(BufWriter<File>),
+    Parquet(ArrowWriter<File>, SchemaRef),
+}
+
+impl TableWriter {
+    fn new(format: TxExportFormat, out_dir: &Path, name: &str, schema: SchemaRef) -> Result<Self> {
+        Ok(match format {
+            TxExportFormat::Ndjson => {
+                let file = File::create(out_dir.join(format!("{name}.ndjson")))?;
+                TableWriter::Ndjson(BufWriter::new(file))
+            }
+            TxExportFormat::Parquet => {
+                let file = File::create(out_dir.join(format!("{name}.parquet")))?;
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+                TableWriter::Parquet(writer, schema)
+            }
+        })
+    }
+
+    fn write<T: Serialize>(&mut self, rows: &[T]) -> Result<()> {
+        match self {
+            TableWriter::Ndjson(w) => {
+                for row in rows {
+                    serde_json::to_writer(&mut *w, row)?;
+                    writeln!(w)?;
+                }
+            }
+            TableWriter::Parquet(w, schema) => {
+                let mut decoder = ReaderBuilder::new(schema.clone())
+                    .with_batch_size(BATCH_SIZE)
+                    .build_decoder()?;
+                for part in rows.chunks(BATCH_SIZE) {
+                    decoder.serialize(part)?;
+                    if let Some(batch) = decoder.flush()? {
+                        w.write(&batch)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            TableWriter::Ndjson(mut w) => w.flush()?,
+            TableWriter::Parquet(w, _) => {
+                w.close()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Exports the transaction backup at `manifest_path` to `out_dir`, one chunk at a time.
+pub async fn export_transactions(
+    manifest_path: &Path,
+    out_dir: &Path,
+    format: TxExportFormat,
+) -> Result<TxExportSummary> {
+    let manifest = load_tx_chunk_manifest(manifest_path)?;
+    // chunk handles are relative to the backup root, the archive dir's
+    // parent, so a bare relative manifest path needs resolving first
+    let manifest_path = manifest_path
+        .canonicalize()
+        .with_context(|| format!("cannot resolve {}", manifest_path.display()))?;
+    let archive_path = manifest_path
+        .parent()
+        .context("manifest path has no parent")?;
+
+    fs::create_dir_all(out_dir)?;
+    let mut tx_writer =
+        TableWriter::new(format, out_dir, "transactions", TransactionRow::schema())?;
+    let mut event_writer = TableWriter::new(format, out_dir, "events", EventRow::schema())?;
+    let mut summary = TxExportSummary {
+        first_version: manifest.first_version,
+        last_version: manifest.last_version,
+        ..Default::default()
+    };
+
+    let mut timestamp_usecs = None;
+    for chunk_manifest in manifest.chunks {
+        let chunk = load_chunk(archive_path, chunk_manifest).await?;
+        let (txs, events) = chunk_to_rows(&chunk, &mut timestamp_usecs);
+        summary.transactions += txs.len() as u64;
+        summary.user_transactions += txs.iter().filter(|t| t.kind == "user").count() as u64;
+        summary.events += events.len() as u64;
+        tx_writer.write(&txs)?;
+        event_writer.write(&events)?;
+    }
+
+    tx_writer.finish()?;
+    event_writer.finish()?;
+    Ok(summary)
+}
+
+#[test]
+fn write_both_formats() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let rows: Vec<EventRow> = (0..3000)
+        .map(|i| EventRow {
+            version: i,
+            event_index: 0,
+            type_tag: "0x1::coin::DepositEvent".to_owned(),
+            data: "00".to_owned(),
+        })
+        .collect();
+
+    let temp = diem_temppath::TempPath::new();
+    temp.create_as_dir().unwrap();
+
+    let mut w = TableWriter::new(
+        TxExportFormat::Ndjson,
+        temp.path(),
+        "events",
+        EventRow::schema(),
+    )
+    .unwrap();
+    w.write(&rows).unwrap();
+    w.finish().unwrap();
+    let s = fs::read_to_string(temp.path().join("events.ndjson")).unwrap();
+    assert_eq!(s.lines().count(), 3000);
+
+    // more rows than one batch, written in two calls like two chunks
+    let mut w = TableWriter::new(
+        TxExportFormat::Parquet,
+        temp.path(),
+        "events",
+        EventRow::schema(),
+    )
+    .unwrap();
+    w.write(&rows).unwrap();
+    w.write(&rows[..10]).unwrap();
+    w.finish().unwrap();
+    let reader =
+        SerializedFileReader::new(File::open(temp.path().join("events.parquet")).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 3010);
+}
+
+#[test]
+fn rows_carry_block_timestamp() {
+    use diem_backup_cli::backup_types::transaction::manifest::TransactionChunk;
+    use diem_crypto::HashValue;
+    use diem_types::{
+        account_address::AccountAddress,
+        block_metadata::BlockMetadata,
+        transaction::{ExecutionStatus, TransactionInfo},
+        write_set::WriteSetMut,
+    };
+
+    let block = |timestamp_usecs| {
+        Transaction::BlockMetadata(BlockMetadata::new(
+            HashValue::zero(),
+            1,
+            1,
+            AccountAddress::ONE,
+            vec![],
+            vec![],
+            timestamp_usecs,
+        ))
+    };
+    let chunk = |first_version, txns: Vec<Transaction>| {
+        let n = txns.len();
+        TransactionArchiveChunk {
+            manifest: TransactionChunk {
+                first_version,
+                last_version: first_version + n as u64 - 1,
+                transactions: String::new(),
+                proof: String::new(),
+            },
+            txn_infos: txns
+                .iter()
+                .map(|_| {
+                    TransactionInfo::new(
+                        HashValue::zero(),
+                        HashValue::zero(),
+                        HashValue::zero(),
+                        None,
+                        0,
+                        ExecutionStatus::Success,
+                    )
+                })
+                .collect(),
+            txns,
+            event_vecs: vec![vec![]; n],
+            write_sets: vec![WriteSetMut::new(vec![]).freeze().unwrap(); n],
+        }
+    };
+
+    let mut timestamp_usecs = None;
+    let first = chunk(
+        10,
+        vec![
+            Transaction::StateCheckpoint(HashValue::zero()),
+            block(5),
+            Transaction::StateCheckpoint(HashValue::zero()),
+        ],
+    );
+    let (txs, events) = chunk_to_rows(&first, &mut timestamp_usecs);
+    assert!(events.is_empty());
+    let kinds: Vec<&str> = txs.iter().map(|t| t.kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        vec!["state_checkpoint", "block_metadata", "state_checkpoint"]
+    );
+    assert_eq!(txs[0].timestamp_usecs, None);
+    assert_eq!(txs[1].timestamp_usecs, Some(5));
+    assert_eq!(txs[2].version, 12);
+    assert_eq!(txs[2].timestamp_usecs, Some(5));
+
+    // the next chunk starts with the timestamp of the last block
+    let second = chunk(
+        13,
+        vec![Transaction::StateCheckpoint(HashValue::zero()), block(9)],
+    );
+    let (txs, _) = chunk_to_rows(&second, &mut timestamp_usecs);
+    assert_eq!(txs[0].version, 13);
+    assert_eq!(txs[0].timestamp_usecs, Some(5));
+    assert_eq!(txs[1].timestamp_usecs, Some(9));
+    assert!(txs.iter().all(|t| t.success && t.sender.is_none()));
+}
@@ -0,0 +1,344 @@
+//! Look into a node's DB while the node is stopped.
+//! The DB is opened read-only, so a running node is not disturbed, but its
+//! latest writes may not be visible.
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use diem_config::config::{
+    RocksdbConfigs, BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use diem_db::DiemDB;
+use diem_debugger::DiemDebugger;
+use diem_resource_viewer::DiemValueAnnotator;
+use diem_storage_interface::{state_view::DbStateViewAtVersion, DbReader, DbReaderWriter};
+use diem_types::{
+    account_address::AccountAddress,
+    ledger_info::LedgerInfoWithSignatures,
+    on_chain_config::{OnChainConfig, ValidatorSet},
+    state_store::state_key::StateKey,
+    transaction::Version,
+    waypoint::Waypoint,
+};
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// the most epoch ending ledger infos the DB returns at once
+const EPOCH_PAGE: u64 = 100;
+
+/// transactions read at once when searching events
+const TXN_PAGE: u64 = 1000;
+
+#[derive(Args, Debug)]
+pub struct InspectOpts {
+    #[clap(long = "db")]
+    /// path of the node's DB, e.g. ~/.libra/data/db
+    pub db_path: PathBuf,
+    #[clap(subcommand)]
+    pub cmd: InspectCmd,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InspectCmd {
+    /// Latest version, epoch, waypoint, and validator set
+    LedgerInfo,
+    /// The ledger info at the end of each epoch
+    Epochs {
+        #[clap(long, default_value_t = 0)]
+        from_epoch: u64,
+        #[clap(long)]
+        /// defaults to the latest ended epoch
+        to_epoch: Option<u64>,
+    },
+    /// An account's resources, annotated with their field names
+    Account {
+        account: AccountAddress,
+        #[clap(long)]
+        /// defaults to the latest version
+        version: Option<Version>,
+    },
+    /// Events whose type contains a string, e.g. `vouch::VouchEvent`
+    Events {
+        #[clap(long = "type")]
+        /// part of the event's type tag
+        type_filter: String,
+        #[clap(long)]
+        /// defaults to `--limit-versions` before the latest version
+        from_version: Option<Version>,
+        #[clap(long)]
+        /// defaults to the latest version
+        to_version: Option<Version>,
+        #[clap(long, default_value_t = 100_000)]
+        /// versions searched when no --from-version is given
+        limit_versions: u64,
+    },
+}
+
+impl InspectOpts {
+    pub async fn run(self) -> Result<()> {
+        let db = open_read_only(&self.db_path)?;
+        let out = match self.cmd {
+            InspectCmd::LedgerInfo => ledger_info(&db)?,
+            InspectCmd::Epochs {
+                from_epoch,
+                to_epoch,
+            } => epochs(&db, from_epoch, to_epoch)?,
+            InspectCmd::Account { account, version } => {
+                let version = match version {
+                    Some(v) => v,
+                    None => db.get_latest_version()?,
+                };
+                // the debugger opens its own read-only handle
+                drop(db);
+                account_state(&self.db_path, account, version).await?
+            }
+            InspectCmd::Events {
+                type_filter,
+                from_version,
+                to_version,
+                limit_versions,
+            } => {
+                let to = match to_version {
+                    Some(v) => v,
+                    None => db.get_latest_version()?,
+                };
+                let from = from_version.unwrap_or_else(|| to.saturating_sub(limit_versions));
+                events(&DbReaderWriter::new(db).reader, &type_filter, from, to)?
+            }
+        };
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        Ok(())
+    }
+}
+
+/// Opens the DB without write access, it may belong to a node
+pub fn open_read_only(db_path: &Path) -> Result<DiemDB> {
+    DiemDB::open(
+        db_path,
+        true,                        /* readonly */
+        NO_OP_STORAGE_PRUNER_CONFIG, /* pruner_config */
+        RocksdbConfigs::default(),   /* rocksdb_configs */
+        false,                       /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    )
+    .with_context(|| format!("cannot open db at {}", db_path.display()))
+}
+
+fn ledger_info_json(li: &LedgerInfoWithSignatures) -> Result<Value> {
+    let li = li.ledger_info();
+    Ok(json!({
+        "epoch": li.epoch(),
+        "round": li.round(),
+        "version": li.version(),
+        "timestamp_usecs": li.timestamp_usecs(),
+        "transaction_accumulator_hash": li.transaction_accumulator_hash().to_hex_literal(),
+        "ends_epoch": li.ends_epoch(),
+        "waypoint": Waypoint::new_any(li).to_string(),
+    }))
+}
+
+fn ledger_info(db: &DiemDB) -> Result<Value> {
+    let latest = db.get_latest_ledger_info()?;
+    let version = latest.ledger_info().version();
+    let epoch = latest.ledger_info().epoch();
+
+    // the waypoint a node syncing to this DB would start from
+    let epoch_waypoint = if epoch > 0 {
+        db.get_epoch_ending_ledger_infos(epoch - 1, epoch)?
+            .ledger_info_with_sigs
+            .last()
+            .map(|li| Waypoint::new_epoch_boundary(li.ledger_info()))
+            .transpose()?
+            .map(|wp| wp.to_string())
+    } else {
+        None
+    };
+
+    let key = StateKey::access_path(ValidatorSet::access_path()?);
+    let validators: Vec<Value> = match db.get_state_value_by_version(&key, version)? {
+        Some(value) => {
+            let set: ValidatorSet = bcs::from_bytes(value.bytes())?;
+            set.active_validators()
+                .iter()
+                .map(|v| {
+                    json!({
+                        "address": v.account_address().to_hex_literal(),
+                        "voting_power": v.consensus_voting_power(),
+                    })
+                })
+                .collect()
+        }
+        None => vec![],
+    };
+
+    Ok(json!({
+        "latest_ledger_info": ledger_info_json(&latest)?,
+        "latest_epoch_waypoint": epoch_waypoint,
+        "validator_set": validators,
+    }))
+}
+
+fn epochs(db: &DiemDB, from_epoch: u64, to_epoch: Option<u64>) -> Result<Value> {
+    let latest = db.get_latest_ledger_info()?;
+    // an epoch has ended if the next one has begun
+    let last_ended = latest.ledger_info().next_block_epoch().checked_sub(1);
+    let Some(last_ended) = last_ended else {
+        return Ok(json!([]));
+    };
+    let to_epoch = to_epoch.unwrap_or(last_ended).min(last_ended);
+
+    let mut out = vec![];
+    let mut start = from_epoch;
+    while start <= to_epoch {
+        let end = (start + EPOCH_PAGE).min(to_epoch + 1);
+        let proof = db.get_epoch_ending_ledger_infos(start, end)?;
+        for li in &proof.ledger_info_with_sigs {
+            let mut row = ledger_info_json(li)?;
+            row["next_validator_count"] = json!(li
+                .ledger_info()
+                .next_epoch_state()
+                .map(|s| s.verifier.len())
+                .unwrap_or_default());
+            out.push(row);
+        }
+        start = end;
+    }
+    Ok(Value::Array(out))
+}
+
+async fn account_state(db_path: &Path, account: AccountAddress, version: Version) -> Result<Value> {
+    let dbgger = DiemDebugger::db(db_path)?;
+    let Some(blob) = dbgger
+        .annotate_account_state_at_version(account, version)
+        .await?
+    else {
+        bail!("cannot find account state of {account} at version {version}");
+    };
+    Ok(json!({
+        "account": account.to_hex_literal(),
+        "version": version,
+        "resources": format!("{:#}", blob),
+    }))
+}
+
+/// Events are annotated with the type layouts at the latest version, and
+/// given as hex when they don't decode.
+fn events(db: &Arc<dyn DbReader>, type_filter: &str, from: Version, to: Version) -> Result<Value> {
+    let ledger_version = db.get_latest_version()?;
+    let to = to.min(ledger_version);
+    let view = db.state_view_at_version(Some(ledger_version))?;
+    let dvm = diem_vm::DiemVM::new(&view);
+    let adapter = dvm.as_move_resolver(&view);
+    let annotator = DiemValueAnnotator::new(&adapter);
+
+    let mut out = vec![];
+    let mut start = from;
+    while start <= to {
+        let limit = TXN_PAGE.min(to - start + 1);
+        let list = db.get_transactions(start, limit, ledger_version, true)?;
+        let events = list.events.unwrap_or_default();
+        for (i, txn_events) in events.iter().enumerate() {
+            let version = start + i as u64;
+            for (index, event) in txn_events.iter().enumerate() {
+                let type_tag = event.type_tag().to_string();
+                if type_tag.contains(type_filter) {
+                    out.push(json!({
+                        "version": version,
+                        "event_index": index,
+                        "type": type_tag,
+                        "data": annotator
+                            .view_value(event.type_tag(), event.event_data())
+                            .map(|v| format!("{}", v))
+                            .unwrap_or_else(|_| hex::encode(event.event_data())),
+                    }));
+                }
+            }
+        }
+        start += limit;
+    }
+    Ok(Value::Array(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diem_temppath::TempPath;
+
+    /// Extracts the epoch 339 DB fixture of the rescue tool. The TempPath
+    /// removes it when dropped.
+    fn db_339() -> (TempPath, PathBuf) {
+        let temp = TempPath::new();
+        temp.create_as_dir().unwrap();
+        let fixture =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../rescue/fixtures/db_339.tar.gz");
+        let tar_gz = std::fs::File::open(&fixture).expect("can't find fixture db_339.tar.gz");
+        tar::Archive::new(flate2::read::GzDecoder::new(tar_gz))
+            .unpack(temp.path())
+            .unwrap();
+        let path = temp.path().join("db_339");
+        (temp, path)
+    }
+
+    #[test]
+    fn ledger_info_and_epochs() {
+        let (_temp, path) = db_339();
+        let db = open_read_only(&path).unwrap();
+
+        let info = ledger_info(&db).unwrap();
+        let latest = &info["latest_ledger_info"];
+        assert!(latest["version"].as_u64().unwrap() > 0);
+        assert!(latest["epoch"].as_u64().unwrap() >= 339);
+        assert!(info["latest_epoch_waypoint"].is_string());
+        assert!(!info["validator_set"].as_array().unwrap().is_empty());
+
+        // the last ended epoch, and nothing past it
+        let ended = db
+            .get_latest_ledger_info()
+            .unwrap()
+            .ledger_info()
+            .next_block_epoch()
+            - 1;
+        let rows = epochs(&db, ended, Some(ended + 10)).unwrap();
+        let rows = rows.as_array().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["epoch"], ended);
+        assert_eq!(rows[0]["ends_epoch"], true);
+        assert!(rows[0]["next_validator_count"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn account_and_events() {
+        let (_temp, path) = db_339();
+        let db = open_read_only(&path).unwrap();
+        let version = db.get_latest_version().unwrap();
+        drop(db);
+
+        let account = account_state(&path, AccountAddress::ONE, version)
+            .await
+            .unwrap();
+        assert_eq!(account["account"], "0x1");
+        assert!(account["resources"]
+            .as_str()
+            .unwrap()
+            .contains("chain_id::ChainId"));
+        let missing = AccountAddress::from_hex_literal("0x1234abcd").unwrap();
+        assert!(account_state(&path, missing, version).await.is_err());
+
+        // the last transaction of the DB emits the block and epoch events
+        let reader = DbReaderWriter::new(open_read_only(&path).unwrap()).reader;
+        let rows = events(&reader, "::", version, version).unwrap();
+        let rows = rows.as_array().unwrap();
+        assert!(!rows.is_empty());
+        for row in rows {
+            assert_eq!(row["version"], version);
+            // annotated with field names, not hex
+            assert!(row["data"].as_str().unwrap().contains(':'), "{row}");
+        }
+        let none = events(&reader, "no_such_module::Event", version, version).unwrap();
+        assert!(none.as_array().unwrap().is_empty());
+    }
+}
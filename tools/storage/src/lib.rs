@@ -1,8 +1,19 @@
+pub mod archive_source;
 pub mod bootstrap;
 pub mod dbtool_init;
+pub mod diff_snapshots;
 pub mod download_bundle;
+pub mod export_snapshot;
+pub mod export_transactions;
+pub mod inspect;
+pub mod make_bundle;
 pub mod parse_folder_names;
+pub mod prune;
+pub mod range_restore;
 pub mod read_snapshot;
+pub mod read_tx_chunk;
 pub mod restore;
 pub mod restore_bundle;
+pub mod snapshot_reader;
 pub mod storage_cli;
+pub mod verify_bundle;
@@ -0,0 +1,260 @@
+//! Create a restore bundle for one epoch from a node's DB, in the layout of
+//! the `epoch-archive` repos, so any operator can publish archives.
+//! The DB is served by an in-process backup service to the same vendor
+//! backup controllers the node's backup coordinator uses.
+
+use crate::{archive_source::sha256_hex, inspect::open_read_only};
+use anyhow::{bail, Context, Result};
+use diem_backup_cli::{
+    backup_types::{
+        epoch_ending::backup::{EpochEndingBackupController, EpochEndingBackupOpt},
+        state_snapshot::backup::{StateSnapshotBackupController, StateSnapshotBackupOpt},
+        transaction::backup::{TransactionBackupController, TransactionBackupOpt},
+    },
+    storage::{local_fs::LocalFs, BackupStorage},
+    utils::{backup_service_client::BackupServiceClient, GlobalBackupOpt},
+};
+use diem_storage_interface::DbReader;
+use diem_types::waypoint::Waypoint;
+use flate2::{write::GzEncoder, Compression};
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// transaction archives are cut in blocks of this many versions
+pub const TRANSACTION_ARCHIVE_SIZE: u64 = 100_000;
+
+/// bytes per chunk file, the backup cli default
+const MAX_CHUNK_SIZE: usize = 128 * 1024 * 1024;
+
+/// Written to `bundle.json` next to the archive folders
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub epoch: u64,
+    /// version of the epoch's last ledger info, and of the state snapshot
+    pub version: u64,
+    pub waypoint: Waypoint,
+    pub epoch_ending: String,
+    pub state_snapshot: String,
+    pub transaction: String,
+    /// sha256 of every file, by path relative to the bundle dir
+    pub files: BTreeMap<String, String>,
+}
+
+/// The transaction archive containing `version`, as first version and number
+/// of transactions, cut at the archive boundaries and at the DB's latest version.
+pub fn transaction_range(version: u64, latest_version: u64) -> (u64, u64) {
+    let start = if version == 0 {
+        0
+    } else {
+        (version - 1) / TRANSACTION_ARCHIVE_SIZE * TRANSACTION_ARCHIVE_SIZE + 1
+    };
+    let end = (start + TRANSACTION_ARCHIVE_SIZE - 1).min(latest_version);
+    (start, end - start + 1)
+}
+
+/// Writes the epoch ending, state snapshot, and transaction archives of
+/// `epoch` from the DB at `db_path` to `out_dir`.
+pub async fn make_bundle(db_path: &Path, epoch: u64, out_dir: &Path) -> Result<BundleManifest> {
+    if out_dir.exists() && fs::read_dir(out_dir)?.next().is_some() {
+        bail!("output directory {} is not empty", out_dir.display());
+    }
+
+    let db = Arc::new(open_read_only(db_path)?);
+
+    let latest = db.get_latest_ledger_info()?;
+    if latest.ledger_info().next_block_epoch() <= epoch {
+        bail!(
+            "epoch {epoch} has not ended yet, the db is at epoch {}",
+            latest.ledger_info().epoch()
+        );
+    }
+    let ending = db
+        .get_epoch_ending_ledger_infos(epoch, epoch + 1)?
+        .ledger_info_with_sigs
+        .pop()
+        .with_context(|| format!("no ending ledger info for epoch {epoch}"))?;
+    let version = ending.ledger_info().version();
+    let waypoint = Waypoint::new_epoch_boundary(ending.ledger_info())?;
+    let (start_version, num_transactions) =
+        transaction_range(version, latest.ledger_info().version());
+
+    fs::create_dir_all(out_dir)?;
+    let storage: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(out_dir.to_owned()));
+    let global = GlobalBackupOpt {
+        max_chunk_size: MAX_CHUNK_SIZE,
+        concurrent_data_requests: num_cpus::get(),
+    };
+
+    // any free local port, the service only lives for this command
+    let address: SocketAddr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    let service = diem_backup_service::start_backup_service(address, db);
+    let client = Arc::new(BackupServiceClient::new(format!("http://{address}")));
+
+    let result = async {
+        let epoch_ending = EpochEndingBackupController::new(
+            EpochEndingBackupOpt {
+                start_epoch: epoch,
+                end_epoch: epoch + 1,
+            },
+            global.clone(),
+            client.clone(),
+            storage.clone(),
+        )
+        .run()
+        .await?;
+        let state_snapshot = StateSnapshotBackupController::new(
+            StateSnapshotBackupOpt { epoch },
+            global.clone(),
+            client.clone(),
+            storage.clone(),
+        )
+        .run()
+        .await?;
+        let transaction = TransactionBackupController::new(
+            TransactionBackupOpt {
+                start_version,
+                num_transactions: num_transactions as usize,
+            },
+            global,
+            client,
+            storage,
+        )
+        .run()
+        .await?;
+        anyhow::Ok([epoch_ending, state_snapshot, transaction])
+    }
+    .await;
+    // dropping a runtime is not allowed inside this one
+    service.shutdown_background();
+    let [epoch_ending, state_snapshot, transaction] = result?;
+
+    let mut folders = vec![];
+    for manifest_handle in [&epoch_ending, &state_snapshot, &transaction] {
+        let manifest = out_dir.join(manifest_handle);
+        compress_archive(&manifest)?;
+        folders.push(folder_name(manifest_handle)?);
+    }
+
+    let mut files = BTreeMap::new();
+    for entry in glob(&format!("{}/**/*", out_dir.display()))?.flatten() {
+        if entry.is_file() {
+            let rel = entry.strip_prefix(out_dir)?.to_string_lossy().into_owned();
+            files.insert(rel, sha256_hex(&entry)?);
+        }
+    }
+
+    let [epoch_ending, state_snapshot, transaction]: [String; 3] =
+        folders.try_into().expect("three archives");
+    let manifest = BundleManifest {
+        epoch,
+        version,
+        waypoint,
+        epoch_ending,
+        state_snapshot,
+        transaction,
+        files,
+    };
+    fs::write(
+        out_dir.join("bundle.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    Ok(manifest)
+}
+
+fn folder_name(manifest_handle: &str) -> Result<String> {
+    Ok(manifest_handle
+        .split('/')
+        .next()
+        .filter(|f| !f.is_empty())
+        .with_context(|| format!("manifest {manifest_handle} is not in a folder"))?
+        .to_owned())
+}
+
+/// Gzips every chunk and proof of the archive, and points the manifest at
+/// the `.gz` files, like the published archives. The manifest itself stays
+/// plain, so `RestoreBundle` can read it and strip the suffixes again.
+fn compress_archive(manifest: &Path) -> Result<()> {
+    let dir = manifest.parent().context("manifest path has no parent")?;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path == manifest || path.extension().map_or(false, |e| e == "gz") {
+            continue;
+        }
+        let mut gz_path = path.clone().into_os_string();
+        gz_path.push(".gz");
+        let mut encoder = GzEncoder::new(
+            File::create(PathBuf::from(gz_path))?,
+            Compression::default(),
+        );
+        io::copy(&mut File::open(&path)?, &mut encoder)?;
+        encoder.finish()?;
+        fs::remove_file(&path)?;
+    }
+    let content = fs::read_to_string(manifest)?;
+    fs::write(manifest, add_gz_to_manifest_paths(&content)?)?;
+    Ok(())
+}
+
+/// The reverse of `RestoreBundle::update_manifest_paths`
+fn add_gz_to_manifest_paths(manifest_content: &str) -> Result<String> {
+    fn add_gz(value: &mut Value) {
+        if let Some(path_str) = value.as_str() {
+            if path_str.contains('/') && !path_str.ends_with(".gz") {
+                *value = Value::String(format!("{path_str}.gz"));
+            }
+        }
+    }
+
+    let mut manifest: Value = serde_json::from_str(manifest_content)?;
+    if let Some(obj) = manifest.as_object_mut() {
+        if let Some(chunks) = obj.get_mut("chunks").and_then(|c| c.as_array_mut()) {
+            for chunk in chunks.iter_mut().filter_map(|c| c.as_object_mut()) {
+                chunk.values_mut().for_each(add_gz);
+            }
+        }
+        // top-level proof of the state manifest
+        if let Some(proof) = obj.get_mut("proof") {
+            add_gz(proof);
+        }
+    }
+    Ok(serde_json::to_string_pretty(&manifest)?)
+}
+
+#[test]
+fn transaction_archive_boundaries() {
+    assert_eq!(transaction_range(38180075, 40_000_000), (38100001, 100_000));
+    assert_eq!(transaction_range(38100001, 40_000_000), (38100001, 100_000));
+    assert_eq!(transaction_range(38100000, 40_000_000), (38000001, 100_000));
+    // the db may not reach the end of the archive
+    assert_eq!(transaction_range(38180075, 38180100), (38100001, 80_100));
+    assert_eq!(transaction_range(0, 5), (0, 6));
+}
+
+#[test]
+fn gz_manifest_paths_round_trip() {
+    use crate::restore_bundle::RestoreBundle;
+    let manifest =
+        fs::read_to_string("fixtures/v7/state_epoch_116_ver_38180075.05af/state.manifest").unwrap();
+    let original: Value = serde_json::from_str(&manifest).unwrap();
+
+    let gz = add_gz_to_manifest_paths(&manifest).unwrap();
+    let gz_value: Value = serde_json::from_str(&gz).unwrap();
+    assert!(gz_value["proof"].as_str().unwrap().ends_with(".proof.gz"));
+    let chunk = &gz_value["chunks"][0];
+    assert!(chunk["blobs"].as_str().unwrap().ends_with(".gz"));
+    assert!(chunk["proof"].as_str().unwrap().ends_with(".gz"));
+    // keys and versions are not paths
+    assert_eq!(chunk["first_key"], original["chunks"][0]["first_key"]);
+
+    let restored: Value = serde_json::from_str(&RestoreBundle::update_manifest_paths(&gz)).unwrap();
+    assert_eq!(restored, original);
+}
@@ -0,0 +1,319 @@
+//! Disk usage of a node's DB, and offline pruning of old versions.
+//! Archive nodes keep every version, fast-sync nodes keep what their pruner
+//! config allows; neither warns before the disk is full.
+
+use crate::inspect::open_read_only;
+use anyhow::{bail, Context, Result};
+use diem_config::config::{
+    EpochSnapshotPrunerConfig, LedgerPrunerConfig, PrunerConfig, RocksdbConfigs,
+    StateMerklePrunerConfig, BUFFERED_STATE_TARGET_ITEMS,
+    DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+};
+use diem_db::{
+    schema::db_metadata::{DbMetadataKey, DbMetadataValue},
+    DiemDB,
+};
+use diem_storage_interface::DbReader;
+use diem_types::transaction::Version;
+use libra_config::make_yaml_public_fullnode::set_pruner_window_in_yaml;
+use rocksdb::{Options, DB};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// seconds without pruner progress before giving up
+const PRUNE_STALL_SECS: u64 = 300;
+
+/// Each pruner, and the RocksDB instance whose `db_metadata` column family
+/// holds its progress
+const PRUNERS: &[(&str, &str, DbMetadataKey)] = &[
+    ("ledger", "ledger_db", DbMetadataKey::LedgerPrunerProgress),
+    (
+        "state merkle",
+        "state_merkle_db",
+        DbMetadataKey::StateMerklePrunerProgress,
+    ),
+    (
+        "epoch snapshot",
+        "state_merkle_db",
+        DbMetadataKey::EpochEndingStateMerklePrunerProgress,
+    ),
+];
+
+/// One column family of one of the node's RocksDB instances
+#[derive(Debug, Serialize)]
+pub struct ColumnFamilyUsage {
+    /// e.g. `ledger_db` or `state_merkle_db`
+    pub db: String,
+    pub column_family: String,
+    pub sst_bytes: u64,
+    pub estimated_keys: u64,
+}
+
+/// What `storage usage` reports
+#[derive(Debug, Serialize)]
+pub struct DbUsage {
+    /// bytes on disk of each RocksDB instance, logs and WAL included
+    pub disk_bytes: Vec<(String, u64)>,
+    pub column_families: Vec<ColumnFamilyUsage>,
+    pub latest_version: Version,
+    pub epoch: u64,
+    /// oldest transaction the ledger pruner kept
+    pub first_txn_version: Option<Version>,
+    /// oldest write set the ledger pruner kept
+    pub first_write_set_version: Option<Version>,
+}
+
+/// RocksDB instances under `db_path`, by their `CURRENT` file, e.g. shards
+fn rocksdb_dirs(db_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![];
+    let mut pending = vec![db_path.to_owned()];
+    while let Some(dir) = pending.pop() {
+        if dir.join("CURRENT").exists() {
+            dirs.push(dir);
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            }
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        total += if meta.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(total)
+}
+
+fn column_families(db_name: &str, dir: &Path) -> Result<Vec<ColumnFamilyUsage>> {
+    let opts = Options::default();
+    let names = DB::list_cf(&opts, dir)?;
+    let db = DB::open_cf_for_read_only(&opts, dir, &names, false)?;
+    let mut out = vec![];
+    for name in names {
+        let cf = db
+            .cf_handle(&name)
+            .with_context(|| format!("no column family {name} in {}", dir.display()))?;
+        out.push(ColumnFamilyUsage {
+            db: db_name.to_owned(),
+            sst_bytes: db
+                .property_int_value_cf(&cf, "rocksdb.total-sst-files-size")?
+                .unwrap_or_default(),
+            estimated_keys: db
+                .property_int_value_cf(&cf, "rocksdb.estimate-num-keys")?
+                .unwrap_or_default(),
+            column_family: name,
+        });
+    }
+    Ok(out)
+}
+
+/// Sizes of every column family, and the oldest versions still in the DB
+pub fn db_usage(db_path: &Path) -> Result<DbUsage> {
+    let mut disk_bytes = vec![];
+    let mut cfs = vec![];
+    for dir in rocksdb_dirs(db_path)? {
+        let name = dir.strip_prefix(db_path)?.display().to_string();
+        disk_bytes.push((name.clone(), dir_size(&dir)?));
+        cfs.extend(column_families(&name, &dir)?);
+    }
+    cfs.sort_by(|a, b| b.sst_bytes.cmp(&a.sst_bytes));
+
+    let db = open_read_only(db_path)?;
+    let latest = db.get_latest_ledger_info()?;
+    Ok(DbUsage {
+        disk_bytes,
+        column_families: cfs,
+        latest_version: latest.ledger_info().version(),
+        epoch: latest.ledger_info().epoch(),
+        first_txn_version: db.get_first_txn_version()?,
+        first_write_set_version: db.get_first_write_set_version()?,
+    })
+}
+
+/// The versions to keep so that the last `keep_epochs` ended epochs, and
+/// the current one, stay in the DB. None if the DB doesn't have more.
+fn prune_window(db: &DiemDB, keep_epochs: u64) -> Result<Option<u64>> {
+    let latest = db.get_latest_ledger_info()?;
+    let current_epoch = latest.ledger_info().next_block_epoch();
+    let Some(last_pruned_epoch) = current_epoch.checked_sub(keep_epochs + 1) else {
+        return Ok(None);
+    };
+    let ending = db
+        .get_epoch_ending_ledger_infos(last_pruned_epoch, last_pruned_epoch + 1)?
+        .ledger_info_with_sigs
+        .pop()
+        .with_context(|| format!("no ending ledger info for epoch {last_pruned_epoch}"))?;
+    Ok(Some(
+        latest.ledger_info().version() - ending.ledger_info().version(),
+    ))
+}
+
+/// The version a pruner has pruned up to, as saved in the `db_metadata`
+/// column family of the RocksDB instance at `dir`. None until it first
+/// saves its progress.
+fn pruner_progress(dir: &Path, key: &DbMetadataKey) -> Result<Option<Version>> {
+    let opts = Options::default();
+    let names = DB::list_cf(&opts, dir)?;
+    // a read-only instance sees the writes of the open DiemDB as of now
+    let db = DB::open_cf_for_read_only(&opts, dir, &names, false)?;
+    let cf = db
+        .cf_handle("db_metadata")
+        .with_context(|| format!("no db_metadata in {}", dir.display()))?;
+    db.get_cf(&cf, bcs::to_bytes(key)?)?
+        .map(|v| Ok(bcs::from_bytes::<DbMetadataValue>(&v)?.expect_version()))
+        .transpose()
+}
+
+/// Waits until every pruner has pruned up to `target`
+fn wait_for_pruners(db_path: &Path, target: Version) -> Result<()> {
+    for (name, db_name, key) in PRUNERS {
+        let dir = db_path.join(db_name);
+        let mut last = pruner_progress(&dir, key)?.unwrap_or_default();
+        let mut stalled = 0;
+        while last < target {
+            thread::sleep(Duration::from_secs(1));
+            let progress = pruner_progress(&dir, key)?.unwrap_or_default();
+            if progress == last {
+                stalled += 1;
+                if stalled >= PRUNE_STALL_SECS {
+                    bail!("{name} pruner stopped at version {progress}, target {target}");
+                }
+            } else {
+                stalled = 0;
+                println!("{name} pruner at version {progress} of {target}");
+            }
+            last = progress;
+        }
+    }
+    Ok(())
+}
+
+/// Compacts every column family of the pruned RocksDB instances, so the
+/// space of the deleted versions is given back to the disk
+fn compact(db_path: &Path) -> Result<()> {
+    let mut dirs: Vec<&str> = PRUNERS.iter().map(|(_, db_name, _)| *db_name).collect();
+    dirs.dedup();
+    for db_name in dirs {
+        let dir = db_path.join(db_name);
+        let opts = Options::default();
+        let names = DB::list_cf(&opts, &dir)?;
+        let db = DB::open_cf(&opts, &dir, &names)?;
+        for name in &names {
+            let cf = db
+                .cf_handle(name)
+                .with_context(|| format!("no column family {name} in {}", dir.display()))?;
+            println!("compacting {db_name}/{name}");
+            db.compact_range_cf(&cf, None::<&[u8]>, None::<&[u8]>);
+        }
+    }
+    Ok(())
+}
+
+/// Prunes the DB at `db_path` to the last `keep_epochs` epochs, then sets
+/// the pruners of `node_yaml`, if given, to the same window. Returns the
+/// window in versions. The node must be stopped.
+pub fn prune(db_path: &Path, keep_epochs: u64, node_yaml: Option<&Path>) -> Result<Option<u64>> {
+    let window = {
+        let db = open_read_only(db_path)?;
+        prune_window(&db, keep_epochs)?
+    };
+    let Some(window) = window else {
+        println!("the db has no more than {keep_epochs} ended epochs, nothing to prune");
+        return Ok(None);
+    };
+
+    let pruner_config = PrunerConfig {
+        ledger_pruner_config: LedgerPrunerConfig {
+            enable: true,
+            prune_window: window,
+            ..Default::default()
+        },
+        state_merkle_pruner_config: StateMerklePrunerConfig {
+            enable: true,
+            prune_window: window,
+            ..Default::default()
+        },
+        epoch_snapshot_pruner_config: EpochSnapshotPrunerConfig {
+            enable: true,
+            prune_window: window,
+            ..Default::default()
+        },
+    };
+    // a writable DB sets the pruners' target to its latest version on open
+    let db = DiemDB::open(
+        db_path,
+        false, /* readonly */
+        pruner_config,
+        RocksdbConfigs::default(),
+        false, /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    )
+    .with_context(|| {
+        format!(
+            "cannot open db at {} for writing, is the node stopped?",
+            db_path.display()
+        )
+    })?;
+
+    let target = db.get_latest_version()?.saturating_sub(window);
+    wait_for_pruners(db_path, target)?;
+    // every pruner reached its target, nothing is left for the workers
+    drop(db);
+    compact(db_path)?;
+
+    if let Some(path) = node_yaml {
+        set_pruner_window_in_yaml(path, window)?;
+        println!(
+            "pruner window of {window} versions set in {}",
+            path.display()
+        );
+    }
+    Ok(Some(window))
+}
+
+#[test]
+fn column_family_sizes() {
+    let temp = diem_temppath::TempPath::new();
+    let dir = temp.path().join("ledger_db");
+    {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db = DB::open_cf(&opts, &dir, ["transaction", "event"]).unwrap();
+        let cf = db.cf_handle("transaction").unwrap();
+        for i in 0u64..100 {
+            db.put_cf(&cf, i.to_be_bytes(), [0u8; 100]).unwrap();
+        }
+        db.flush_cf(&cf).unwrap();
+    }
+
+    assert_eq!(rocksdb_dirs(temp.path()).unwrap(), vec![dir.clone()]);
+    let cfs = column_families("ledger_db", &dir).unwrap();
+    let txns = cfs
+        .iter()
+        .find(|c| c.column_family == "transaction")
+        .unwrap();
+    assert!(txns.sst_bytes > 0);
+    assert_eq!(txns.estimated_keys, 100);
+    let events = cfs.iter().find(|c| c.column_family == "event").unwrap();
+    assert_eq!(events.sst_bytes, 0);
+}
@@ -0,0 +1,538 @@
+//! Restore the full history of a range of epochs, for audits.
+//! The state snapshot of the last epoch before the range is restored, and
+//! every transaction from there to the end of the last epoch of the range is
+//! replayed on it. The epoch chain is restored from genesis and checked from
+//! a waypoint the user trusts, and the ledger infos of the snapshot and of
+//! every transaction chunk are checked against it. Progress is kept in the
+//! work dir, so an interrupted restore picks up again after the last applied
+//! version.
+
+use crate::{
+    archive_source::{download_folder, ArchiveSource},
+    dbtool_init::get_backup_storage,
+    parse_folder_names::{
+        parse_epoch_ending_number, parse_state_epoch_info, parse_transaction_number,
+    },
+    restore::maybe_decompress_gz_files,
+    restore_bundle::RestoreBundle,
+};
+use anyhow::{bail, ensure, Context, Result};
+use diem_backup_cli::{
+    backup_types::{
+        epoch_ending::{
+            manifest::EpochEndingBackup,
+            restore::{EpochHistory, EpochHistoryRestoreController},
+        },
+        state_snapshot::restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
+        transaction::{
+            manifest::TransactionBackup,
+            restore::{TransactionRestoreController, TransactionRestoreOpt},
+        },
+    },
+    storage::BackupStorage,
+    utils::{GlobalRestoreOptions, RestoreRunMode, TrustedWaypointOpt},
+};
+use diem_config::config::{
+    RocksdbConfigs, BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use diem_db::{state_restore::StateSnapshotRestoreMode, DiemDB, GetRestoreHandler};
+use diem_executor_types::VerifyExecutionMode;
+use diem_logger::info;
+use diem_types::waypoint::Waypoint;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const PROGRESS_FILE: &str = "range_restore.json";
+
+/// The archive folders of a range restore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangePlan {
+    pub from_epoch: u64,
+    pub to_epoch: u64,
+    /// state snapshot of the last epoch before the range
+    pub state_epoch: String,
+    pub snapshot_epoch: u64,
+    pub snapshot_version: u64,
+    /// epoch ending archives which may hold every epoch from genesis to
+    /// `to_epoch`, checked against their manifests once downloaded
+    pub epoch_endings: Vec<String>,
+}
+
+/// Saved in the work dir after every step
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RangeProgress {
+    pub plan: RangePlan,
+    /// folders downloaded and decompressed
+    pub downloaded: BTreeSet<String>,
+    /// the last version of the range, once the epoch endings are read
+    pub target_version: Option<u64>,
+    pub snapshot_done: bool,
+    /// the last version replayed into the DB
+    pub applied_version: Option<u64>,
+}
+
+impl RangeProgress {
+    fn load_or_new(work_dir: &Path, plan: RangePlan) -> Result<Self> {
+        let path = work_dir.join(PROGRESS_FILE);
+        if !path.exists() {
+            return Ok(Self {
+                plan,
+                downloaded: BTreeSet::new(),
+                target_version: None,
+                snapshot_done: false,
+                applied_version: None,
+            });
+        }
+        let progress: Self = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        if progress.plan != plan {
+            bail!(
+                "{} is for epochs {} to {}, use another work dir for this range",
+                path.display(),
+                progress.plan.from_epoch,
+                progress.plan.to_epoch
+            );
+        }
+        info!("resuming range restore from {}", path.display());
+        Ok(progress)
+    }
+
+    fn save(&self, work_dir: &Path) -> Result<()> {
+        fs::write(
+            work_dir.join(PROGRESS_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Folders of `folders`, sorted by their first epoch or version, which may
+/// hold anything from `first` to `last`: the last one starting at or before
+/// `first`, and every one starting after it up to `last`. Only the names are
+/// known here, a folder may end well before the next one starts.
+fn covering(mut folders: Vec<(u64, String)>, first: u64, last: u64) -> Result<Vec<String>> {
+    folders.sort();
+    match folders.first() {
+        Some((start, name)) if *start > first => {
+            bail!("the earliest archive {name} starts after {first}")
+        }
+        None => bail!("no archives found"),
+        _ => {}
+    }
+    Ok(folders
+        .iter()
+        .enumerate()
+        .filter(|(i, (start, _))| {
+            let next = folders.get(i + 1).map(|(s, _)| *s);
+            *start <= last && next.map_or(true, |n| n > first)
+        })
+        .map(|(_, (_, name))| name.clone())
+        .collect())
+}
+
+/// Picks the state snapshot and epoch ending archives for the range. The
+/// epoch endings go back to genesis, since the restore controllers check
+/// ledger infos against an epoch history that starts there.
+pub fn plan_range(folder_names: &[String], from_epoch: u64, to_epoch: u64) -> Result<RangePlan> {
+    if from_epoch == 0 || from_epoch > to_epoch {
+        bail!("the range must start at epoch 1 or later and not end before it starts");
+    }
+    let (snapshot_epoch, snapshot_version, state_epoch) = folder_names
+        .iter()
+        .filter_map(|f| parse_state_epoch_info(f).map(|(e, v)| (e, v, f.clone())))
+        .filter(|(e, _, _)| *e < from_epoch)
+        .max()
+        .with_context(|| format!("no state snapshot found before epoch {from_epoch}"))?;
+
+    let epoch_folders = folder_names
+        .iter()
+        .filter_map(|f| parse_epoch_ending_number(f).map(|e| (e, f.clone())))
+        .collect();
+    let epoch_endings = covering(epoch_folders, 0, to_epoch)?;
+
+    Ok(RangePlan {
+        from_epoch,
+        to_epoch,
+        state_epoch,
+        snapshot_epoch,
+        snapshot_version,
+        epoch_endings,
+    })
+}
+
+/// The transaction archives with versions from `first` to `last`.
+pub fn transaction_folders(folder_names: &[String], first: u64, last: u64) -> Result<Vec<String>> {
+    let folders = folder_names
+        .iter()
+        .filter_map(|f| parse_transaction_number(f).map(|v| (v, f.clone())))
+        .collect();
+    covering(folders, first, last)
+}
+
+/// Checks that the archives, each holding the epochs or versions `first` to
+/// `last` of its manifest, hold every one from `from` to `to` with no
+/// overlap, and lists the missing ones otherwise.
+fn check_contiguous(
+    what: &str,
+    mut archives: Vec<(u64, u64, String)>,
+    from: u64,
+    to: u64,
+) -> Result<()> {
+    archives.sort();
+    let mut missing = vec![];
+    let mut next = from;
+    let mut previous: Option<&str> = None;
+    for (first, last, name) in &archives {
+        if *last < next {
+            if let Some(p) = previous {
+                bail!("archives {p} and {name} overlap");
+            }
+            continue;
+        }
+        match previous {
+            Some(p) if *first < next => bail!("archives {p} and {name} overlap"),
+            _ if *first > next => missing.push(format!("{next}-{}", first - 1)),
+            _ => {}
+        }
+        previous = Some(name);
+        next = last + 1;
+        if next > to {
+            break;
+        }
+    }
+    if next <= to {
+        missing.push(format!("{next}-{to}"));
+    }
+    ensure!(
+        missing.is_empty(),
+        "the archives are missing {what} {}, needed from {from} to {to}",
+        missing.join(", ")
+    );
+    Ok(())
+}
+
+/// Downloads a folder once, and decompresses it for the restore controllers.
+async fn fetch_folder(
+    source: &dyn ArchiveSource,
+    folder: &str,
+    work_dir: &Path,
+    progress: &mut RangeProgress,
+) -> Result<()> {
+    if progress.downloaded.contains(folder) {
+        return Ok(());
+    }
+    download_folder(source, folder, work_dir).await?;
+    let dir = work_dir.join(folder);
+    maybe_decompress_gz_files(&dir).await?;
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |e| e == "manifest") {
+            let content = fs::read_to_string(&path)?;
+            fs::write(&path, RestoreBundle::update_manifest_paths(&content))?;
+        }
+    }
+    progress.downloaded.insert(folder.to_owned());
+    progress.save(work_dir)
+}
+
+fn manifest_handle(folder: &str, name: &str) -> String {
+    format!("{folder}/{name}")
+}
+
+fn global_opts(
+    db_path: &Path,
+    target_version: u64,
+    trusted: &[Waypoint],
+) -> Result<GlobalRestoreOptions> {
+    let restore_handler = Arc::new(DiemDB::open_kv_only(
+        db_path.to_owned(),
+        false,                       /* read_only */
+        NO_OP_STORAGE_PRUNER_CONFIG, /* pruner config */
+        RocksdbConfigs::default(),
+        false,
+        BUFFERED_STATE_TARGET_ITEMS,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    )?)
+    .get_restore_handler();
+
+    let twp = TrustedWaypointOpt {
+        trust_waypoint: trusted.to_vec(),
+    };
+    Ok(GlobalRestoreOptions {
+        target_version,
+        trusted_waypoints: Arc::new(twp.verify()?),
+        run_mode: Arc::new(RestoreRunMode::Restore { restore_handler }),
+        concurrent_downloads: num_cpus::get(),
+        replay_concurrency_level: 4,
+    })
+}
+
+/// Restores epochs `from_epoch` to `to_epoch` with their full transaction
+/// history into `db_path`, downloading archives to `work_dir`. The first
+/// epoch ending restored must have one of the `trusted` waypoints.
+pub async fn range_restore(
+    source: &dyn ArchiveSource,
+    from_epoch: u64,
+    to_epoch: u64,
+    trusted: &[Waypoint],
+    work_dir: &Path,
+    db_path: &Path,
+) -> Result<RangeProgress> {
+    fs::create_dir_all(work_dir)?;
+    fs::create_dir_all(db_path)?;
+    let work_dir = fs::canonicalize(work_dir)?;
+    let db_path = fs::canonicalize(db_path)?;
+
+    let folder_names = source.list_folders().await?;
+    let plan = plan_range(&folder_names, from_epoch, to_epoch)?;
+    let mut progress = RangeProgress::load_or_new(&work_dir, plan.clone())?;
+    let storage: Arc<dyn BackupStorage> = get_backup_storage(work_dir.clone())?;
+
+    // read the epoch endings first, for the last version of the range, and
+    // check they hold every epoch from genesis before restoring anything
+    let mut manifests = vec![];
+    for folder in &plan.epoch_endings {
+        fetch_folder(source, folder, &work_dir, &mut progress).await?;
+        let path = work_dir.join(folder).join("epoch_ending.manifest");
+        let manifest: EpochEndingBackup = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        manifests.push((folder, manifest));
+    }
+    manifests.sort_by_key(|(_, m)| m.first_epoch);
+    check_contiguous(
+        "epochs",
+        manifests
+            .iter()
+            .map(|(f, m)| (m.first_epoch, m.last_epoch, f.to_string()))
+            .collect(),
+        0,
+        to_epoch,
+    )?;
+    // an archive starting after the range holds nothing needed
+    manifests.retain(|(_, m)| m.first_epoch <= to_epoch);
+
+    // the chain is only as good as its anchor, which must come no later
+    // than the snapshot, so everything restored after it is checked
+    let mut waypoints = manifests
+        .iter()
+        .flat_map(|(_, m)| (m.first_epoch..=m.last_epoch).zip(&m.waypoints));
+    ensure!(
+        waypoints
+            .clone()
+            .any(|(epoch, wp)| epoch <= plan.snapshot_epoch && trusted.contains(wp)),
+        "none of the trusted waypoints is an epoch ending up to the snapshot's epoch {}",
+        plan.snapshot_epoch
+    );
+    let target_version = waypoints
+        .find(|(epoch, _)| *epoch == to_epoch)
+        .map(|(_, wp)| wp.version())
+        .with_context(|| format!("no epoch ending archive has epoch {to_epoch}"))?;
+    progress.target_version = Some(target_version);
+    progress.save(&work_dir)?;
+
+    // rebuilt on every run, the snapshot and transaction restores need it;
+    // saving the epoch endings again is harmless
+    let epoch_history: Arc<EpochHistory> = Arc::new(
+        EpochHistoryRestoreController::new(
+            manifests
+                .iter()
+                .map(|(folder, _)| manifest_handle(folder, "epoch_ending.manifest"))
+                .collect(),
+            global_opts(&db_path, target_version, trusted)?,
+            storage.clone(),
+        )
+        .run()
+        .await
+        .context("cannot restore the epoch endings")?,
+    );
+
+    if !progress.snapshot_done {
+        fetch_folder(source, &plan.state_epoch, &work_dir, &mut progress).await?;
+        StateSnapshotRestoreController::new(
+            StateSnapshotRestoreOpt {
+                manifest_handle: manifest_handle(&plan.state_epoch, "state.manifest"),
+                version: plan.snapshot_version,
+                validate_modules: false,
+                restore_mode: StateSnapshotRestoreMode::Default,
+            },
+            global_opts(&db_path, target_version, trusted)?,
+            storage.clone(),
+            Some(epoch_history.clone()),
+        )
+        .run()
+        .await
+        .with_context(|| format!("cannot restore {}", plan.state_epoch))?;
+        progress.snapshot_done = true;
+        progress.save(&work_dir)?;
+    }
+
+    let txn_folders =
+        transaction_folders(&folder_names, plan.snapshot_version + 1, target_version)?;
+    let mut txn_manifests = vec![];
+    for folder in &txn_folders {
+        fetch_folder(source, folder, &work_dir, &mut progress).await?;
+        let path = work_dir.join(folder).join("transaction.manifest");
+        let manifest: TransactionBackup = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        txn_manifests.push((folder, manifest));
+    }
+    check_contiguous(
+        "versions",
+        txn_manifests
+            .iter()
+            .map(|(f, m)| (m.first_version, m.last_version, f.to_string()))
+            .collect(),
+        plan.snapshot_version + 1,
+        target_version,
+    )?;
+
+    for (folder, manifest) in &txn_manifests {
+        let opts = global_opts(&db_path, target_version, trusted)?;
+        // the DB holds every version before this one
+        let next_version = opts.run_mode.get_next_expected_transaction_version()?;
+        if manifest.last_version < next_version || next_version > target_version {
+            continue;
+        }
+        info!(
+            "applying versions {} to {} of {folder}",
+            manifest.first_version.max(next_version),
+            manifest.last_version.min(target_version)
+        );
+        TransactionRestoreController::new(
+            TransactionRestoreOpt {
+                manifest_handle: manifest_handle(folder, "transaction.manifest"),
+                // versions up to the snapshot are saved, later ones replayed,
+                // skipping those the DB already holds
+                replay_from_version: Some((plan.snapshot_version + 1).max(next_version)),
+                kv_only_replay: None,
+            },
+            opts,
+            storage.clone(),
+            Some(epoch_history.clone()),
+            VerifyExecutionMode::NoVerify,
+        )
+        .run()
+        .await
+        .with_context(|| format!("cannot restore {folder}"))?;
+        progress.applied_version = Some(manifest.last_version.min(target_version));
+        progress.save(&work_dir)?;
+    }
+
+    info!(
+        "SUCCESS: restored epochs {from_epoch} to {to_epoch}, versions {} to {target_version}",
+        plan.snapshot_version
+    );
+    Ok(progress)
+}
+
+/// where range restore keeps its downloads when no work dir is given
+pub fn default_work_dir(db_path: &Path) -> PathBuf {
+    let mut dir = db_path.as_os_str().to_owned();
+    dir.push("_archives");
+    PathBuf::from(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn plan_epoch_range() {
+        // archives of one epoch each, as the epoch-archive repos hold them
+        let folders = names(&[
+            "epoch_ending_0-.0000",
+            "epoch_ending_1-.1111",
+            "epoch_ending_114-.aaaa",
+            "epoch_ending_115-.bbbb",
+            "epoch_ending_116-.cccc",
+            "epoch_ending_117-.dddd",
+            "epoch_ending_130-.eeee",
+            "state_epoch_105_ver_1000.aaaa",
+            "state_epoch_115_ver_2000.bbbb",
+            "state_epoch_118_ver_2500.cccc",
+            "transaction_1-.aaaa",
+        ]);
+        let plan = plan_range(&folders, 117, 125).unwrap();
+        // the snapshot is before the range, so its first epoch is replayed
+        assert_eq!(plan.state_epoch, "state_epoch_115_ver_2000.bbbb");
+        assert_eq!(plan.snapshot_epoch, 115);
+        assert_eq!(plan.snapshot_version, 2000);
+        // the epoch history starts at genesis, and the names don't tell
+        // where an archive ends, only the manifests do
+        assert_eq!(
+            plan.epoch_endings,
+            names(&[
+                "epoch_ending_0-.0000",
+                "epoch_ending_1-.1111",
+                "epoch_ending_114-.aaaa",
+                "epoch_ending_115-.bbbb",
+                "epoch_ending_116-.cccc",
+                "epoch_ending_117-.dddd"
+            ])
+        );
+
+        assert!(plan_range(&folders, 105, 110).is_err());
+        assert!(plan_range(&folders, 125, 118).is_err());
+        // without genesis there is no epoch history to check against
+        assert!(plan_range(&folders[1..], 117, 125).is_err());
+    }
+
+    #[test]
+    fn contiguous_archives() {
+        let archives = |v: &[(u64, u64)]| -> Vec<(u64, u64, String)> {
+            v.iter()
+                .map(|(f, l)| (*f, *l, format!("epoch_ending_{f}-")))
+                .collect()
+        };
+
+        check_contiguous(
+            "epochs",
+            archives(&[(115, 115), (116, 116), (117, 125)]),
+            115,
+            125,
+        )
+        .unwrap();
+        // an archive may start before the range, or end after it
+        check_contiguous("epochs", archives(&[(100, 116), (117, 130)]), 115, 125).unwrap();
+        check_contiguous("epochs", archives(&[(110, 114), (115, 130)]), 115, 125).unwrap();
+
+        // one epoch per archive leaves gaps between the archive names
+        let err = check_contiguous("epochs", archives(&[(115, 115), (117, 117)]), 115, 125)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing epochs 116-116, 118-125"), "{err}");
+        let err = check_contiguous("epochs", archives(&[(110, 113)]), 115, 125)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing epochs 115-125"), "{err}");
+
+        assert!(check_contiguous("epochs", archives(&[(115, 120), (118, 125)]), 115, 125).is_err());
+    }
+
+    #[test]
+    fn transactions_of_versions() {
+        let folders = names(&[
+            "transaction_1-.aaaa",
+            "transaction_100001-.bbbb",
+            "transaction_200001-.cccc",
+            "transaction_300001-.dddd",
+            "epoch_ending_100-.aaaa",
+        ]);
+        assert_eq!(
+            transaction_folders(&folders, 150_000, 200_001).unwrap(),
+            names(&["transaction_100001-.bbbb", "transaction_200001-.cccc"])
+        );
+        assert_eq!(
+            transaction_folders(&folders, 350_000, 360_000).unwrap(),
+            names(&["transaction_300001-.dddd"])
+        );
+        assert!(transaction_folders(&folders[1..], 50_000, 60_000).is_err());
+    }
+}
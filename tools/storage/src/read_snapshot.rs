@@ -1,4 +1,5 @@
 //! read-archive
+use crate::snapshot_reader::SnapshotReader;
 use anyhow::{anyhow, Context, Error, Result};
 use diem_backup_cli::{
     backup_types::{
@@ -8,18 +9,15 @@ use diem_backup_cli::{
     utils::read_record_bytes::ReadRecordBytes,
 };
 use diem_types::{
-    account_address::AccountAddress,
     account_state::AccountState,
-    state_store::{
-        state_key::{StateKey, StateKeyInner},
-        state_value::StateValue,
-    },
+    state_store::{state_key::StateKey, state_value::StateValue},
 };
+use futures::{stream::BoxStream, TryStreamExt};
 use libra_backwards_compatibility::legacy_recovery_v6;
 use serde_json::json;
 use std::{
-    collections::HashMap,
-    fs,
+    fs::{self, File},
+    io::{BufWriter, Write},
     path::{Path, PathBuf},
 };
 use tokio::{fs::OpenOptions, io::AsyncRead};
@@ -71,52 +69,14 @@ async fn open_for_read(file_handle: &FileHandleRef) -> Result<Box<dyn AsyncRead
     Ok(Box::new(file))
 }
 
-/// Tokio async parsing of state snapshot into blob
+/// Tokio async parsing of state snapshot into a stream of accounts
 pub async fn accounts_from_snapshot_backup(
     manifest: StateSnapshotBackup,
     archive_path: &Path,
-) -> anyhow::Result<Vec<AccountState>> {
-    // parse AccountStateBlob from chunks of the archive
-    let mut account_states_map: HashMap<AccountAddress, Vec<(StateKey, StateValue)>> =
-        HashMap::new();
-    for chunk in manifest.chunks {
-        let blobs: Vec<(StateKey, StateValue)> =
-            read_account_state_chunk(chunk.blobs, archive_path).await?;
-
-        // Filter out the AccountState blobs
-        // and group by address
-        let account_states_map_chunk: HashMap<AccountAddress, Vec<(StateKey, StateValue)>> = blobs
-            .into_iter()
-            .fold(HashMap::new(), |mut acc, (key, blob)| {
-                if let StateKeyInner::AccessPath(access_path) = key.inner() {
-                    acc.entry(access_path.address)
-                        .or_default()
-                        .push((key, blob));
-                }
-                acc
-            });
-
-        // merge account_states_map_chunk into account_states_map
-        for (address, blobs) in account_states_map_chunk {
-            account_states_map.entry(address).or_default().extend(blobs);
-        }
-    }
-    // materialize account state for each address
-    let mut account_states: Vec<AccountState> = Vec::new();
-    for (address, blobs) in account_states_map {
-        #[allow(clippy::mutable_key_type)]
-        let mut blobs_hash_table = HashMap::new();
-        blobs.into_iter().for_each(|(key, blob)| {
-            blobs_hash_table.insert(key, blob);
-        });
-        if let Some(a_state) =
-            AccountState::from_access_paths_and_values(address, &blobs_hash_table)?
-        {
-            account_states.push(a_state);
-        };
-    }
-
-    Ok(account_states)
+) -> anyhow::Result<BoxStream<'static, Result<AccountState>>> {
+    SnapshotReader::from_manifest(manifest, archive_path)
+        .accounts()
+        .await
 }
 
 #[test]
@@ -129,23 +89,34 @@ fn test_parse_manifest() {
     let _r = load_snapshot_manifest(&this_path).expect("parse manifest");
 }
 
+/// Writes the legacy recovery of every account as a JSON list, one account
+/// at a time.
 pub async fn manifest_to_json(manifest_path: PathBuf, out_path: Option<PathBuf>) {
-    let snapshot_manifest = load_snapshot_manifest(&manifest_path).expect("parse manifest");
-    let archive_path = manifest_path.parent().unwrap();
-    let account_states = accounts_from_snapshot_backup(snapshot_manifest, archive_path)
+    let mut account_states = SnapshotReader::new(&manifest_path)
+        .expect("parse manifest")
+        .accounts()
         .await
         .expect("could not decode snapshot");
-    let mut legacy_recovery_vec = Vec::new();
-    for account_state in account_states.iter() {
-        let legacy_recovery = legacy_recovery_v6::get_legacy_recovery(account_state)
+    let out = out_path.unwrap_or(manifest_path.parent().unwrap().join("migration.json"));
+    let mut file = BufWriter::new(File::create(out).expect("could not save file"));
+    write!(file, "[").expect("could not save file");
+    let mut first = true;
+    while let Some(account_state) = account_states
+        .try_next()
+        .await
+        .expect("could not decode snapshot")
+    {
+        let legacy_recovery = legacy_recovery_v6::get_legacy_recovery(&account_state)
             .expect("could not get legacy recovery");
 
-        legacy_recovery_vec.push(legacy_recovery);
+        if !first {
+            write!(file, ",").expect("could not save file");
+        }
+        first = false;
+        write!(file, "{}", json!(&legacy_recovery)).expect("could not save file");
     }
-
-    let json = json!(&legacy_recovery_vec);
-    let out = out_path.unwrap_or(manifest_path.parent().unwrap().join("migration.json"));
-    fs::write(out, json.to_string()).expect("could not save file");
+    write!(file, "]").expect("could not save file");
+    file.flush().expect("could not save file");
 }
 
 #[tokio::test]
@@ -169,9 +140,13 @@ async fn test_deserialize_account() {
     this_path.push("fixtures/state_epoch_79_ver_33217173.795d/state.manifest");
     let snapshot_manifest = load_snapshot_manifest(&this_path).expect("parse manifest");
     let archive_path = this_path.parent().unwrap();
-    let account_states = accounts_from_snapshot_backup(snapshot_manifest, archive_path)
-        .await
-        .expect("could not decode snapshot");
+    let account_states: Vec<AccountState> =
+        accounts_from_snapshot_backup(snapshot_manifest, archive_path)
+            .await
+            .expect("could not decode snapshot")
+            .try_collect()
+            .await
+            .expect("could not decode snapshot");
     let mut legacy_recovery_vec = Vec::new();
     for account_state in account_states.iter() {
         let legacy_recovery = legacy_recovery_v6::get_legacy_recovery(account_state)
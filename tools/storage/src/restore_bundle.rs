@@ -176,7 +176,7 @@ impl RestoreBundle {
         );
     }
 
-    fn update_manifest_paths(manifest_content: &str) -> String {
+    pub(crate) fn update_manifest_paths(manifest_content: &str) -> String {
         let mut manifest: Value = serde_json::from_str(manifest_content).unwrap();
 
         if let Some(obj) = manifest.as_object_mut() {
@@ -0,0 +1,310 @@
+//! Streaming reader of V7 state snapshots.
+//! Chunks are read concurrently, by a configurable number of workers, and
+//! handed out in snapshot order. Since records are sorted by key hash, the
+//! resources of one account are spread over all chunks; to group them
+//! without holding the whole snapshot in memory, records are first spilled
+//! to bucket files by address, then each bucket is grouped on its own.
+
+use crate::read_snapshot::{load_snapshot_manifest, read_account_state_chunk};
+use anyhow::{Context, Result};
+use diem_backup_cli::backup_types::state_snapshot::manifest::StateSnapshotBackup;
+use diem_types::{
+    account_address::AccountAddress,
+    account_state::AccountState,
+    state_store::{
+        state_key::{StateKey, StateKeyInner},
+        state_value::StateValue,
+    },
+};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Records of one snapshot chunk
+pub type ChunkRecords = Vec<(StateKey, StateValue)>;
+
+/// Reads a state snapshot as a stream of chunks or of accounts
+pub struct SnapshotReader {
+    manifest: StateSnapshotBackup,
+    /// the snapshot dir, whose parent the chunk handles are relative to
+    archive_path: PathBuf,
+    workers: usize,
+    spill_dir: Option<PathBuf>,
+}
+
+impl SnapshotReader {
+    /// Reader of the snapshot at `manifest_path`
+    pub fn new(manifest_path: &Path) -> Result<Self> {
+        let manifest = load_snapshot_manifest(manifest_path)?;
+        // chunk handles are relative to the snapshot dir's parent, so a
+        // bare relative manifest path needs resolving first
+        let manifest_path = manifest_path
+            .canonicalize()
+            .with_context(|| format!("cannot resolve {}", manifest_path.display()))?;
+        let archive_path = manifest_path
+            .parent()
+            .context("manifest path has no parent")?;
+        Ok(Self::from_manifest(manifest, archive_path))
+    }
+
+    /// Reader of a loaded manifest, `archive_path` being the snapshot dir
+    pub fn from_manifest(manifest: StateSnapshotBackup, archive_path: &Path) -> Self {
+        Self {
+            manifest,
+            archive_path: archive_path.to_owned(),
+            workers: num_cpus::get(),
+            spill_dir: None,
+        }
+    }
+
+    /// How many chunks are read at once, and held in memory
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Where to put the bucket files of `accounts`, defaults to the system temp dir
+    pub fn with_spill_dir(mut self, spill_dir: PathBuf) -> Self {
+        self.spill_dir = Some(spill_dir);
+        self
+    }
+
+    pub fn manifest(&self) -> &StateSnapshotBackup {
+        &self.manifest
+    }
+
+    /// The records of every chunk, in snapshot order
+    pub fn chunks(&self) -> BoxStream<'static, Result<ChunkRecords>> {
+        let archive_path = self.archive_path.clone();
+        stream::iter(self.manifest.chunks.clone())
+            .map(move |chunk| {
+                let archive_path = archive_path.clone();
+                async move { read_account_state_chunk(chunk.blobs, &archive_path).await }
+            })
+            .buffered(self.workers)
+            .boxed()
+    }
+
+    /// Every account with all its resources. Reads the whole snapshot into
+    /// bucket files before the first account is yielded. The files are
+    /// written on the blocking thread pool, a chunk at a time, while the
+    /// next chunks are read.
+    pub async fn accounts(&self) -> Result<BoxStream<'static, Result<AccountState>>> {
+        let spill = Arc::new(SpillDir::new(self.spill_dir.as_deref())?);
+        // about one chunk of records per bucket
+        let buckets = self.manifest.chunks.len().max(1);
+        let mut files = (0..buckets)
+            .map(|i| Ok(BufWriter::new(File::create(spill.bucket(i))?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut chunks = self.chunks();
+        while let Some(records) = chunks.try_next().await? {
+            files = tokio::task::spawn_blocking(move || {
+                spill_records(&mut files, records)?;
+                Ok::<_, anyhow::Error>(files)
+            })
+            .await??;
+        }
+        tokio::task::spawn_blocking(move || {
+            files.into_iter().try_for_each(|mut file| file.flush())
+        })
+        .await??;
+
+        let accounts = stream::iter(0..buckets)
+            .map(move |i| {
+                // the closure keeps the spill dir until the stream is dropped
+                let path = spill.bucket(i);
+                async move {
+                    tokio::task::spawn_blocking(move || read_bucket(&path))
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|accounts| accounts)
+                }
+            })
+            .buffered(self.workers)
+            .map_ok(|accounts| stream::iter(accounts.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed();
+        Ok(accounts)
+    }
+}
+
+/// Appends each record of a chunk to the bucket file of its account
+fn spill_records(files: &mut [BufWriter<File>], records: ChunkRecords) -> Result<()> {
+    for (key, value) in records {
+        let StateKeyInner::AccessPath(ap) = key.inner() else {
+            continue;
+        };
+        let bucket = bucket_of(&ap.address, files.len());
+        let bytes = bcs::to_bytes(&(key, value))?;
+        let file = &mut files[bucket];
+        file.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        file.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+fn bucket_of(address: &AccountAddress, buckets: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    address.hash(&mut hasher);
+    (hasher.finish() % buckets as u64) as usize
+}
+
+/// Groups the records of a bucket file by account
+#[allow(clippy::mutable_key_type)]
+fn read_bucket(path: &Path) -> Result<Vec<AccountState>> {
+    let bytes = fs::read(path)?;
+    let mut by_address: HashMap<AccountAddress, HashMap<StateKey, StateValue>> = HashMap::new();
+    let mut rest = bytes.as_slice();
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_at(4);
+        let len = u32::from_be_bytes(len.try_into()?) as usize;
+        let (record, tail) = tail.split_at(len);
+        let (key, value): (StateKey, StateValue) = bcs::from_bytes(record)?;
+        if let StateKeyInner::AccessPath(ap) = key.inner() {
+            by_address
+                .entry(ap.address)
+                .or_default()
+                .insert(key.clone(), value);
+        }
+        rest = tail;
+    }
+
+    let mut accounts = vec![];
+    for (address, blobs) in by_address {
+        if let Some(a_state) = AccountState::from_access_paths_and_values(address, &blobs)? {
+            accounts.push(a_state);
+        }
+    }
+    Ok(accounts)
+}
+
+/// A temporary directory, removed on drop
+struct SpillDir {
+    path: PathBuf,
+}
+
+impl SpillDir {
+    fn new(parent: Option<&Path>) -> Result<Self> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let path = parent
+            .map(Path::to_owned)
+            .unwrap_or_else(std::env::temp_dir)
+            .join(format!("snapshot-reader-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    fn bucket(&self, i: usize) -> PathBuf {
+        self.path.join(format!("{i}.bucket"))
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[tokio::test]
+async fn group_accounts_across_chunks() {
+    use diem_types::access_path::AccessPath;
+    use libra_types::move_resource::{jail::JailResource, wallet::SlowWalletResource};
+    use move_core_types::move_resource::MoveStructType;
+    use serde::Serialize;
+
+    fn record<T: MoveStructType + Serialize>(
+        address: AccountAddress,
+        resource: &T,
+    ) -> (StateKey, StateValue) {
+        let ap = AccessPath::resource_access_path(address, T::struct_tag()).unwrap();
+        let value = StateValue::from(bcs::to_bytes(resource).unwrap());
+        (StateKey::access_path(ap), value)
+    }
+
+    let alice = AccountAddress::from_hex_literal("0xa").unwrap();
+    let bob = AccountAddress::from_hex_literal("0xb").unwrap();
+    let jail = JailResource {
+        is_jailed: true,
+        lifetime_jailed: 2,
+        lifetime_vouchees_jailed: 0,
+        consecutive_failure_to_rejoin: 1,
+    };
+    let slow = SlowWalletResource {
+        unlocked: 10,
+        transferred: 3,
+    };
+    // alice's resources are in both chunks
+    let chunks = [
+        vec![record(alice, &jail)],
+        vec![record(bob, &slow), record(alice, &slow)],
+    ];
+
+    let temp = diem_temppath::TempPath::new();
+    let snapshot_dir = temp.path().join("state_epoch_1_ver_10.abcd");
+    fs::create_dir_all(&snapshot_dir).unwrap();
+    let mut manifest_chunks = vec![];
+    for (i, records) in chunks.iter().enumerate() {
+        let mut bytes = vec![];
+        for r in records {
+            let record = bcs::to_bytes(r).unwrap();
+            bytes.extend((record.len() as u32).to_be_bytes());
+            bytes.extend(record);
+        }
+        fs::write(snapshot_dir.join(format!("{i}-.chunk")), bytes).unwrap();
+        manifest_chunks.push(serde_json::json!({
+            "first_idx": i,
+            "last_idx": i,
+            "first_key": "00".repeat(32),
+            "last_key": "00".repeat(32),
+            "blobs": format!("state_epoch_1_ver_10.abcd/{i}-.chunk"),
+            "proof": format!("state_epoch_1_ver_10.abcd/{i}-.proof"),
+        }));
+    }
+    let manifest: StateSnapshotBackup = serde_json::from_value(serde_json::json!({
+        "version": 10,
+        "epoch": 1,
+        "root_hash": "00".repeat(32),
+        "chunks": manifest_chunks,
+        "proof": "state_epoch_1_ver_10.abcd/state.proof",
+    }))
+    .unwrap();
+
+    let reader = SnapshotReader::from_manifest(manifest, &snapshot_dir)
+        .with_workers(2)
+        .with_spill_dir(temp.path().to_owned());
+    let chunk_sizes: Vec<usize> = reader
+        .chunks()
+        .map_ok(|c| c.len())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(chunk_sizes, vec![1, 2]);
+
+    let accounts: Vec<AccountState> = reader
+        .accounts()
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(accounts.len(), 2);
+    let with_jail: Vec<_> = accounts
+        .iter()
+        .filter(|a| a.get_move_resource::<JailResource>().unwrap().is_some())
+        .collect();
+    assert_eq!(with_jail.len(), 1);
+    assert!(with_jail[0]
+        .get_move_resource::<SlowWalletResource>()
+        .unwrap()
+        .is_some());
+    // the bucket files are gone with the stream
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 1);
+}
@@ -3,11 +3,21 @@ use clap::{Parser, Subcommand};
 use diem_db_tool::DBTool;
 use diem_logger::{Level, Logger};
 use diem_push_metrics::MetricsPusher;
+use diem_types::waypoint::Waypoint;
+use libra_config::make_yaml_public_fullnode::FN_FILENAME;
+use libra_types::global_config_dir;
 use std::path::PathBuf;
 
 // Import the correct functions from libra-config
 
-use crate::{bootstrap, download_bundle, read_snapshot, restore};
+use crate::{
+    archive_source::ArchiveSourceOpts,
+    bootstrap, diff_snapshots, download_bundle,
+    export_snapshot::{self, ExportFormat},
+    export_transactions::{self, TxExportFormat},
+    inspect::InspectOpts,
+    make_bundle, prune, range_restore, read_snapshot, restore, verify_bundle,
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -37,17 +47,10 @@ pub enum Sub {
         prevent_bootstrap: bool,
     },
     /// downloads the stat, epoch, and transaction
-    /// restore files from the `epoch-archive` repo
+    /// restore files from the `epoch-archive` repo, a local mirror, or an S3 bucket
     DownloadRestoreBundle {
-        #[clap(long, default_value = "0LNetworkCommunity")]
-        /// github organization or user, canonically: "0LNetworkCommunity"
-        owner: String,
-        #[clap(long, default_value = "epoch-archive-mainnet")]
-        /// repo of archive, canonically: "epoch-archive-mainnet"
-        repo: String,
-        #[clap(long, default_value = "v7.0.0")]
-        /// branch of the archive, canonically: "v7.0.0"
-        branch: String,
+        #[clap(flatten)]
+        source: ArchiveSourceOpts,
         #[clap(short, long)]
         /// required, the number of epoch to restore
         epoch: u64,
@@ -55,12 +58,111 @@ pub enum Sub {
         /// required, the directory to download the restore bundle to
         destination: PathBuf,
     },
+    /// Restore a range of epochs with their full transaction history.
+    /// Run it again with the same work dir to resume after an interruption
+    RangeRestore {
+        #[clap(flatten)]
+        source: ArchiveSourceOpts,
+        #[clap(long)]
+        /// first epoch whose transactions the DB must hold
+        from_epoch: u64,
+        #[clap(long)]
+        /// last epoch of the range, the DB is restored to its end
+        to_epoch: u64,
+        #[clap(long, required = true)]
+        /// waypoint from a source you trust, one must be an epoch ending no
+        /// later than the state snapshot the range is restored on
+        trust_waypoint: Vec<Waypoint>,
+        #[clap(short, long)]
+        /// destination db path to restore to
+        destination_db: PathBuf,
+        #[clap(short, long)]
+        /// directory for the downloaded archives and the restore progress,
+        /// defaults to `<destination_db>_archives`
+        work_dir: Option<PathBuf>,
+    },
     /// Read a snapshot, parse and export to JSON
     ExportSnapshot {
         #[clap(short, long)]
         manifest_path: PathBuf,
         #[clap(short, long)]
+        /// directory to export to, defaults to the snapshot's directory.
+        /// With --legacy-v6 the JSON file to write
         out_path: Option<PathBuf>,
+        #[clap(short, long, value_enum, default_value_t)]
+        /// newline-delimited JSON, or one CSV table per resource
+        format: ExportFormat,
+        #[clap(long)]
+        /// export a V6 snapshot to the migration JSON
+        legacy_v6: bool,
+    },
+    /// Compare two state snapshots, reporting changed accounts and resources
+    DiffSnapshots {
+        /// state.manifest of the earlier snapshot
+        manifest_a: PathBuf,
+        /// state.manifest of the later snapshot
+        manifest_b: PathBuf,
+        #[clap(short, long, default_value = "snapshot_diff.json")]
+        /// JSON report to write
+        out_path: PathBuf,
+    },
+    /// Export a transaction archive to tables of transactions and events
+    ExportTransactions {
+        #[clap(short, long, alias = "manifest")]
+        /// the transaction.manifest of the archive
+        manifest_path: PathBuf,
+        #[clap(short, long)]
+        /// directory to export to, defaults to the archive's directory
+        out_path: Option<PathBuf>,
+        #[clap(short, long, value_enum, default_value_t)]
+        format: TxExportFormat,
+    },
+    /// Verify the proofs and signatures of a restore bundle, without restoring it
+    VerifyBundle {
+        #[clap(short, long)]
+        /// directory with the epoch_ending, state_epoch, and transaction archives
+        bundle_path: PathBuf,
+        #[clap(short, long, required = true)]
+        /// waypoint from a source you trust, one must be an epoch ending no
+        /// later than the state snapshot the range is restored on
+        waypoint: Vec<Waypoint>,
+    },
+    /// Read ledger info, epochs, accounts, or events from a stopped node's DB
+    Inspect(InspectOpts),
+    /// Write the restore bundle of an epoch from a node's DB, in the
+    /// `epoch-archive` layout, gzipped, with a `bundle.json` manifest
+    MakeBundle {
+        #[clap(long = "db")]
+        /// path of the node's DB, opened read-only
+        db_path: PathBuf,
+        #[clap(short, long)]
+        /// the epoch to archive, which must have ended
+        epoch: u64,
+        #[clap(short, long)]
+        /// empty directory to write the bundle to
+        out: PathBuf,
+    },
+    /// Disk usage of a node's DB per column family, and its oldest versions
+    Usage {
+        #[clap(long = "db")]
+        /// path of the node's DB, e.g. ~/.libra/data/db
+        db_path: PathBuf,
+        #[clap(long)]
+        /// print the report as JSON
+        json: bool,
+    },
+    /// Prune a stopped node's DB to its last epochs, and set the node's
+    /// pruner config to keep it there
+    Prune {
+        #[clap(long = "db")]
+        /// path of the node's DB, e.g. ~/.libra/data/db
+        db_path: PathBuf,
+        #[clap(long)]
+        /// ended epochs to keep, besides the current one
+        keep_epochs: u64,
+        #[clap(long)]
+        /// node config to update, defaults to fullnode.yaml in the config dir
+        node_yaml: Option<PathBuf>,
     },
     /// Bootstrap a restored DB with genesis and waypoint
     Bootstrap {
@@ -94,9 +196,115 @@ impl StorageCli {
             Some(Sub::ExportSnapshot {
                 manifest_path,
                 out_path,
+                format,
+                legacy_v6,
+            }) => {
+                if legacy_v6 {
+                    read_snapshot::manifest_to_json(manifest_path, out_path).await;
+                } else {
+                    let out_dir = out_path.unwrap_or_else(|| {
+                        manifest_path
+                            .parent()
+                            .expect("manifest path has no parent")
+                            .to_owned()
+                    });
+                    let summary =
+                        export_snapshot::export_snapshot(&manifest_path, &out_dir, format).await?;
+                    println!(
+                        "exported {} accounts to {}",
+                        summary.accounts,
+                        out_dir.display()
+                    );
+                    for (table, rows) in &summary.tables {
+                        println!("{table}: {rows}");
+                    }
+                    println!("other resources skipped: {}", summary.skipped);
+                }
+            }
+            Some(Sub::ExportTransactions {
+                manifest_path,
+                out_path,
+                format,
+            }) => {
+                let out_dir = out_path.unwrap_or_else(|| {
+                    manifest_path
+                        .parent()
+                        .expect("manifest path has no parent")
+                        .to_owned()
+                });
+                let summary =
+                    export_transactions::export_transactions(&manifest_path, &out_dir, format)
+                        .await?;
+                println!(
+                    "exported versions {} to {}: {} transactions ({} user), {} events, to {}",
+                    summary.first_version,
+                    summary.last_version,
+                    summary.transactions,
+                    summary.user_transactions,
+                    summary.events,
+                    out_dir.display()
+                );
+            }
+            Some(Sub::DiffSnapshots {
+                manifest_a,
+                manifest_b,
+                out_path,
+            }) => {
+                let s = diff_snapshots::diff_snapshots(&manifest_a, &manifest_b, &out_path).await?;
+                println!("versions {} to {}", s.version_a, s.version_b);
+                println!(
+                    "keys: {} added, {} removed, {} modified, {} unchanged",
+                    s.keys_added, s.keys_removed, s.keys_modified, s.keys_unchanged
+                );
+                println!(
+                    "accounts: {} added, {} removed",
+                    s.accounts_added, s.accounts_removed
+                );
+                println!(
+                    "balances: {} changed, total delta {}",
+                    s.balances_changed, s.total_balance_delta
+                );
+                for (resource, n) in &s.resource_changes {
+                    println!("{resource}: {n}");
+                }
+                println!("report written to {}", out_path.display());
+            }
+            Some(Sub::VerifyBundle {
+                bundle_path,
+                waypoint,
+            }) => {
+                let checks = verify_bundle::verify_bundle(&bundle_path, &waypoint)?;
+                let mut failed = 0;
+                for c in &checks {
+                    match &c.outcome {
+                        Ok(msg) => println!("OK: {}\n  {msg}", c.manifest.display()),
+                        Err(e) => {
+                            failed += 1;
+                            println!("FAILED: {}\n  {e:#}", c.manifest.display());
+                        }
+                    }
+                }
+                if failed > 0 {
+                    anyhow::bail!("{failed} of {} archives failed verification", checks.len());
+                }
+                println!("SUCCESS: all {} archives verified", checks.len());
+            }
+            Some(Sub::Inspect(opts)) => {
+                opts.run().await?;
+            }
+            Some(Sub::MakeBundle {
+                db_path,
+                epoch,
+                out,
             }) => {
-                read_snapshot::manifest_to_json(manifest_path.to_owned(), out_path.to_owned())
-                    .await;
+                let manifest = make_bundle::make_bundle(&db_path, epoch, &out).await?;
+                println!(
+                    "bundle for epoch {} at version {} written to {}",
+                    manifest.epoch,
+                    manifest.version,
+                    out.display()
+                );
+                println!("waypoint: {}", manifest.waypoint);
             }
             Some(Sub::EpochRestore {
                 bundle_path,
@@ -112,21 +320,84 @@ impl StorageCli {
                 }
             }
             Some(Sub::DownloadRestoreBundle {
-                owner,
-                repo,
-                branch,
+                source,
                 epoch,
                 destination,
             }) => {
                 download_bundle::download_restore_bundle(
-                    &owner,
-                    &repo,
-                    &branch,
+                    source.source().as_ref(),
                     &epoch,
                     &destination,
                 )
                 .await?;
             }
+            Some(Sub::RangeRestore {
+                source,
+                from_epoch,
+                to_epoch,
+                trust_waypoint,
+                destination_db,
+                work_dir,
+            }) => {
+                let work_dir =
+                    work_dir.unwrap_or_else(|| range_restore::default_work_dir(&destination_db));
+                let progress = range_restore::range_restore(
+                    source.source().as_ref(),
+                    from_epoch,
+                    to_epoch,
+                    &trust_waypoint,
+                    &work_dir,
+                    &destination_db,
+                )
+                .await?;
+                println!(
+                    "restored epochs {from_epoch} to {to_epoch} from the snapshot at version {} to version {}",
+                    progress.plan.snapshot_version,
+                    progress.target_version.unwrap_or_default()
+                );
+            }
+            Some(Sub::Usage { db_path, json }) => {
+                let usage = prune::db_usage(&db_path)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&usage)?);
+                } else {
+                    for (db, bytes) in &usage.disk_bytes {
+                        println!("{db}: {bytes} bytes on disk");
+                    }
+                    println!(
+                        "\n{:<20} {:<40} {:>16} {:>14}",
+                        "db", "column family", "sst bytes", "keys (est.)"
+                    );
+                    for cf in &usage.column_families {
+                        println!(
+                            "{:<20} {:<40} {:>16} {:>14}",
+                            cf.db, cf.column_family, cf.sst_bytes, cf.estimated_keys
+                        );
+                    }
+                    println!(
+                        "\nepoch {}, latest version {}",
+                        usage.epoch, usage.latest_version
+                    );
+                    println!("oldest transaction: {:?}", usage.first_txn_version);
+                    println!("oldest write set: {:?}", usage.first_write_set_version);
+                }
+            }
+            Some(Sub::Prune {
+                db_path,
+                keep_epochs,
+                node_yaml,
+            }) => {
+                let node_yaml = node_yaml.or_else(|| {
+                    let p = global_config_dir().join(FN_FILENAME);
+                    p.exists().then_some(p)
+                });
+                if node_yaml.is_none() {
+                    println!("no node yaml found, only the db will be pruned");
+                }
+                if let Some(window) = prune::prune(&db_path, keep_epochs, node_yaml.as_deref())? {
+                    println!("SUCCESS: kept the last {window} versions");
+                }
+            }
             Some(Sub::Bootstrap {
                 db_path,
                 home_path,
@@ -0,0 +1,535 @@
+//! Verify backup archives offline, before spending hours restoring them.
+//! Epoch-ending ledger infos are checked as a signature chain anchored at a
+//! trusted waypoint, and the ledger infos of the state snapshot and
+//! transaction proofs are checked against that chain. No DB is written.
+
+use anyhow::{bail, ensure, Context, Result};
+use diem_backup_cli::backup_types::{
+    epoch_ending::manifest::EpochEndingBackup, state_snapshot::manifest::StateSnapshotBackup,
+    transaction::manifest::TransactionBackup,
+};
+use diem_crypto::{
+    hash::{CryptoHash, SPARSE_MERKLE_PLACEHOLDER_HASH},
+    HashValue,
+};
+use diem_types::{
+    contract_event::ContractEvent,
+    epoch_change::Verifier,
+    ledger_info::LedgerInfoWithSignatures,
+    proof::{
+        SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleRangeProof,
+        TransactionAccumulatorRangeProof, TransactionInfoListWithProof, TransactionInfoWithProof,
+    },
+    state_store::{state_key::StateKey, state_value::StateValue},
+    transaction::{Transaction, TransactionInfo, TransactionListWithProof},
+    waypoint::Waypoint,
+    write_set::WriteSet,
+};
+use flate2::read::GzDecoder;
+use glob::glob;
+use serde::de::DeserializeOwned;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// Epoch-ending ledger infos whose signatures have been verified
+#[derive(Default)]
+pub struct VerifiedEpochs {
+    endings: BTreeMap<u64, LedgerInfoWithSignatures>,
+}
+
+impl VerifiedEpochs {
+    /// Checks a ledger info is signed by the validator set of its epoch,
+    /// which is given by the verified ending of the previous epoch.
+    pub fn verify_ledger_info(&self, li: &LedgerInfoWithSignatures) -> Result<()> {
+        let epoch = li.ledger_info().epoch();
+        if let Some(known) = self.endings.get(&epoch) {
+            if known.ledger_info() == li.ledger_info() {
+                return Ok(());
+            }
+        }
+        let Some(previous) = epoch.checked_sub(1).and_then(|e| self.endings.get(&e)) else {
+            bail!(
+                "cannot verify a ledger info of epoch {epoch}, the bundle has no verified ending of epoch {}",
+                epoch.saturating_sub(1)
+            );
+        };
+        previous
+            .ledger_info()
+            .next_epoch_state()
+            .context("epoch-ending ledger info has no next epoch state")?
+            .verify(li)
+            .with_context(|| {
+                format!("ledger info of epoch {epoch} is not signed by its validators")
+            })
+    }
+
+    pub fn epochs(&self) -> impl Iterator<Item = &u64> {
+        self.endings.keys()
+    }
+}
+
+/// The result of verifying one manifest
+pub struct Check {
+    pub manifest: PathBuf,
+    pub outcome: Result<String>,
+}
+
+/// Verifies every epoch-ending, state snapshot and transaction archive in
+/// the bundle directory. Failures are reported per manifest, so one bad
+/// archive doesn't hide the state of the others.
+pub fn verify_bundle(bundle_dir: &Path, trusted: &[Waypoint]) -> Result<Vec<Check>> {
+    let mut checks = vec![];
+    let mut epochs = VerifiedEpochs::default();
+
+    // the chain must be followed in order
+    let mut epoch_manifests = vec![];
+    for path in find_manifests(bundle_dir, "epoch_ending.manifest")? {
+        let manifest: EpochEndingBackup = read_manifest(&path)?;
+        epoch_manifests.push((manifest.first_epoch, path));
+    }
+    epoch_manifests.sort();
+    for (_, path) in epoch_manifests {
+        let outcome = verify_epoch_endings(&path, trusted, &mut epochs);
+        checks.push(Check {
+            manifest: path,
+            outcome,
+        });
+    }
+
+    for path in find_manifests(bundle_dir, "state.manifest")? {
+        let outcome = verify_state_snapshot(&path, &epochs);
+        checks.push(Check {
+            manifest: path,
+            outcome,
+        });
+    }
+
+    for path in find_manifests(bundle_dir, "transaction.manifest")? {
+        let outcome = verify_transactions(&path, &epochs);
+        checks.push(Check {
+            manifest: path,
+            outcome,
+        });
+    }
+
+    if checks.is_empty() {
+        bail!("no backup manifests found in {}", bundle_dir.display());
+    }
+    Ok(checks)
+}
+
+/// Verifies the signature chain of an epoch-ending archive. An epoch is
+/// trusted if a trusted waypoint has its version, otherwise it must be
+/// signed by the validators of the previous, already verified, epoch.
+pub fn verify_epoch_endings(
+    manifest_path: &Path,
+    trusted: &[Waypoint],
+    epochs: &mut VerifiedEpochs,
+) -> Result<String> {
+    let manifest: EpochEndingBackup = read_manifest(manifest_path)?;
+    let root = archive_root(manifest_path)?;
+    ensure!(
+        manifest.waypoints.len() as u64 == manifest.last_epoch - manifest.first_epoch + 1,
+        "manifest has {} waypoints for epochs {} to {}",
+        manifest.waypoints.len(),
+        manifest.first_epoch,
+        manifest.last_epoch
+    );
+
+    let mut waypoints = manifest.waypoints.iter();
+    let mut next_epoch = manifest.first_epoch;
+    for chunk in &manifest.chunks {
+        let bytes = read_handle(root, &chunk.ledger_infos)?;
+        for record in records(&bytes)? {
+            let li: LedgerInfoWithSignatures = bcs::from_bytes(record)?;
+            let epoch = li.ledger_info().epoch();
+            ensure!(
+                epoch == next_epoch,
+                "expected the ledger info of epoch {next_epoch}, found epoch {epoch}"
+            );
+            let wp = Waypoint::new_epoch_boundary(li.ledger_info())?;
+            ensure!(
+                waypoints.next() == Some(&wp),
+                "ledger info of epoch {epoch} does not match the manifest waypoint"
+            );
+
+            if let Some(t) = trusted.iter().find(|t| t.version() == wp.version()) {
+                ensure!(
+                    *t == wp,
+                    "epoch {epoch} does not match the trusted waypoint {t}, found {wp}"
+                );
+            } else {
+                epochs.verify_ledger_info(&li).with_context(|| {
+                    format!("epoch {epoch} is not anchored to a trusted waypoint")
+                })?;
+            }
+            epochs.endings.insert(epoch, li);
+            next_epoch += 1;
+        }
+    }
+    ensure!(
+        next_epoch == manifest.last_epoch + 1,
+        "chunks end at epoch {}, manifest ends at {}",
+        next_epoch - 1,
+        manifest.last_epoch
+    );
+
+    Ok(format!(
+        "epochs {} to {} signed and chained",
+        manifest.first_epoch, manifest.last_epoch
+    ))
+}
+
+/// Verifies the snapshot's root hash is committed by a verified ledger
+/// info, then every chunk's range proof against that root hash.
+pub fn verify_state_snapshot(manifest_path: &Path, epochs: &VerifiedEpochs) -> Result<String> {
+    let manifest: StateSnapshotBackup = read_manifest(manifest_path)?;
+    let root = archive_root(manifest_path)?;
+
+    let (txn_info_with_proof, li): (TransactionInfoWithProof, LedgerInfoWithSignatures) =
+        bcs::from_bytes(&read_handle(root, &manifest.proof)?)?;
+    txn_info_with_proof.verify(li.ledger_info(), manifest.version)?;
+    let state_root = txn_info_with_proof
+        .transaction_info()
+        .ensure_state_checkpoint_hash()?;
+    ensure!(
+        state_root == manifest.root_hash,
+        "manifest root hash {} does not match the proven root hash {state_root}",
+        manifest.root_hash
+    );
+    epochs.verify_ledger_info(&li)?;
+
+    // key hash and value hash of every leaf so far, in key order
+    let mut leaves: Vec<(HashValue, HashValue)> = vec![];
+    for chunk in &manifest.chunks {
+        let bytes = read_handle(root, &chunk.blobs)?;
+        let start = leaves.len();
+        for record in records(&bytes)? {
+            let (key, value): (StateKey, StateValue) = bcs::from_bytes(record)?;
+            let key_hash = key.hash();
+            if let Some((previous, _)) = leaves.last() {
+                ensure!(key_hash > *previous, "state keys are not in order");
+            }
+            leaves.push((key_hash, value.hash()));
+        }
+
+        let chunk_leaves = &leaves[start..];
+        ensure!(
+            chunk_leaves.len() == chunk.last_idx + 1 - chunk.first_idx
+                && chunk_leaves.first().map(|l| l.0) == Some(chunk.first_key)
+                && chunk_leaves.last().map(|l| l.0) == Some(chunk.last_key),
+            "chunk {}-{} does not match its manifest entry",
+            chunk.first_idx,
+            chunk.last_idx
+        );
+        let proof: SparseMerkleRangeProof = bcs::from_bytes(&read_handle(root, &chunk.proof)?)?;
+        verify_range_proof(&leaves, &proof, manifest.root_hash).with_context(|| {
+            format!(
+                "range proof of chunk {}-{} failed",
+                chunk.first_idx, chunk.last_idx
+            )
+        })?;
+    }
+    ensure!(
+        subtree_root(&leaves, 0) == manifest.root_hash,
+        "state values do not hash to the root hash"
+    );
+
+    Ok(format!(
+        "{} state values at version {} match root hash {}",
+        leaves.len(),
+        manifest.version,
+        manifest.root_hash
+    ))
+}
+
+/// Verifies each chunk's transactions against the accumulator range proof
+/// of a verified ledger info, and the write sets against the infos.
+pub fn verify_transactions(manifest_path: &Path, epochs: &VerifiedEpochs) -> Result<String> {
+    let manifest: TransactionBackup = read_manifest(manifest_path)?;
+    let root = archive_root(manifest_path)?;
+
+    let mut next_version = manifest.first_version;
+    for chunk in &manifest.chunks {
+        ensure!(
+            chunk.first_version == next_version,
+            "chunks are not contiguous, expected version {next_version}, found {}",
+            chunk.first_version
+        );
+        let (range_proof, li): (TransactionAccumulatorRangeProof, LedgerInfoWithSignatures) =
+            bcs::from_bytes(&read_handle(root, &chunk.proof)?)?;
+        epochs.verify_ledger_info(&li)?;
+
+        let bytes = read_handle(root, &chunk.transactions)?;
+        let mut txns = vec![];
+        let mut txn_infos = vec![];
+        let mut event_vecs = vec![];
+        for record in records(&bytes)? {
+            let (txn, info, events, write_set): (
+                Transaction,
+                TransactionInfo,
+                Vec<ContractEvent>,
+                WriteSet,
+            ) = bcs::from_bytes(record)?;
+            ensure!(
+                info.state_change_hash() == write_set.hash(),
+                "write set of version {} does not match its transaction info",
+                chunk.first_version + txns.len() as u64
+            );
+            txns.push(txn);
+            txn_infos.push(info);
+            event_vecs.push(events);
+        }
+        ensure!(
+            txns.len() as u64 == chunk.last_version + 1 - chunk.first_version,
+            "chunk {}-{} has {} transactions",
+            chunk.first_version,
+            chunk.last_version,
+            txns.len()
+        );
+
+        let list = TransactionListWithProof::new(
+            txns,
+            Some(event_vecs),
+            Some(chunk.first_version),
+            TransactionInfoListWithProof::new(range_proof, txn_infos),
+        );
+        list.verify(li.ledger_info(), Some(chunk.first_version))
+            .with_context(|| {
+                format!(
+                    "accumulator proof of versions {}-{} failed",
+                    chunk.first_version, chunk.last_version
+                )
+            })?;
+        next_version = chunk.last_version + 1;
+    }
+    ensure!(
+        next_version == manifest.last_version + 1,
+        "chunks end at version {}, manifest ends at {}",
+        next_version - 1,
+        manifest.last_version
+    );
+
+    Ok(format!(
+        "versions {} to {} match the accumulator",
+        manifest.first_version, manifest.last_version
+    ))
+}
+
+/// Checks that all leaves so far, plus the proof's right siblings, hash to
+/// the root. The left siblings on the path of the rightmost leaf are
+/// subtrees of leaves already seen, so they are complete.
+fn verify_range_proof(
+    leaves: &[(HashValue, HashValue)],
+    proof: &SparseMerkleRangeProof,
+    root_hash: HashValue,
+) -> Result<()> {
+    let Some((key, value_hash)) = leaves.last() else {
+        bail!("no leaves to prove");
+    };
+
+    // the path ends after the zero bit of the last right sibling, plus any
+    // one bits below it
+    let num_right = proof.right_siblings().len();
+    let mut depth = 0;
+    let mut zeros = 0;
+    while depth < HashValue::LENGTH_IN_BITS {
+        if !bit(key, depth) {
+            if zeros == num_right {
+                break;
+            }
+            zeros += 1;
+        }
+        depth += 1;
+    }
+
+    // left siblings from the root down
+    let mut left_siblings = vec![];
+    let mut subtree = leaves;
+    for i in 0..depth {
+        let split = subtree.partition_point(|(k, _)| !bit(k, i));
+        if bit(key, i) {
+            left_siblings.push(subtree_root(&subtree[..split], i + 1));
+            subtree = &subtree[split..];
+        } else {
+            subtree = &subtree[..split];
+        }
+    }
+
+    // a leaf alone in its subtree sits higher, so drop the empty left
+    // siblings at the bottom of the path
+    while depth > 0
+        && bit(key, depth - 1)
+        && left_siblings.last() == Some(&*SPARSE_MERKLE_PLACEHOLDER_HASH)
+    {
+        left_siblings.pop();
+        depth -= 1;
+    }
+
+    left_siblings.reverse();
+    proof.verify(
+        root_hash,
+        SparseMerkleLeafNode::new(*key, *value_hash),
+        left_siblings,
+    )?;
+    Ok(())
+}
+
+/// Root of the sparse merkle subtree holding `leaves`, all of which share
+/// their first `depth` bits.
+fn subtree_root(leaves: &[(HashValue, HashValue)], depth: usize) -> HashValue {
+    match leaves {
+        [] => *SPARSE_MERKLE_PLACEHOLDER_HASH,
+        [(key, value_hash)] => SparseMerkleLeafNode::new(*key, *value_hash).hash(),
+        _ => {
+            let split = leaves.partition_point(|(k, _)| !bit(k, depth));
+            SparseMerkleInternalNode::new(
+                subtree_root(&leaves[..split], depth + 1),
+                subtree_root(&leaves[split..], depth + 1),
+            )
+            .hash()
+        }
+    }
+}
+
+fn bit(hash: &HashValue, index: usize) -> bool {
+    hash[index / 8] & (0x80 >> (index % 8)) != 0
+}
+
+fn find_manifests(bundle_dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let pattern = format!("{}/**/{name}", bundle_dir.display());
+    Ok(glob(&pattern)?.flatten().collect())
+}
+
+fn read_manifest<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let s = fs::read_to_string(path)
+        .with_context(|| format!("cannot read manifest at {}", path.display()))?;
+    Ok(serde_json::from_str(&s)?)
+}
+
+/// File handles in a manifest are relative to the backup root, the parent
+/// of the archive's folder
+fn archive_root(manifest_path: &Path) -> Result<&Path> {
+    manifest_path
+        .parent()
+        .and_then(|p| p.parent())
+        .context("manifest is not inside an archive folder")
+}
+
+/// Reads a file of the archive, whether or not it is still gzipped
+fn read_handle(root: &Path, handle: &str) -> Result<Vec<u8>> {
+    let plain = root.join(handle.strip_suffix(".gz").unwrap_or(handle));
+    let gz = PathBuf::from(format!("{}.gz", plain.display()));
+    if plain.exists() {
+        Ok(fs::read(plain)?)
+    } else if gz.exists() {
+        let mut bytes = vec![];
+        GzDecoder::new(File::open(&gz)?).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        bail!("archive file {} is missing", plain.display())
+    }
+}
+
+/// Splits a backup file into its records, each prefixed by a big endian u32 length
+fn records(mut bytes: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut out = vec![];
+    while !bytes.is_empty() {
+        ensure!(bytes.len() >= 4, "truncated record length");
+        let len = u32::from_be_bytes(bytes[..4].try_into()?) as usize;
+        ensure!(bytes.len() >= 4 + len, "truncated record");
+        out.push(&bytes[4..4 + len]);
+        bytes = &bytes[4 + len..];
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_epoch_manifest() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/v7/epoch_ending_116-.be9b/epoch_ending.manifest")
+    }
+
+    #[test]
+    fn epoch_chain_needs_trusted_waypoint() {
+        let trusted: Waypoint =
+            "38180075:98d7eb16747c1441350c4a1a6aa8b2d504c30ce2520807f2adc87d5a06d4f70f"
+                .parse()
+                .unwrap();
+        let mut epochs = VerifiedEpochs::default();
+        verify_epoch_endings(&fixture_epoch_manifest(), &[trusted], &mut epochs).unwrap();
+        assert_eq!(epochs.epochs().collect::<Vec<_>>(), vec![&116]);
+
+        // a bundle can't vouch for itself
+        let mut epochs = VerifiedEpochs::default();
+        assert!(verify_epoch_endings(&fixture_epoch_manifest(), &[], &mut epochs).is_err());
+
+        // nor match a different trusted waypoint at that version
+        let other: Waypoint =
+            "38180075:0000000000000000000000000000000000000000000000000000000000000000"
+                .parse()
+                .unwrap();
+        let mut epochs = VerifiedEpochs::default();
+        assert!(verify_epoch_endings(&fixture_epoch_manifest(), &[other], &mut epochs).is_err());
+    }
+
+    #[test]
+    fn range_proofs_of_chunks() {
+        let mut leaves: Vec<(HashValue, HashValue)> = (0u64..50)
+            .map(|i| {
+                (
+                    HashValue::sha3_256_of(&i.to_le_bytes()),
+                    HashValue::sha3_256_of(&(i + 1000).to_le_bytes()),
+                )
+            })
+            .collect();
+        leaves.sort();
+        let root = subtree_root(&leaves, 0);
+
+        // the right siblings on the path of the rightmost leaf of a chunk,
+        // bottom up, as the backup service writes them
+        let right_siblings = |last: usize| {
+            let key = leaves[last].0;
+            let mut siblings = vec![];
+            let mut subtree = &leaves[..];
+            let mut depth = 0;
+            while subtree.len() > 1 {
+                let split = subtree.partition_point(|(k, _)| !bit(k, depth));
+                if bit(&key, depth) {
+                    subtree = &subtree[split..];
+                } else {
+                    siblings.push(subtree_root(&subtree[split..], depth + 1));
+                    subtree = &subtree[..split];
+                }
+                depth += 1;
+            }
+            siblings.reverse();
+            siblings
+        };
+
+        for last in [9, 24, 49] {
+            let proof = SparseMerkleRangeProof::new(right_siblings(last));
+            verify_range_proof(&leaves[..=last], &proof, root).unwrap();
+        }
+
+        // a tampered value fails
+        let proof = SparseMerkleRangeProof::new(right_siblings(24));
+        leaves[3].1 = HashValue::zero();
+        assert!(verify_range_proof(&leaves[..=24], &proof, root).is_err());
+    }
+
+    #[test]
+    fn split_records() {
+        let bytes = [0, 0, 0, 2, 7, 8, 0, 0, 0, 0, 0, 0, 0, 1, 9];
+        let r = records(&bytes).unwrap();
+        assert_eq!(r, vec![&[7u8, 8][..], &[][..], &[9][..]]);
+        assert!(records(&bytes[..4]).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use libra_storage::{download_bundle, restore};
+use libra_storage::{archive_source::GitHubSource, download_bundle, restore};
 use std::path::PathBuf;
 
 /// Helper to do a single step operation to download a backup archive from epoch archive,
@@ -43,14 +43,9 @@ pub async fn one_step_restore_db(
         data_path.display()
     );
 
-    let backup_files_dir = download_bundle::download_restore_bundle(
-        &owner_str,
-        &repo_str,
-        &branch_str,
-        &epoch,
-        &data_path,
-    )
-    .await?;
+    let source = GitHubSource::new(&owner_str, &repo_str, &branch_str);
+    let backup_files_dir =
+        download_bundle::download_restore_bundle(&source, &epoch, &data_path).await?;
 
     let destination_db = data_path.join(format!("db_{epoch}"));
     // Restore and bootstrap the DB using the EpochRestore subcommand
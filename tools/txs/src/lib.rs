@@ -1,5 +1,6 @@
 pub mod constants;
 pub mod generic_tx;
+pub mod multi_sig;
 pub mod publish;
 pub mod stream;
 pub mod submit_transaction;
@@ -7,6 +8,7 @@ pub mod transfer;
 pub mod txs_cli;
 pub mod txs_cli_community;
 pub mod txs_cli_governance;
+pub mod txs_cli_multi_sig;
 pub mod txs_cli_stream;
 pub mod txs_cli_user;
 pub mod txs_cli_vals;
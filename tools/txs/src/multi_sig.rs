@@ -0,0 +1,285 @@
+//! K-of-N multi-ed25519 accounts, for owner accounts no single person should control.
+//! A policy lists the participants' public keys and the threshold. Proposals
+//! are written to a file, each participant adds a signature offline, and the
+//! file is submitted once enough signatures are collected.
+
+use crate::submit_transaction::Sender;
+use anyhow::{bail, Context};
+use diem::common::types::RotationProofChallenge;
+use diem_sdk::{
+    crypto::{
+        ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
+        multi_ed25519::{MultiEd25519PublicKey, MultiEd25519Signature},
+        signing_message, PrivateKey, Signature, SigningKey,
+    },
+    rest_client::diem_api_types::TransactionOnChainData,
+    transaction_builder::TransactionBuilder,
+    types::transaction::{RawTransaction, SignedTransaction, TransactionPayload},
+};
+use diem_types::account_config::CORE_CODE_ADDRESS;
+use libra_cached_packages::libra_stdlib;
+use libra_types::{
+    exports::{AccountAddress, AuthenticationKey},
+    type_extensions::client_ext::ClientExt,
+};
+use libra_wallet::utils::write_to_user_only_file;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Participants need time to sign, so proposals expire after a day
+pub const PROPOSAL_EXPIRATION_SECS: u64 = 24 * 60 * 60;
+
+/// Scheme ids of `0x1::account`
+const ED25519_SCHEME: u8 = 0;
+const MULTI_ED25519_SCHEME: u8 = 1;
+
+/// The participants of a multi-ed25519 account and how many must sign
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiSigPolicy {
+    pub threshold: u8,
+    /// the order matters, a participant signs with their index in this list
+    pub public_keys: Vec<Ed25519PublicKey>,
+}
+
+impl MultiSigPolicy {
+    pub fn new(threshold: u8, public_keys: Vec<Ed25519PublicKey>) -> anyhow::Result<Self> {
+        let policy = Self {
+            threshold,
+            public_keys,
+        };
+        // checks the threshold and number of keys
+        policy.public_key()?;
+        Ok(policy)
+    }
+
+    pub fn public_key(&self) -> anyhow::Result<MultiEd25519PublicKey> {
+        MultiEd25519PublicKey::new(self.public_keys.clone(), self.threshold)
+            .map_err(|e| anyhow::anyhow!("invalid policy, {e}"))
+    }
+
+    pub fn auth_key(&self) -> anyhow::Result<AuthenticationKey> {
+        Ok(AuthenticationKey::multi_ed25519(&self.public_key()?))
+    }
+
+    /// The index of a participant's key, used in the signature bitmap
+    pub fn index_of(&self, key: &Ed25519PublicKey) -> Option<u8> {
+        self.public_keys
+            .iter()
+            .position(|k| k == key)
+            .map(|i| i as u8)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("cannot read policy at {}", path.display()))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+}
+
+/// What the participants are signing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Proposal {
+    /// Rotate a single key account to the policy's key. The participants sign
+    /// the rotation challenge, the current owner submits it.
+    Rotation {
+        account: AccountAddress,
+        #[serde(with = "hex::serde")]
+        challenge: Vec<u8>,
+    },
+    /// A transaction sent by the multi-ed25519 account
+    Transaction {
+        #[serde(with = "hex::serde")]
+        raw_txn: Vec<u8>,
+    },
+}
+
+/// A proposal with the signatures collected so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiSigFile {
+    pub policy: MultiSigPolicy,
+    pub proposal: Proposal,
+    /// signatures by participant index
+    pub signatures: BTreeMap<u8, Ed25519Signature>,
+}
+
+impl MultiSigFile {
+    /// Proposes rotating the sender's account to the policy's key.
+    pub async fn propose_rotation(sender: &Sender, policy: MultiSigPolicy) -> anyhow::Result<Self> {
+        let account = sender.local_account.address();
+        let seq = sender.client().get_sequence_number(account).await?;
+        // same challenge as a single key rotation, see account.move
+        let challenge = RotationProofChallenge {
+            account_address: CORE_CODE_ADDRESS,
+            module_name: "account".to_string(),
+            struct_name: "RotationProofChallenge".to_string(),
+            sequence_number: seq,
+            originator: account,
+            current_auth_key: AccountAddress::from_bytes(
+                sender.local_account.authentication_key(),
+            )?,
+            new_public_key: policy.public_key()?.to_bytes(),
+        };
+
+        Ok(Self {
+            policy,
+            proposal: Proposal::Rotation {
+                account,
+                challenge: bcs::to_bytes(&challenge)?,
+            },
+            signatures: BTreeMap::new(),
+        })
+    }
+
+    /// Proposes a transaction from the account controlled by the policy.
+    pub async fn propose_transaction(
+        sender: &Sender,
+        policy: MultiSigPolicy,
+        payload: TransactionPayload,
+    ) -> anyhow::Result<Self> {
+        // a rotated account is found by its authentication key
+        let account = sender
+            .client()
+            .lookup_originating_address(policy.auth_key()?)
+            .await?;
+        let seq = sender.client().get_sequence_number(account).await?;
+        let expiration =
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + PROPOSAL_EXPIRATION_SECS;
+
+        let raw_txn = TransactionBuilder::new(payload, expiration, sender.chain_id())
+            .sender(account)
+            .sequence_number(seq)
+            .gas_unit_price(sender.tx_cost.coin_price_per_unit)
+            .max_gas_amount(sender.tx_cost.max_gas_unit_for_tx)
+            .build();
+
+        Ok(Self {
+            policy,
+            proposal: Proposal::Transaction {
+                raw_txn: bcs::to_bytes(&raw_txn)?,
+            },
+            signatures: BTreeMap::new(),
+        })
+    }
+
+    /// The account the proposal acts on
+    pub fn account(&self) -> anyhow::Result<AccountAddress> {
+        Ok(match &self.proposal {
+            Proposal::Rotation { account, .. } => *account,
+            Proposal::Transaction { .. } => self.raw_txn()?.sender(),
+        })
+    }
+
+    fn raw_txn(&self) -> anyhow::Result<RawTransaction> {
+        match &self.proposal {
+            Proposal::Transaction { raw_txn } => Ok(bcs::from_bytes(raw_txn)?),
+            Proposal::Rotation { .. } => bail!("proposal is a rotation, not a transaction"),
+        }
+    }
+
+    /// The bytes each participant signs
+    fn message(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(match &self.proposal {
+            Proposal::Rotation { challenge, .. } => challenge.to_owned(),
+            Proposal::Transaction { .. } => signing_message(&self.raw_txn()?)?,
+        })
+    }
+
+    /// Adds the participant's signature. Works offline.
+    pub fn sign(&mut self, key: &Ed25519PrivateKey) -> anyhow::Result<u8> {
+        let Some(index) = self.policy.index_of(&key.public_key()) else {
+            bail!("this key is not a participant of the policy");
+        };
+        let signature = key.sign_arbitrary_message(&self.message()?);
+        self.signatures.insert(index, signature);
+        Ok(index)
+    }
+
+    /// Checks every collected signature, then combines `threshold` of them.
+    pub fn combine(&self) -> anyhow::Result<MultiEd25519Signature> {
+        let message = self.message()?;
+        let mut partial = vec![];
+        for (index, sig) in &self.signatures {
+            let Some(key) = self.policy.public_keys.get(*index as usize) else {
+                bail!("signature for participant {index}, who is not in the policy");
+            };
+            sig.verify_arbitrary_msg(&message, key)
+                .with_context(|| format!("signature of participant {index} is not valid"))?;
+            partial.push((sig.to_owned(), *index));
+        }
+
+        if partial.len() < self.policy.threshold as usize {
+            bail!(
+                "need {} signatures, have {}",
+                self.policy.threshold,
+                partial.len()
+            );
+        }
+        partial.truncate(self.policy.threshold as usize);
+        MultiEd25519Signature::new(partial).map_err(|e| anyhow::anyhow!("cannot combine, {e}"))
+    }
+
+    /// Combines the signatures and submits. A rotation must be submitted by
+    /// the account's current owner, a transaction by anyone.
+    pub async fn submit(&self, sender: &mut Sender) -> anyhow::Result<TransactionOnChainData> {
+        let signature = self.combine()?;
+        let res = match &self.proposal {
+            Proposal::Rotation { account, challenge } => {
+                if sender.local_account.address() != *account {
+                    bail!("a rotation must be submitted by the current owner of {account}");
+                }
+                let current_key = sender.local_account.private_key();
+                let payload = libra_stdlib::account_rotate_authentication_key(
+                    ED25519_SCHEME,
+                    current_key.public_key().to_bytes().to_vec(),
+                    MULTI_ED25519_SCHEME,
+                    self.policy.public_key()?.to_bytes(),
+                    current_key
+                        .sign_arbitrary_message(challenge)
+                        .to_bytes()
+                        .to_vec(),
+                    signature.to_bytes(),
+                );
+                sender.sign_submit_wait(payload).await?
+            }
+            Proposal::Transaction { .. } => {
+                let signed = SignedTransaction::new_multisig(
+                    self.raw_txn()?,
+                    self.policy.public_key()?,
+                    signature,
+                );
+                sender.submit(&signed).await?
+            }
+        };
+
+        if !res.info.status().is_success() {
+            bail!("transaction failed, status: {:?}", res.info.status());
+        }
+        Ok(res)
+    }
+
+    /// Writes the file, readable only by the user.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        write_to_user_only_file(
+            path,
+            "multi-sig proposal",
+            &serde_json::to_vec_pretty(self)?,
+        )
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("cannot read proposal at {}", path.display()))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+}
@@ -5,7 +5,10 @@ use anyhow::{bail, Context};
 use diem::common::types::{CliConfig, ConfigSearchMode};
 use diem_logger::prelude::*;
 use diem_sdk::{
-    crypto::{HashValue, PrivateKey},
+    crypto::{
+        ed25519::{Ed25519Signature, ED25519_SIGNATURE_LENGTH},
+        HashValue, PrivateKey,
+    },
     rest_client::{
         diem_api_types::{TransactionOnChainData, UserTransaction},
         Client,
@@ -95,10 +98,16 @@ impl Sender {
 
         let key = match profile.borrow_private_key() {
             Ok(k) => k.to_owned(),
-            _ => {
-                let leg_keys = libra_wallet::account_keys::get_keys_from_prompt()?;
-                leg_keys.child_0_owner.pri_key
-            }
+            _ => match libra_wallet::keystore::unlock_profile_key(
+                &app_cfg.workspace.node_home,
+                &profile.nickname,
+            )? {
+                Some(k) => k,
+                None => {
+                    let leg_keys = libra_wallet::account_keys::get_keys_from_prompt()?;
+                    leg_keys.child_0_owner.pri_key
+                }
+            },
         };
 
         let temp_seq_num = 0;
@@ -238,17 +247,21 @@ impl Sender {
 
     /// Signs a transaction payload.
     pub fn sign_payload(&mut self, payload: TransactionPayload) -> SignedTransaction {
+        let tb = self.transaction_builder(payload);
+        self.local_account.sign_with_transaction_builder(tb)
+    }
+
+    /// A transaction builder with the expiry and gas costs of this sender.
+    fn transaction_builder(&self, payload: TransactionPayload) -> TransactionBuilder {
         let t = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
         let time = t + (DEFAULT_TIMEOUT_SECS * 10);
 
-        let tb = TransactionBuilder::new(payload, time, self.chain_id)
+        TransactionBuilder::new(payload, time, self.chain_id)
             .gas_unit_price(self.tx_cost.coin_price_per_unit)
-            .max_gas_amount(self.tx_cost.max_gas_unit_for_tx);
-
-        self.local_account.sign_with_transaction_builder(tb)
+            .max_gas_amount(self.tx_cost.max_gas_unit_for_tx)
     }
 
     /// submit to API and wait for the transaction on chain data
@@ -284,13 +297,32 @@ impl Sender {
         }
     }
 
+    /// The transaction the simulate endpoint takes for `payload`. It rejects
+    /// validly signed transactions, so this one carries a zeroed signature,
+    /// and the sequence number of the local account is left as is.
+    fn simulation_transaction(
+        &self,
+        payload: TransactionPayload,
+    ) -> anyhow::Result<SignedTransaction> {
+        let raw = self
+            .transaction_builder(payload)
+            .sender(self.local_account.address())
+            .sequence_number(self.local_account.sequence_number())
+            .build();
+        let zero_sig = Ed25519Signature::try_from(&[0u8; ED25519_SIGNATURE_LENGTH][..])?;
+        Ok(SignedTransaction::new(
+            raw,
+            self.local_account.public_key().clone(),
+            zero_sig,
+        ))
+    }
+
     /// estimate the transaction gas cost.
     pub async fn estimate(
         &mut self,
         payload: TransactionPayload,
     ) -> anyhow::Result<Vec<UserTransaction>> {
-        let signed = self.sign_payload(payload);
-
+        let signed = self.simulation_transaction(payload)?;
         let res = self
             .client
             .simulate_with_gas_estimation(&signed, true, true)
@@ -307,6 +339,11 @@ impl Sender {
         None
     }
 
+    /// The chain id transactions are signed for.
+    pub fn chain_id(&self) -> ChainId {
+        self.chain_id
+    }
+
     /// Returns a reference to the underlying client.
     pub fn client(&self) -> &Client {
         &self.client
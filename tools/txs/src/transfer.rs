@@ -1,11 +1,19 @@
 //! form a transfer payload and execute transaction
 use super::submit_transaction::Sender;
-use anyhow::bail;
+use anyhow::{bail, Context};
 use diem_sdk::{
-    rest_client::diem_api_types::TransactionOnChainData, types::account_address::AccountAddress,
+    crypto::HashValue,
+    rest_client::diem_api_types::{Transaction, TransactionOnChainData},
+    types::{account_address::AccountAddress, transaction::TransactionPayload},
 };
 use libra_cached_packages::libra_framework_sdk_builder::EntryFunctionCall::OlAccountTransfer;
-use libra_types::move_resource::gas_coin;
+use libra_types::{move_resource::gas_coin, type_extensions::client_ext::is_not_found};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+};
 
 impl Sender {
     pub async fn transfer(
@@ -14,13 +22,7 @@ impl Sender {
         amount: f64,
         estimate: bool,
     ) -> anyhow::Result<Option<TransactionOnChainData>> {
-        // must scale the coin from decimal to onchain representation
-        let coin_scaled = gas_coin::cast_decimal_to_coin(amount);
-        let payload = OlAccountTransfer {
-            to,
-            amount: coin_scaled,
-        }
-        .encode();
+        let payload = transfer_payload(to, amount);
 
         if estimate {
             let res = self.estimate(payload).await?;
@@ -43,4 +45,171 @@ impl Sender {
             }
         }
     }
+
+    /// Transfers the same amount to each account in turn, for funding
+    /// accounts from `libra wallet bulk-keygen`. Failures are reported and
+    /// skipped, and the accounts which failed are returned.
+    ///
+    /// Each transfer is appended to the `progress` log, first as
+    /// `<account> pending <tx hash>` before it is submitted, then as
+    /// `<account> ok <tx hash>` or `<account> failed <error>`. Accounts the
+    /// log already has as ok are skipped, so an interrupted run can resume
+    /// with the same log. A transfer which failed after it was submitted may
+    /// still have committed, so its pending transactions are looked up before
+    /// it is retried.
+    pub async fn batch_transfer(
+        &mut self,
+        to: &[AccountAddress],
+        amount: f64,
+        estimate: bool,
+        progress: Option<&Path>,
+    ) -> anyhow::Result<Vec<AccountAddress>> {
+        let (done, pending) = match progress {
+            Some(p) if p.exists() => (done_transfers(p)?, pending_transfers(p)?),
+            _ => (HashSet::new(), HashMap::new()),
+        };
+        let mut log = match progress {
+            Some(p) if !estimate => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(p)
+                    .with_context(|| format!("cannot open progress log {}", p.display()))?,
+            ),
+            _ => None,
+        };
+
+        let mut failed = vec![];
+        for (i, addr) in to.iter().enumerate() {
+            if done.contains(addr) {
+                println!("{}/{}: {addr} already funded, skipping", i + 1, to.len());
+                continue;
+            }
+            if let Some(hashes) = pending.get(addr) {
+                if let Some(hash) = self.find_committed(hashes).await? {
+                    println!(
+                        "{}/{}: {addr} was funded by an earlier run, skipping",
+                        i + 1,
+                        to.len()
+                    );
+                    if let Some(f) = log.as_mut() {
+                        writeln!(f, "{} ok {}", addr.to_hex_literal(), hash.to_hex_literal())?;
+                    }
+                    continue;
+                }
+            }
+            println!("{}/{}: transferring {amount} to {addr}", i + 1, to.len());
+            let res = match log.as_mut() {
+                Some(f) => {
+                    let signed = self.sign_payload(transfer_payload(*addr, amount));
+                    // logged before submitting, in case the wait fails but the
+                    // transaction commits
+                    writeln!(
+                        f,
+                        "{} pending {}",
+                        addr.to_hex_literal(),
+                        signed.clone().committed_hash().to_hex_literal()
+                    )?;
+                    f.flush()?;
+                    self.submit(&signed).await.map(Some)
+                }
+                None => self.transfer(*addr, amount, estimate).await,
+            };
+            let line = match res {
+                Ok(tx) => format!(
+                    "{} ok {}",
+                    addr.to_hex_literal(),
+                    tx.map(|t| t.info.transaction_hash().to_hex_literal())
+                        .unwrap_or_default()
+                ),
+                Err(e) => {
+                    println!("{e}");
+                    failed.push(*addr);
+                    // one line per transfer
+                    let e = e.to_string().replace('\n', " ");
+                    format!("{} failed {e}", addr.to_hex_literal())
+                }
+            };
+            if let Some(f) = log.as_mut() {
+                writeln!(f, "{line}")?;
+            }
+        }
+        Ok(failed)
+    }
+
+    /// The first of the transactions which committed successfully. A
+    /// transaction still in the mempool is an error, since retrying the
+    /// transfer could fund the account twice.
+    async fn find_committed(&self, hashes: &[HashValue]) -> anyhow::Result<Option<HashValue>> {
+        for hash in hashes {
+            match self.client().get_transaction_by_hash(*hash).await {
+                Ok(res) => match res.into_inner() {
+                    Transaction::PendingTransaction(_) => bail!(
+                        "transaction {} is still pending, run again once it commits or expires",
+                        hash.to_hex_literal()
+                    ),
+                    tx if tx.success() => return Ok(Some(*hash)),
+                    _ => {}
+                },
+                Err(e) => {
+                    let e = anyhow::Error::from(e);
+                    if !is_not_found(&e) {
+                        return Err(e.context(format!(
+                            "cannot look up transaction {}",
+                            hash.to_hex_literal()
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn transfer_payload(to: AccountAddress, amount: f64) -> TransactionPayload {
+    // must scale the coin from decimal to onchain representation
+    let coin_scaled = gas_coin::cast_decimal_to_coin(amount);
+    OlAccountTransfer {
+        to,
+        amount: coin_scaled,
+    }
+    .encode()
+}
+
+/// The accounts a batch transfer progress log has as funded
+pub fn done_transfers(progress: &Path) -> anyhow::Result<HashSet<AccountAddress>> {
+    let log = std::fs::read_to_string(progress)
+        .with_context(|| format!("cannot read progress log {}", progress.display()))?;
+    let mut done = HashSet::new();
+    for line in log.lines() {
+        let mut cols = line.split_whitespace();
+        if let (Some(addr), Some("ok")) = (cols.next(), cols.next()) {
+            done.insert(AccountAddress::from_hex_literal(addr)?);
+        }
+    }
+    Ok(done)
+}
+
+/// The transactions a batch transfer progress log has submitted to accounts
+/// it does not have as funded, in the order they were sent
+pub fn pending_transfers(
+    progress: &Path,
+) -> anyhow::Result<HashMap<AccountAddress, Vec<HashValue>>> {
+    let log = std::fs::read_to_string(progress)
+        .with_context(|| format!("cannot read progress log {}", progress.display()))?;
+    let done = done_transfers(progress)?;
+    let mut pending: HashMap<AccountAddress, Vec<HashValue>> = HashMap::new();
+    for line in log.lines() {
+        let mut cols = line.split_whitespace();
+        if let (Some(addr), Some("pending"), Some(hash)) = (cols.next(), cols.next(), cols.next()) {
+            let addr = AccountAddress::from_hex_literal(addr)?;
+            if !done.contains(&addr) {
+                pending
+                    .entry(addr)
+                    .or_default()
+                    .push(HashValue::from_hex_literal(hash)?);
+            }
+        }
+    }
+    Ok(pending)
 }
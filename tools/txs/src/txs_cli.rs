@@ -1,9 +1,14 @@
 use crate::{
-    publish::encode_publish_payload, submit_transaction::Sender, txs_cli_community::CommunityTxs,
-    txs_cli_governance::GovernanceTxs, txs_cli_stream::StreamTxs, txs_cli_user::UserTxs,
+    publish::encode_publish_payload,
+    submit_transaction::Sender,
+    txs_cli_community::CommunityTxs,
+    txs_cli_governance::GovernanceTxs,
+    txs_cli_multi_sig::{save_policy, MultiSigTxs},
+    txs_cli_stream::StreamTxs,
+    txs_cli_user::UserTxs,
     txs_cli_vals::ValidatorTxs,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 use diem::common::types::MovePackageDir;
 use diem_sdk::{
@@ -16,7 +21,11 @@ use libra_types::{
     core_types::app_cfg::{AppCfg, TxCost, TxType},
     exports::{ChainId, NamedChain},
 };
-use libra_wallet::account_keys::{get_keys_from_mnem, get_keys_from_prompt};
+use libra_wallet::{
+    account_keys::{get_keys_from_mnem, get_keys_from_prompt},
+    bulk_keygen::read_manifest,
+    keystore::unlock_profile_key,
+};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use url::Url;
@@ -32,7 +41,7 @@ pub struct TxsCli {
     #[clap(short, long)]
     pub config_path: Option<PathBuf>,
 
-    /// optional, mnemonic to pass at runtime. Otherwise this will prompt for mnemonic
+    /// optional, mnemonic to pass at runtime. Otherwise this will unlock the profile keystore, or prompt for mnemonic
     #[clap(short, long)]
     pub mnemonic: Option<String>,
 
@@ -82,6 +91,9 @@ pub enum TxsSub {
     #[clap(subcommand)]
     /// Perform transactions for addresses with Community Wallet settings
     Community(CommunityTxs),
+    #[clap(subcommand)]
+    /// Threshold signing for multi-ed25519 owner accounts
+    MultiSig(MultiSigTxs),
     /// Transfer coins between accounts (and create new account)
     // Transferring can also be used to create accounts
     Transfer {
@@ -93,6 +105,21 @@ pub enum TxsSub {
         #[clap(short, long)]
         amount: f64,
     },
+    /// Transfer the same amount to every account in a manifest from `libra wallet bulk-keygen`
+    BatchTransfer {
+        /// JSON or CSV manifest of accounts
+        #[clap(short, long)]
+        manifest: PathBuf,
+
+        /// The amount of coins to transfer to each account
+        #[clap(short, long)]
+        amount: f64,
+
+        /// Log of completed transfers, to resume an interrupted run.
+        /// Defaults to the manifest path with `.progress` appended.
+        #[clap(long)]
+        progress: Option<PathBuf>,
+    },
     #[clap(subcommand, hide(true))]
     /// Warn: Streaming transactions is experimental
     Stream(StreamTxs),
@@ -143,8 +170,18 @@ pub enum TxsSub {
 impl TxsCli {
     /// Executes the transaction CLI command based on parsed arguments.
     pub async fn run(&self) -> Result<()> {
+        // a policy is made from public keys, it needs neither a config nor a key
+        if let Some(TxsSub::MultiSig(MultiSigTxs::Policy {
+            threshold,
+            public_key,
+            out,
+        })) = &self.subcommand
+        {
+            return save_policy(*threshold, public_key, out);
+        }
+
         // Load application configuration
-        let app_cfg = AppCfg::load(self.config_path.clone())?;
+        let mut app_cfg = AppCfg::load(self.config_path.clone())?;
         let profile = app_cfg.get_profile(None)?;
 
         // Determine private key based on CLI options or prompts
@@ -158,11 +195,21 @@ impl TxsCli {
         } else if let Ok(p) = profile.borrow_private_key() {
             println!("use private key from test libra-cli-config.yaml");
             p.to_owned()
+        } else if let Some(k) = unlock_profile_key(&app_cfg.workspace.node_home, &profile.nickname)?
+        {
+            k
         } else {
             let legacy = get_keys_from_prompt()?;
             legacy.child_0_owner.pri_key
         };
 
+        // policies and signatures are made offline, no node needed
+        if let Some(TxsSub::MultiSig(multi)) = &self.subcommand {
+            if multi.is_offline() {
+                return multi.run_offline(&pri_key);
+            }
+        }
+
         // Determine chain ID and URL for client
         let chain_name = self
             .chain_name
@@ -207,6 +254,32 @@ impl TxsCli {
                     .await?;
                 Ok(())
             }
+            Some(TxsSub::BatchTransfer {
+                manifest,
+                amount,
+                progress,
+            }) => {
+                let to: Vec<AccountAddress> =
+                    read_manifest(manifest)?.iter().map(|a| a.account).collect();
+                let progress = progress.clone().unwrap_or_else(|| {
+                    let mut p = manifest.as_os_str().to_owned();
+                    p.push(".progress");
+                    PathBuf::from(p)
+                });
+                let failed = send
+                    .batch_transfer(&to, *amount, self.estimate_only, Some(&progress))
+                    .await?;
+                if !failed.is_empty() {
+                    bail!(
+                        "{} of {} transfers failed: {:?}, run again to retry them (progress in {})",
+                        failed.len(),
+                        to.len(),
+                        failed,
+                        progress.display()
+                    );
+                }
+                Ok(())
+            }
             Some(TxsSub::Publish(move_opts)) => {
                 let payload = encode_publish_payload(move_opts)?;
                 send.sign_submit_wait(payload).await?;
@@ -220,8 +293,9 @@ impl TxsCli {
             }) => send.generic(function_id, ty_args, args).await,
             Some(TxsSub::Validator(val_txs)) => val_txs.run(&mut send).await,
             Some(TxsSub::Governance(upgrade_txs)) => upgrade_txs.run(&mut send).await,
-            Some(TxsSub::User(user_txs)) => user_txs.run(&mut send).await,
+            Some(TxsSub::User(user_txs)) => user_txs.run(&mut send, &mut app_cfg).await,
             Some(TxsSub::Community(comm_txs)) => comm_txs.run(&mut send).await,
+            Some(TxsSub::MultiSig(multi_txs)) => multi_txs.run(&mut send).await,
             Some(TxsSub::Stream(stream_txs)) => {
                 let arc_send = Arc::new(Mutex::new(send));
                 stream_txs.start(arc_send);
@@ -0,0 +1,168 @@
+//! Multi-ed25519 owner account subcommands
+
+use crate::{
+    generic_tx::build_entry_function,
+    multi_sig::{MultiSigFile, MultiSigPolicy},
+    submit_transaction::Sender,
+};
+use diem_sdk::crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    PrivateKey, ValidCryptoMaterialStringExt,
+};
+use diem_types::transaction::TransactionPayload;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Subcommand)]
+pub enum MultiSigTxs {
+    /// Print this participant's public key, to share with the other participants
+    PublicKey,
+    /// Create a K-of-N policy from the participants' public keys
+    Policy {
+        #[clap(short, long)]
+        /// How many participants must sign
+        threshold: u8,
+        #[clap(short, long)]
+        /// Public key of each participant, in a fixed order
+        public_key: Vec<String>,
+        #[clap(short, long)]
+        /// File to write the policy to
+        out: PathBuf,
+    },
+    /// Propose rotating the signer's account to the policy's multi-ed25519 key
+    ProposeRotation {
+        #[clap(short, long)]
+        policy: PathBuf,
+        #[clap(short, long)]
+        /// File to write the proposal to
+        out: PathBuf,
+    },
+    /// Propose a transaction from the multi-ed25519 account
+    Propose {
+        #[clap(short, long)]
+        policy: PathBuf,
+        #[clap(short, long)]
+        /// Function identifier, <ADDRESS>::<MODULE_ID>::<FUNCTION_NAME>
+        function_id: String,
+        #[clap(short('y'), long)]
+        /// Type arguments separated by commas
+        type_args: Option<String>,
+        #[clap(short, long)]
+        /// Function arguments separated by commas
+        args: Option<String>,
+        #[clap(short, long)]
+        /// File to write the proposal to
+        out: PathBuf,
+    },
+    /// Add this participant's signature to a proposal file
+    Sign {
+        #[clap(short, long)]
+        file: PathBuf,
+    },
+    /// Combine the signatures in a proposal file and submit it
+    Submit {
+        #[clap(short, long)]
+        file: PathBuf,
+    },
+}
+
+/// Creates a K-of-N policy from the participants' public keys. No private
+/// key is needed.
+pub fn save_policy(threshold: u8, public_key: &[String], out: &Path) -> anyhow::Result<()> {
+    let keys = public_key
+        .iter()
+        .map(|k| Ed25519PublicKey::from_encoded_string(k))
+        .collect::<Result<Vec<_>, _>>()?;
+    let policy = MultiSigPolicy::new(threshold, keys)?;
+    policy.save(out)?;
+    let auth_key = policy.auth_key()?;
+    println!(
+        "{}-of-{} policy saved to {}",
+        threshold,
+        public_key.len(),
+        out.display()
+    );
+    println!("authentication key: {auth_key}");
+    println!("address of a new account: {}", auth_key.derived_address());
+    Ok(())
+}
+
+impl MultiSigTxs {
+    /// Policies and signatures don't need a node
+    pub fn is_offline(&self) -> bool {
+        matches!(
+            self,
+            MultiSigTxs::PublicKey | MultiSigTxs::Policy { .. } | MultiSigTxs::Sign { .. }
+        )
+    }
+
+    pub fn run_offline(&self, key: &Ed25519PrivateKey) -> anyhow::Result<()> {
+        match self {
+            MultiSigTxs::PublicKey => {
+                println!("{}", key.public_key().to_encoded_string()?);
+            }
+            MultiSigTxs::Policy {
+                threshold,
+                public_key,
+                out,
+            } => save_policy(*threshold, public_key, out)?,
+            MultiSigTxs::Sign { file } => {
+                let mut proposal = MultiSigFile::load(file)?;
+                println!("signing proposal for account {}", proposal.account()?);
+                let index = proposal.sign(key)?;
+                proposal.save(file)?;
+                println!(
+                    "signed as participant {index}, {} of {} signatures collected",
+                    proposal.signatures.len(),
+                    proposal.policy.threshold
+                );
+            }
+            _ => anyhow::bail!("this subcommand needs a connection to a node"),
+        }
+        Ok(())
+    }
+
+    pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+        match self {
+            MultiSigTxs::ProposeRotation { policy, out } => {
+                let policy = MultiSigPolicy::load(policy)?;
+                let proposal = MultiSigFile::propose_rotation(sender, policy).await?;
+                proposal.save(out)?;
+                println!(
+                    "rotation proposal saved to {}, participants now sign it",
+                    out.display()
+                );
+            }
+            MultiSigTxs::Propose {
+                policy,
+                function_id,
+                type_args,
+                args,
+                out,
+            } => {
+                let policy = MultiSigPolicy::load(policy)?;
+                let payload = TransactionPayload::EntryFunction(build_entry_function(
+                    function_id,
+                    type_args,
+                    args,
+                )?);
+                let proposal = MultiSigFile::propose_transaction(sender, policy, payload).await?;
+                proposal.save(out)?;
+                println!(
+                    "proposal saved to {}, participants now sign it",
+                    out.display()
+                );
+            }
+            MultiSigTxs::Submit { file } => {
+                let proposal = MultiSigFile::load(file)?;
+                let res = proposal.submit(sender).await?;
+                println!(
+                    "SUCCESS: submitted for account {}, tx hash: {}",
+                    proposal.account()?,
+                    res.info.transaction_hash()
+                );
+            }
+            _ => return self.run_offline(sender.local_account.private_key()),
+        }
+        Ok(())
+    }
+}
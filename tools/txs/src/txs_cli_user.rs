@@ -1,6 +1,7 @@
 //! Validator subcommands
 
 use crate::submit_transaction::Sender;
+use anyhow::Context;
 use dialoguer::Confirm;
 use diem::common::types::RotationProofChallenge;
 use diem_sdk::{
@@ -12,13 +13,18 @@ use diem_types::{
     transaction::TransactionPayload,
 };
 use libra_cached_packages::libra_stdlib;
-use libra_query;
 use libra_types::{
+    core_types::app_cfg::{AppCfg, Profile},
     exports::{AuthenticationKey, Ed25519PrivateKey},
-    type_extensions::client_ext::ClientExt,
+    type_extensions::client_ext::{is_api_error, ClientExt},
+};
+use libra_wallet::{
+    account_keys::get_keys_from_prompt,
+    keystore::{keystore_dir, prompt_passphrase, KdfParams, KeystoreFile},
+    recovery::{recovery_dir, RecoveryBundle},
 };
-use libra_wallet::account_keys::get_keys_from_prompt;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 #[derive(clap::Subcommand)]
 pub enum UserTxs {
@@ -30,14 +36,16 @@ pub enum UserTxs {
 }
 
 impl UserTxs {
-    pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+    pub async fn run(&self, sender: &mut Sender, app_cfg: &mut AppCfg) -> anyhow::Result<()> {
         match &self {
-            UserTxs::RotateKey(rotate) => match rotate.run(sender, true).await {
-                Ok(_) => println!("SUCCESS: private key rotated"),
-                Err(e) => {
-                    println!("ERROR: could not rotate private key, message: {}", e);
-                }
-            },
+            UserTxs::RotateKey(rotate) => {
+                let rotated = rotate
+                    .run(sender, true)
+                    .await
+                    .context("could not rotate private key")?;
+                println!("SUCCESS: private key rotated");
+                after_rotation(&rotated, app_cfg)?;
+            }
             UserTxs::SetSlow(slow) => match slow.run(sender).await {
                 Ok(_) => println!("SUCCESS: account set to Slow Wallet"),
                 Err(e) => {
@@ -48,12 +56,11 @@ impl UserTxs {
                 }
             },
             UserTxs::RotationCapability(offer_rotation_capability) => {
-                match offer_rotation_capability.run(sender).await {
-                    Ok(_) => println!("SUCCESS: offered rotation capability"),
-                    Err(e) => {
-                        println!("ERROR: could not offer rotation capability, message: {}", e);
-                    }
-                }
+                offer_rotation_capability
+                    .run(sender)
+                    .await
+                    .context("could not offer rotation capability")?;
+                println!("SUCCESS: offered rotation capability");
             }
             UserTxs::ReJoin => {
                 println!("Migrating v7 account...");
@@ -69,6 +76,45 @@ impl UserTxs {
     }
 }
 
+/// Writes the recovery bundle and updates the config after a rotation.
+/// The key is printed if the bundle can't be written, so it is never lost.
+fn after_rotation(rotated: &RotatedKey, app_cfg: &mut AppCfg) -> anyhow::Result<()> {
+    println!("\nEncrypting the new private key to a recovery bundle");
+    let saved = prompt_passphrase(true)
+        .and_then(|p| rotated.save_recovery_bundle(&app_cfg.workspace.node_home, &p));
+    match saved {
+        Ok(path) => println!("recovery bundle saved to {}", path.display()),
+        Err(e) => {
+            println!("ERROR: could not save the recovery bundle, message: {e}");
+            println!(
+                "Store the new private key now: {}",
+                rotated.new_private_key.to_encoded_string()?
+            );
+        }
+    }
+
+    rotated.update_profile(app_cfg)?;
+    let path = app_cfg.save_file()?;
+    println!(
+        "profile for {} updated in {}",
+        rotated.account,
+        path.display()
+    );
+
+    let ks_dir = keystore_dir(&app_cfg.workspace.node_home);
+    if let Some(p) = app_cfg
+        .user_profiles
+        .iter()
+        .find(|p| p.account == rotated.account && KeystoreFile::exists(&ks_dir, &p.nickname))
+    {
+        println!(
+            "WARN: the keystore of profile {} holds the old key, run `libra wallet keystore add` to replace it",
+            p.nickname
+        );
+    }
+    Ok(())
+}
+
 /// Sets the account sending the tx to a Slow Wallet.
 /// WARNING: this is not reversible!
 #[derive(clap::Args)]
@@ -94,10 +140,60 @@ pub struct RotateKeyTx {
     #[clap(short, long)]
     /// Account being claimed, if being transferred
     pub claim_address: Option<AccountAddress>,
+    #[clap(short, long)]
+    /// Authentication key the new private key must derive, checked before submitting
+    pub expected_auth_key: Option<AuthenticationKey>,
+}
+
+/// The outcome of a successful rotation
+pub struct RotatedKey {
+    /// the account whose key was rotated
+    pub account: AccountAddress,
+    pub old_auth_key: AuthenticationKey,
+    pub new_auth_key: AuthenticationKey,
+    pub new_private_key: Ed25519PrivateKey,
+}
+
+impl RotatedKey {
+    /// Encrypts the new key to `<node_home>/recovery/`, returns the file path.
+    pub fn save_recovery_bundle(
+        &self,
+        node_home: &Path,
+        passphrase: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let bundle = RecoveryBundle::new(
+            self.account,
+            self.old_auth_key,
+            &self.new_private_key,
+            passphrase,
+            KdfParams::default(),
+        )?;
+        bundle.save(&recovery_dir(node_home))
+    }
+
+    /// Points the profile of the rotated account at the new authentication
+    /// key, or adds a profile for a claimed account. Does not save the file.
+    pub fn update_profile(&self, app_cfg: &mut AppCfg) -> anyhow::Result<()> {
+        let maybe_profile = app_cfg
+            .user_profiles
+            .iter_mut()
+            .find(|p| p.account == self.account);
+
+        if let Some(p) = maybe_profile {
+            p.auth_key = self.new_auth_key;
+            // a test key in the config would now be the wrong one
+            if p.borrow_private_key().is_ok() {
+                p.set_private_key(&self.new_private_key);
+            }
+        } else {
+            app_cfg.maybe_add_profile(Profile::new(self.new_auth_key, self.account))?;
+        }
+        Ok(())
+    }
 }
 
 impl RotateKeyTx {
-    pub async fn run(&self, sender: &mut Sender, confirm: bool) -> anyhow::Result<()> {
+    pub async fn run(&self, sender: &mut Sender, confirm: bool) -> anyhow::Result<RotatedKey> {
         println!("\nWARN: you will be rotating the keys for an account, this could permanently lock you out!");
 
         let sender_account = sender.local_account.address();
@@ -130,9 +226,10 @@ impl RotateKeyTx {
             let legacy = get_keys_from_prompt()?;
             legacy.child_0_owner.pri_key
         };
+        let new_auth_key = AuthenticationKey::ed25519(&new_private_key.public_key());
 
         let seq = sender.client().get_sequence_number(sender_account).await?;
-        let payload = if let Some(target_account_address) = self.claim_address {
+        let (old_auth_key, payload) = if let Some(target_account_address) = self.claim_address {
             let target_account = sender
                 .client()
                 .get_account(target_account_address)
@@ -140,22 +237,27 @@ impl RotateKeyTx {
                 .into_inner();
 
             // rotate key for account_address
-            rotate_key_delegated(
+            let payload = rotate_key_delegated(
                 seq,
                 &target_account_address, // account which is being claimed
                 &target_account.authentication_key, // the on chain authkey of the account being claimed (old)
                 &new_private_key, // the private key with which new auth key will be generated
-            )
+            )?;
+            (target_account.authentication_key, payload)
         } else {
             // rotate key for self
-            rotate_key(
+            let payload = rotate_key(
                 sender_account,
                 sender.local_account.private_key().to_owned(),
                 sender.local_account.authentication_key(),
                 seq,
                 &new_private_key,
-            )
-        }?;
+            )?;
+            (sender.local_account.authentication_key(), payload)
+        };
+
+        self.preflight(sender, rotating_account, new_auth_key, payload.clone())
+            .await?;
 
         if confirm {
             let msg = format!("\nYou are claiming account: {}\nThe new private key will be: {}\nThis will permanent, you will get no other confirmation! Do you wish to continue? ", rotating_account, &new_private_key.to_encoded_string().unwrap());
@@ -166,6 +268,81 @@ impl RotateKeyTx {
         }
 
         sender.sign_submit_wait(payload).await?;
+
+        // the rotation went through, so only warn from here on
+        match sender
+            .client()
+            .lookup_originating_address(new_auth_key)
+            .await
+        {
+            Ok(a) if a == rotating_account => {}
+            Ok(a) => {
+                println!("WARN: the new authentication key resolves to {a}, not {rotating_account}")
+            }
+            Err(e) => println!("WARN: could not check the new authentication key on chain: {e}"),
+        }
+
+        Ok(RotatedKey {
+            account: rotating_account,
+            old_auth_key,
+            new_auth_key,
+            new_private_key,
+        })
+    }
+
+    /// Checks before anything is submitted: the new key derives the expected
+    /// authentication key, the key does not already belong to another account,
+    /// and the rotation succeeds in simulation.
+    async fn preflight(
+        &self,
+        sender: &mut Sender,
+        rotating_account: AccountAddress,
+        new_auth_key: AuthenticationKey,
+        payload: TransactionPayload,
+    ) -> anyhow::Result<()> {
+        println!("\nnew authentication key: {new_auth_key}");
+        if let Some(expected) = self.expected_auth_key {
+            if expected != new_auth_key {
+                anyhow::bail!("the new private key derives authentication key {new_auth_key}, not the expected {expected}");
+            }
+        }
+
+        // the lookup aborts for an unmapped key, which resolves to its own
+        // derived address
+        let originating = match sender
+            .client()
+            .lookup_originating_address(new_auth_key)
+            .await
+        {
+            Ok(a) => a,
+            Err(e) if is_api_error(&e) => new_auth_key.derived_address(),
+            Err(e) => return Err(e),
+        };
+        if originating != rotating_account {
+            let derived = new_auth_key.derived_address();
+            if originating != derived {
+                anyhow::bail!("the new key is already mapped to account {originating}, after rotating it would not resolve to {rotating_account}");
+            }
+            let res = sender
+                .client()
+                .view_ext("0x1::account::exists_at", None, Some(derived.to_string()))
+                .await?;
+            let (exists,): (bool,) = serde_json::from_value(res)?;
+            if exists {
+                anyhow::bail!(
+                    "the new key is the original key of account {derived}, use a fresh key"
+                );
+            }
+        }
+
+        let sim = sender.estimate(payload).await?;
+        let Some(tx) = sim.first() else {
+            anyhow::bail!("no result from simulating the rotation");
+        };
+        if !tx.info.success {
+            anyhow::bail!("the rotation fails in simulation: {}", tx.info.vm_status);
+        }
+        println!("pre-flight checks passed");
         Ok(())
     }
 }
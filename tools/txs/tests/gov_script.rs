@@ -1,6 +1,6 @@
 use std::{path::PathBuf, str::FromStr};
 
-use libra_query::query_view;
+use libra_query::{governance_queries, query_view};
 use libra_smoke_tests::{configure_validator, libra_smoke::LibraSmoke};
 use libra_txs::{
     txs_cli::{TxsCli, TxsSub::Governance},
@@ -94,6 +94,17 @@ async fn smoke_gov_script() {
     .await
     .unwrap();
 
+    // the proposal inspector sees the vote and the script we compiled
+    let proposal = governance_queries::get_proposal(&s.client(), 0).await.unwrap();
+    assert!(proposal.yes_votes > 0);
+    assert_eq!(
+        proposal.metadata_location.as_deref(),
+        Some("http://allyourbase.com")
+    );
+    let check =
+        governance_queries::check_proposal_script(&script_dir, &proposal.execution_hash).unwrap();
+    assert!(check.matches_onchain);
+
     // Now try to resolve upgrade
     cli.subcommand = Some(Governance(Resolve {
         proposal_id: 0,
@@ -1,10 +1,12 @@
-use diem_sdk::crypto::{ed25519::Ed25519PrivateKey, Uniform, ValidCryptoMaterialStringExt};
+use diem_sdk::crypto::{
+    ed25519::Ed25519PrivateKey, PrivateKey, Uniform, ValidCryptoMaterialStringExt,
+};
 use libra_smoke_tests::libra_smoke::LibraSmoke;
 use libra_txs::{
     submit_transaction::Sender,
     txs_cli_user::{RotateKeyTx, RotationCapabilityTx},
 };
-use libra_types::core_types::app_cfg::Profile;
+use libra_types::{core_types::app_cfg::Profile, exports::AuthenticationKey};
 use libra_wallet::account_keys;
 
 // Scenario: We have an initial validator, Val 0 with a random address
@@ -67,13 +69,30 @@ async fn rotate_key() -> anyhow::Result<()> {
         Ed25519PrivateKey::to_encoded_string(&generated_private_key);
     assert!(generated_private_key_encoded.is_ok());
 
+    let generated_private_key_encoded = generated_private_key_encoded.unwrap();
+    let generated_auth_key = AuthenticationKey::ed25519(&generated_private_key.public_key());
+
+    // a key which does not derive the expected auth key is refused before submitting
     let cli = RotateKeyTx {
-        new_private_key: Some(generated_private_key_encoded.unwrap()),
+        new_private_key: Some(generated_private_key_encoded.clone()),
         claim_address: None,
+        expected_auth_key: Some(alice.child_0_owner.auth_key),
     };
+    assert!(cli.run(&mut alice_sender, false).await.is_err());
 
-    let res = cli.run(&mut alice_sender, false).await;
-    assert!(res.is_ok());
+    let cli = RotateKeyTx {
+        new_private_key: Some(generated_private_key_encoded),
+        claim_address: None,
+        expected_auth_key: Some(generated_auth_key),
+    };
+
+    let rotated = cli.run(&mut alice_sender, false).await?;
+    assert_eq!(rotated.new_auth_key, generated_auth_key);
+
+    // the profile now points at the new key
+    rotated.update_profile(&mut val_app_cfg)?;
+    let p = val_app_cfg.get_profile(Some(alice_acct.to_string()))?;
+    assert_eq!(p.auth_key, generated_auth_key);
 
     // check new auth key
     let updated_account_query = ls.client().get_account(alice.child_0_owner.account).await;
@@ -166,6 +185,7 @@ async fn offer_rotation_capability() -> anyhow::Result<()> {
     let cli = RotateKeyTx {
         new_private_key: Some(generated_private_key_encoded.unwrap()),
         claim_address: Some(alice_acct.to_owned()),
+        expected_auth_key: None,
     };
 
     let res_rotation = cli.run(&mut bob_sender, false).await;
@@ -282,6 +302,7 @@ async fn revoke_rotation_capability() -> anyhow::Result<()> {
     let cli = RotateKeyTx {
         new_private_key: Some(generated_private_key_encoded.unwrap()),
         claim_address: Some(alice_acct.to_owned()),
+        expected_auth_key: None,
     };
 
     let res_rotation = cli.run(&mut bob_sender, false).await;
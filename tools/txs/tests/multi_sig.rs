@@ -0,0 +1,104 @@
+use diem_sdk::crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    Uniform, ValidCryptoMaterialStringExt,
+};
+use libra_cached_packages::libra_stdlib;
+use libra_smoke_tests::libra_smoke::LibraSmoke;
+use libra_txs::{
+    multi_sig::{MultiSigFile, MultiSigPolicy},
+    submit_transaction::Sender,
+    txs_cli::{TxsCli, TxsSub},
+    txs_cli_multi_sig::MultiSigTxs,
+};
+use libra_types::core_types::app_cfg::Profile;
+use libra_wallet::account_keys;
+
+// Scenario: Alice's account is handed to a co-op of three, any two of which
+// must sign. Alice rotates to a 2-of-3 multi-ed25519 key, then the co-op
+// sends a transfer from the account.
+
+/// Test rotating to a multi-ed25519 key and sending a threshold signed transaction
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn rotate_to_multi_sig_and_transfer() -> anyhow::Result<()> {
+    let mut ls = LibraSmoke::new(Some(1), None)
+        .await
+        .expect("could not start libra smoke");
+    let mut val_app_cfg = ls.first_account_app_cfg()?;
+
+    let alice = account_keys::get_keys_from_mnem("talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse".to_owned())?;
+    let alice_acct = alice.child_0_owner.account;
+
+    // create an account for alice by transferring funds
+    let mut val_sender = Sender::from_app_cfg(&val_app_cfg, None).await?;
+    val_sender.transfer(alice_acct, 100.0, false).await?;
+
+    let mut p = Profile::new(alice.child_0_owner.auth_key, alice_acct);
+    p.set_private_key(&alice.child_0_owner.pri_key);
+    val_app_cfg.maybe_add_profile(p)?;
+    let mut alice_sender = Sender::from_app_cfg(&val_app_cfg, Some(alice_acct.to_string())).await?;
+
+    let participants: Vec<Ed25519PrivateKey> = (0..3)
+        .map(|_| Ed25519PrivateKey::generate_for_testing())
+        .collect();
+    let policy = MultiSigPolicy::new(2, participants.iter().map(|k| k.into()).collect())?;
+
+    // two participants sign the rotation, alice submits it
+    let mut rotation = MultiSigFile::propose_rotation(&alice_sender, policy.clone()).await?;
+    rotation.sign(&participants[0])?;
+    assert!(rotation.combine().is_err());
+    rotation.sign(&participants[2])?;
+    rotation.submit(&mut alice_sender).await?;
+
+    let account = ls.client().get_account(alice_acct).await?.into_inner();
+    assert_eq!(account.authentication_key, policy.auth_key()?);
+
+    // a transfer from the rotated account, proposed and submitted by the validator
+    let payload = libra_stdlib::ol_account_transfer(val_sender.local_account.address(), 1_000);
+    let mut proposal = MultiSigFile::propose_transaction(&val_sender, policy, payload).await?;
+    assert_eq!(proposal.account()?, alice_acct);
+
+    // an outsider can't sign
+    assert!(proposal
+        .sign(&Ed25519PrivateKey::generate_for_testing())
+        .is_err());
+
+    proposal.sign(&participants[1])?;
+    proposal.sign(&participants[2])?;
+    let res = proposal.submit(&mut val_sender).await?;
+    assert!(res.info.status().is_success());
+
+    Ok(())
+}
+
+/// A policy is made from public keys alone, without a config or a private key
+#[tokio::test]
+async fn policy_needs_no_key() -> anyhow::Result<()> {
+    let d = diem_temppath::TempPath::new();
+    d.create_as_dir()?;
+    let out = d.path().join("policy.json");
+
+    let participants: Vec<Ed25519PublicKey> = (0..3)
+        .map(|_| (&Ed25519PrivateKey::generate_for_testing()).into())
+        .collect();
+    let cli = TxsCli {
+        subcommand: Some(TxsSub::MultiSig(MultiSigTxs::Policy {
+            threshold: 2,
+            public_key: participants
+                .iter()
+                .map(|k| k.to_encoded_string())
+                .collect::<Result<_, _>>()?,
+            out: out.clone(),
+        })),
+        // there is no config here, so reading it would fail
+        config_path: Some(d.path().join("libra-cli-config.yaml")),
+        ..Default::default()
+    };
+    cli.run().await?;
+
+    let policy = MultiSigPolicy::load(&out)?;
+    assert_eq!(
+        policy.auth_key()?,
+        MultiSigPolicy::new(2, participants)?.auth_key()?
+    );
+    Ok(())
+}
@@ -1,6 +1,7 @@
+use libra_cached_packages::libra_framework_sdk_builder::EntryFunctionCall::OlAccountTransfer;
 use libra_smoke_tests::libra_smoke::LibraSmoke;
 use libra_txs::submit_transaction::Sender;
-use libra_types::core_types::app_cfg::Profile;
+use libra_types::{core_types::app_cfg::Profile, move_resource::gas_coin};
 use libra_wallet::account_keys;
 
 // Scenario: We have an initial validator, Val 0 with a random address
@@ -53,3 +54,35 @@ async fn sender_back_and_forth() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// An estimate simulates the transaction without spending the sequence
+/// number, so the next real transaction still goes through
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn estimate_keeps_sequence_number() -> anyhow::Result<()> {
+    let mut ls = LibraSmoke::new(Some(1), None)
+        .await
+        .expect("could not start libra smoke");
+    let val_app_cfg = ls.first_account_app_cfg()?;
+    let alice = account_keys::get_keys_from_mnem("talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse".to_owned())?;
+
+    let mut s = Sender::from_app_cfg(&val_app_cfg, None).await?;
+    let sequence_number = s.local_account.sequence_number();
+    let payload = OlAccountTransfer {
+        to: alice.child_0_owner.account,
+        amount: gas_coin::cast_decimal_to_coin(100.0),
+    }
+    .encode();
+
+    let res = s.estimate(payload).await?;
+    assert_eq!(res.len(), 1);
+    assert!(res[0].info.success, "{}", res[0].info.vm_status);
+    assert!(res[0].info.gas_used > 0);
+    assert_eq!(s.local_account.sequence_number(), sequence_number);
+
+    let res = s
+        .transfer(alice.child_0_owner.account, 100.0, false)
+        .await?
+        .unwrap();
+    assert!(res.info.status().is_success());
+    Ok(())
+}
@@ -1,10 +1,14 @@
 use libra_smoke_tests::{configure_validator, helpers::get_libra_balance, libra_smoke::LibraSmoke};
 use libra_txs::{
     submit_transaction::Sender,
-    txs_cli::{to_legacy_address, TxsCli, TxsSub::Transfer},
+    transfer::done_transfers,
+    txs_cli::{
+        to_legacy_address, TxsCli,
+        TxsSub::{BatchTransfer, Transfer},
+    },
 };
 use libra_types::core_types::app_cfg::TxCost;
-use libra_wallet::account_keys;
+use libra_wallet::{account_keys, bulk_keygen};
 
 // Testing that we can send the minimal transaction: a transfer from one existing validator to another.
 // Case 1: send to an existing account: another genesis validator
@@ -97,6 +101,112 @@ async fn smoke_transfer_create_account() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Fund a manifest of accounts from bulk-keygen, creating each on chain
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn smoke_batch_transfer_manifest() -> anyhow::Result<()> {
+    let d = diem_temppath::TempPath::new();
+
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start libra smoke");
+
+    let (_, _app_cfg) =
+        configure_validator::init_val_config_files(&mut s.swarm, 0, Some(d.path().to_owned()))
+            .expect("could not init validator config");
+
+    let accounts = bulk_keygen::bulk_keygen(3, None)?;
+    let manifest = d.path().join("accounts.csv");
+    bulk_keygen::write_manifest(&manifest, &accounts)?;
+
+    let progress = d.path().join("accounts.progress");
+    let cli = TxsCli {
+        subcommand: Some(BatchTransfer {
+            manifest,
+            amount: 1.0,
+            progress: Some(progress.clone()),
+        }),
+        mnemonic: None,
+        test_private_key: Some(s.encoded_pri_key.clone()),
+        chain_name: None,
+        config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
+        url: Some(s.api_endpoint.clone()),
+        tx_profile: None,
+        tx_cost: Some(TxCost::default_baseline_cost()),
+        estimate_only: false,
+        legacy_address: false,
+    };
+
+    cli.run().await.expect("could not fund the manifest");
+    let done = done_transfers(&progress)?;
+    assert_eq!(done.len(), 3, "each transfer should be logged");
+
+    // a second run resumes from the log, and funds nothing again
+    cli.run().await.expect("could not resume the manifest");
+
+    let client = s.client();
+    for a in accounts {
+        let bal = get_libra_balance(&client, a.account).await?;
+        assert_eq!(bal.total, 1000000, "each account should have 1.0(1000000)");
+    }
+    Ok(())
+}
+
+/// A transfer which committed after its wait failed is not sent again on resume
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn smoke_batch_transfer_resume_pending() -> anyhow::Result<()> {
+    let d = diem_temppath::TempPath::new();
+
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start libra smoke");
+
+    let (_, app_cfg) =
+        configure_validator::init_val_config_files(&mut s.swarm, 0, Some(d.path().to_owned()))
+            .expect("could not init validator config");
+
+    let accounts = bulk_keygen::bulk_keygen(1, None)?;
+    let manifest = d.path().join("accounts.csv");
+    bulk_keygen::write_manifest(&manifest, &accounts)?;
+    let recipient = accounts[0].account;
+
+    // the transfer commits, but the log only has it as pending and failed
+    let mut send = Sender::from_app_cfg(&app_cfg, None).await?;
+    let tx = send.transfer(recipient, 1.0, false).await?.unwrap();
+    let progress = d.path().join("accounts.progress");
+    std::fs::write(
+        &progress,
+        format!(
+            "{0} pending {1}\n{0} failed timed out\n",
+            recipient.to_hex_literal(),
+            tx.info.transaction_hash().to_hex_literal()
+        ),
+    )?;
+    assert!(done_transfers(&progress)?.is_empty());
+
+    let cli = TxsCli {
+        subcommand: Some(BatchTransfer {
+            manifest,
+            amount: 1.0,
+            progress: Some(progress.clone()),
+        }),
+        mnemonic: None,
+        test_private_key: Some(s.encoded_pri_key.clone()),
+        chain_name: None,
+        config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
+        url: Some(s.api_endpoint.clone()),
+        tx_profile: None,
+        tx_cost: Some(TxCost::default_baseline_cost()),
+        estimate_only: false,
+        legacy_address: false,
+    };
+    cli.run().await.expect("could not resume the manifest");
+
+    assert!(done_transfers(&progress)?.contains(&recipient));
+    let bal = get_libra_balance(&s.client(), recipient).await?;
+    assert_eq!(bal.total, 1000000, "the account should be funded once");
+    Ok(())
+}
+
 /// Estimate only. Esitmates will fail if the coin name is not set in the diem-node compiled binary.
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 // #[ignore] // TODO: ignore this test until diem-platform 1.6.3 final lands
@@ -0,0 +1,196 @@
+//! Generate many accounts at once, for load tests, testnets and airdrop
+//! planning. Accounts are generated in parallel, optionally constrained to
+//! an address prefix, and written to a JSON or CSV manifest.
+
+use crate::{
+    key_gen::keygen,
+    keystore::{KdfParams, Sealed},
+    utils::write_to_user_only_file,
+};
+use anyhow::{bail, Context};
+use libra_types::exports::{AccountAddress, AuthenticationKey};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, str::FromStr};
+
+/// Each hex character of a prefix makes generation 16 times slower, and
+/// every attempt derives a fresh mnemonic seed, so only short prefixes are
+/// practical
+pub const MAX_PREFIX_LEN: usize = 4;
+
+/// One generated account in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkAccount {
+    pub account: AccountAddress,
+    pub auth_key: AuthenticationKey,
+    /// plaintext mnemonic, when the manifest is not encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    /// mnemonic encrypted with the manifest passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_mnemonic: Option<Sealed>,
+}
+
+impl BulkAccount {
+    /// The mnemonic, decrypting it if needed
+    pub fn get_mnemonic(&self, passphrase: Option<&str>) -> anyhow::Result<String> {
+        match (&self.mnemonic, &self.encrypted_mnemonic, passphrase) {
+            (Some(m), _, _) => Ok(m.to_owned()),
+            (None, Some(sealed), Some(p)) => Ok(String::from_utf8(sealed.open(p)?)?),
+            (None, Some(_), None) => bail!("mnemonic is encrypted, a passphrase is needed"),
+            (None, None, _) => bail!("no mnemonic for account {}", self.account),
+        }
+    }
+}
+
+/// A flat row, since CSV can't hold the nested encrypted mnemonic
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    account: String,
+    auth_key: String,
+    /// the plaintext mnemonic, or the encrypted mnemonic as JSON
+    mnemonic: String,
+    encrypted: bool,
+}
+
+/// Generates `count` accounts in parallel. With a prefix, keeps generating
+/// until each address starts with those hex characters.
+pub fn bulk_keygen(count: usize, prefix: Option<&str>) -> anyhow::Result<Vec<BulkAccount>> {
+    let prefix = prefix
+        .map(|p| p.trim_start_matches("0x").to_lowercase())
+        .unwrap_or_default();
+    if prefix.len() > MAX_PREFIX_LEN {
+        bail!("address prefix can be at most {MAX_PREFIX_LEN} characters");
+    }
+    if !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("address prefix must be hex");
+    }
+
+    let accounts = (0..count)
+        .into_par_iter()
+        .map(|_| loop {
+            let (auth_key, account, _, mnemonic) = keygen();
+            if account.to_hex().starts_with(&prefix) {
+                break BulkAccount {
+                    account,
+                    auth_key,
+                    mnemonic: Some(mnemonic),
+                    encrypted_mnemonic: None,
+                };
+            }
+        })
+        .collect();
+    Ok(accounts)
+}
+
+/// Replaces the plaintext mnemonics with ones encrypted under the passphrase.
+pub fn encrypt_mnemonics(accounts: &mut [BulkAccount], passphrase: &str) -> anyhow::Result<()> {
+    // a cheaper scrypt than the keystore, there may be thousands of these
+    let kdf = KdfParams {
+        log_n: 14,
+        ..Default::default()
+    };
+    accounts
+        .par_iter_mut()
+        .try_for_each(|a| -> anyhow::Result<()> {
+            if let Some(m) = a.mnemonic.take() {
+                a.encrypted_mnemonic = Some(Sealed::seal(m.as_bytes(), passphrase, kdf)?);
+            }
+            Ok(())
+        })
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("csv")
+}
+
+/// Writes the manifest as CSV if the file ends in `.csv`, otherwise as JSON.
+/// The file is only readable by the user since it may hold mnemonics.
+pub fn write_manifest(path: &Path, accounts: &[BulkAccount]) -> anyhow::Result<()> {
+    let bytes = if is_csv(path) {
+        let mut w = csv::Writer::from_writer(vec![]);
+        for a in accounts {
+            let mnemonic = match (&a.mnemonic, &a.encrypted_mnemonic) {
+                (Some(m), _) => m.to_owned(),
+                (None, Some(sealed)) => serde_json::to_string(sealed)?,
+                (None, None) => String::new(),
+            };
+            w.serialize(CsvRow {
+                account: a.account.to_hex_literal(),
+                auth_key: a.auth_key.to_string(),
+                mnemonic,
+                encrypted: a.encrypted_mnemonic.is_some(),
+            })?;
+        }
+        w.into_inner()?
+    } else {
+        serde_json::to_vec_pretty(accounts)?
+    };
+    write_to_user_only_file(path, "accounts manifest", &bytes)
+}
+
+/// Reads a manifest written by `write_manifest`.
+pub fn read_manifest(path: &Path) -> anyhow::Result<Vec<BulkAccount>> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("cannot read manifest at {}", path.display()))?;
+    if !is_csv(path) {
+        return Ok(serde_json::from_slice(&data)?);
+    }
+
+    let mut r = csv::Reader::from_reader(data.as_slice());
+    r.deserialize::<CsvRow>()
+        .map(|row| -> anyhow::Result<BulkAccount> {
+            let row = row?;
+            let (mnemonic, encrypted_mnemonic) = match (row.encrypted, row.mnemonic.is_empty()) {
+                (true, _) => (None, Some(serde_json::from_str(&row.mnemonic)?)),
+                (false, true) => (None, None),
+                (false, false) => (Some(row.mnemonic), None),
+            };
+            Ok(BulkAccount {
+                account: AccountAddress::from_hex_literal(&row.account)?,
+                auth_key: AuthenticationKey::from_str(&row.auth_key)?,
+                mnemonic,
+                encrypted_mnemonic,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn bulk_keygen_prefix() {
+    let accounts = bulk_keygen(4, Some("0xa")).unwrap();
+    assert_eq!(accounts.len(), 4);
+    assert!(accounts.iter().all(|a| a.account.to_hex().starts_with('a')));
+
+    // every mnemonic derives the account it is listed with
+    for a in &accounts {
+        let keys = crate::account_keys::get_keys_from_mnem(a.get_mnemonic(None).unwrap()).unwrap();
+        assert_eq!(keys.child_0_owner.account, a.account);
+    }
+
+    assert!(bulk_keygen(1, Some("xyz")).is_err());
+    assert!(bulk_keygen(1, Some("12345")).is_err());
+}
+
+#[test]
+fn bulk_manifest_roundtrip() {
+    let mut accounts = bulk_keygen(3, None).unwrap();
+    let plain = accounts[0].get_mnemonic(None).unwrap();
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+
+    let json = dir.path().join("accounts.json");
+    write_manifest(&json, &accounts).unwrap();
+    let read = read_manifest(&json).unwrap();
+    assert_eq!(read[0].get_mnemonic(None).unwrap(), plain);
+
+    encrypt_mnemonics(&mut accounts, "airdrop").unwrap();
+    assert!(accounts[0].get_mnemonic(None).is_err());
+
+    let csv = dir.path().join("accounts.csv");
+    write_manifest(&csv, &accounts).unwrap();
+    let read = read_manifest(&csv).unwrap();
+    assert_eq!(read.len(), 3);
+    assert_eq!(read[2].account, accounts[2].account);
+    assert_eq!(read[0].get_mnemonic(Some("airdrop")).unwrap(), plain);
+}
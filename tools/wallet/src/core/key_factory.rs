@@ -203,10 +203,18 @@ impl Seed {
     /// particular Mnemonic and salt. WalletLibrary implements a fixed salt, but a user could
     /// choose a user-defined salt instead of the hardcoded one.
     pub fn new(mnemonic: &Mnemonic, salt: &str) -> Seed {
+        Self::new_with_passphrase(mnemonic, salt, "")
+    }
+
+    /// Like BIP-39, an optional passphrase is appended to the salt. The same
+    /// mnemonic with a different passphrase derives unrelated keys, and an
+    /// empty passphrase derives the same keys as `Seed::new`.
+    pub fn new_with_passphrase(mnemonic: &Mnemonic, salt: &str, passphrase: &str) -> Seed {
         let mut output = [0u8; 32];
 
         let mut msalt = KeyFactory::MNEMONIC_SALT_PREFIX.to_vec();
         msalt.extend_from_slice(salt.as_bytes());
+        msalt.extend_from_slice(passphrase.as_bytes());
 
         pbkdf2::<Hmac<Sha3_256>>(mnemonic.to_string().as_ref(), &msalt, 2048, &mut output);
         Seed(output)
@@ -268,3 +276,18 @@ fn test_key_derivation() {
         hex::encode(&child_private_1_from_increment.private_key.to_bytes()[..])
     );
 }
+
+#[cfg(test)]
+#[test]
+fn test_seed_passphrase() {
+    let mnemonic = Mnemonic::from("legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will").unwrap();
+
+    // an empty passphrase keeps the keys of the plain seed
+    let plain = KeyFactory::new(&Seed::new(&mnemonic, "DIEM")).unwrap();
+    let empty = KeyFactory::new(&Seed::new_with_passphrase(&mnemonic, "DIEM", "")).unwrap();
+    assert_eq!(plain.main(), empty.main());
+
+    let with_pass =
+        KeyFactory::new(&Seed::new_with_passphrase(&mnemonic, "DIEM", "hunter2")).unwrap();
+    assert_ne!(plain.main(), with_pass.main());
+}
@@ -105,6 +105,20 @@ impl Mnemonic {
         Ok(Mnemonic(mnemonic))
     }
 
+    /// Recover the entropy the mnemonic encodes, without the checksum.
+    pub fn entropy(&self) -> Vec<u8> {
+        let len = self.0.len();
+        let mut bit_writer = U11BitWriter::new(len);
+        for word in &self.0 {
+            // words were checked against the list when the mnemonic was built
+            let idx = WORDS.binary_search(word).unwrap_or_else(|_| unreachable!());
+            bit_writer.write_u11(idx as u16);
+        }
+        bit_writer.write_buffer();
+        bit_writer.bytes.truncate(len * 4 / 3);
+        bit_writer.bytes
+    }
+
     /// Write mnemonic to output_file_path.
     pub fn write(&self, output_file_path: &Path) -> Result<()> {
         if output_file_path.exists() && !output_file_path.is_file() {
@@ -128,6 +142,34 @@ impl Mnemonic {
     }
 }
 
+/// Encode arbitrary bytes as words from the mnemonic word list, zero padding
+/// the last word. Unlike a mnemonic there is no length or checksum, callers
+/// must add their own.
+pub(crate) fn bytes_to_words(bytes: &[u8]) -> Vec<&'static str> {
+    let word_count = (bytes.len() * 8).div_ceil(11);
+    let mut padded = bytes.to_vec();
+    padded.resize((word_count * 11).div_ceil(8), 0);
+
+    let mut bit_reader = U11BitReader::new(&padded);
+    (0..word_count)
+        .map(|_| WORDS[bit_reader.read_u11() as usize])
+        .collect()
+}
+
+/// Decode words from the mnemonic word list back into bytes. The result may
+/// have trailing zero padding.
+pub(crate) fn words_to_bytes(words: &[&str]) -> Result<Vec<u8>> {
+    let mut bit_writer = U11BitWriter::new(words.len());
+    for word in words {
+        match WORDS.binary_search(word) {
+            Ok(idx) => bit_writer.write_u11(idx as u16),
+            Err(_) => bail!("unknown word: {}", word),
+        }
+    }
+    bit_writer.write_buffer();
+    Ok(bit_writer.bytes)
+}
+
 /// BitReader reads data from a byte slice at the granularity of 11 bits.
 struct U11BitReader<'a> {
     bytes: &'a [u8],
@@ -495,6 +537,26 @@ fn test_entropy_to_word_number_compatibility() {
     }
 }
 
+#[test]
+fn test_entropy_roundtrip() {
+    for size in (16..=32).step_by(4) {
+        let mut entropy = vec![0; size];
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic = Mnemonic::new(&entropy).unwrap();
+        let parsed = Mnemonic::from(&mnemonic.to_string()).unwrap();
+        assert_eq!(parsed.entropy(), entropy);
+    }
+}
+
+#[test]
+fn test_bytes_to_words_roundtrip() {
+    let bytes = b"not a multiple of eleven bits".to_vec();
+    let words = bytes_to_words(&bytes);
+    let decoded = words_to_bytes(&words).unwrap();
+    assert_eq!(&decoded[..bytes.len()], &bytes[..]);
+    assert!(decoded[bytes.len()..].iter().all(|b| *b == 0));
+}
+
 #[test]
 fn test_bips39_vectors() {
     let tests = test_vectors_bip39();
@@ -4,4 +4,5 @@
 pub mod key_factory;
 pub mod legacy_scheme;
 pub mod mnemonic;
+pub mod shamir;
 pub mod wallet_library;
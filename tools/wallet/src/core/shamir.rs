@@ -0,0 +1,245 @@
+//! Shamir secret sharing of mnemonic entropy.
+//!
+//! The entropy is split byte by byte over GF(256), so any `threshold` of the
+//! shares recover it and fewer reveal nothing. Each share is written as
+//! words from the mnemonic word list, in the spirit of SLIP-39:
+//!
+//! +--------+-------+-----------+-------+---------+----------+
+//! | LENGTH | SET   | THRESHOLD | INDEX | SHARE   | CHECKSUM |
+//! +--------+-------+-----------+-------+---------+----------+
+//! | 1 byte | 2     | 1         | 1     | 16..=32 | 2        |
+//! +--------+-------+-----------+-------+---------+----------+
+//!
+//! The set identifier is random, so shares of different splits can't be mixed.
+//! The checksum is the first two bytes of the SHA256 of the fields before it.
+
+use super::mnemonic::{bytes_to_words, words_to_bytes, Mnemonic};
+use anyhow::{bail, Result};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+
+const HEADER_LEN: usize = 4;
+const CHECKSUM_LEN: usize = 2;
+
+/// One share of a split mnemonic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// random identifier common to every share of one split
+    pub set_id: u16,
+    pub threshold: u8,
+    /// x coordinate of the share, never zero
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    /// Encode the share as words, with length prefix and checksum.
+    pub fn to_words(&self) -> String {
+        let mut payload = self.set_id.to_be_bytes().to_vec();
+        payload.push(self.threshold);
+        payload.push(self.index);
+        payload.extend_from_slice(&self.data);
+        let checksum = Sha256::digest(&payload);
+        payload.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+
+        let mut bytes = vec![payload.len() as u8];
+        bytes.extend(payload);
+        bytes_to_words(&bytes).join(" ")
+    }
+
+    /// Parse a share from words, checking its checksum.
+    pub fn from_words(s: &str) -> Result<Share> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let bytes = words_to_bytes(&words)?;
+        let Some((len, rest)) = bytes.split_first() else {
+            bail!("share is empty");
+        };
+        let len = *len as usize;
+        if len < HEADER_LEN + CHECKSUM_LEN + 16 || rest.len() < len {
+            bail!("share has an invalid length");
+        }
+        let (payload, checksum) = rest[..len].split_at(len - CHECKSUM_LEN);
+        if Sha256::digest(payload)[..CHECKSUM_LEN] != *checksum {
+            bail!("share checksum failed, check the words were typed correctly");
+        }
+
+        let share = Share {
+            set_id: u16::from_be_bytes([payload[0], payload[1]]),
+            threshold: payload[2],
+            index: payload[3],
+            data: payload[HEADER_LEN..].to_vec(),
+        };
+        if share.index == 0 || share.threshold == 0 {
+            bail!("share has an invalid index or threshold");
+        }
+        Ok(share)
+    }
+}
+
+/// Splits the entropy of a mnemonic into `count` shares, any `threshold` of
+/// which can recover it.
+pub fn split_mnemonic(mnemonic: &Mnemonic, threshold: u8, count: u8) -> Result<Vec<Share>> {
+    if threshold == 0 || threshold > count {
+        bail!("threshold must be between 1 and the number of shares");
+    }
+    let secret = mnemonic.entropy();
+    let set_id = OsRng.next_u32() as u16;
+
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|index| Share {
+            set_id,
+            threshold,
+            index,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    for byte in secret {
+        // random polynomial of degree threshold - 1 with the secret at x = 0
+        let mut coefficients = vec![0u8; threshold as usize];
+        OsRng.fill_bytes(&mut coefficients);
+        coefficients[0] = byte;
+
+        for share in shares.iter_mut() {
+            share.data.push(eval_polynomial(&coefficients, share.index));
+        }
+    }
+    Ok(shares)
+}
+
+/// Recovers the mnemonic from at least `threshold` shares of the same split.
+pub fn combine_shares(shares: &[Share]) -> Result<Mnemonic> {
+    let Some(first) = shares.first() else {
+        bail!("no shares given");
+    };
+    if shares.iter().any(|s| {
+        s.set_id != first.set_id
+            || s.threshold != first.threshold
+            || s.data.len() != first.data.len()
+    }) {
+        bail!("shares are not from the same split");
+    }
+
+    let indexes: BTreeSet<u8> = shares.iter().map(|s| s.index).collect();
+    if indexes.len() < first.threshold as usize {
+        bail!(
+            "need {} distinct shares, got {}",
+            first.threshold,
+            indexes.len()
+        );
+    }
+
+    // only threshold shares are needed, use the first distinct ones
+    let mut used: Vec<&Share> = vec![];
+    for s in shares {
+        if !used.iter().any(|u| u.index == s.index) {
+            used.push(s);
+        }
+    }
+    used.truncate(first.threshold as usize);
+
+    let secret: Vec<u8> = (0..first.data.len())
+        .map(|i| {
+            let points: Vec<(u8, u8)> = used.iter().map(|s| (s.index, s.data[i])).collect();
+            interpolate_at_zero(&points)
+        })
+        .collect();
+
+    Mnemonic::new(&secret)
+}
+
+fn eval_polynomial(coefficients: &[u8], x: u8) -> u8 {
+    // Horner's method, highest degree first
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |acc, c| gf_mul(acc, x) ^ c)
+}
+
+/// Lagrange interpolation of the polynomial at x = 0
+fn interpolate_at_zero(points: &[(u8, u8)]) -> u8 {
+    let mut result = 0;
+    for (i, (xi, yi)) in points.iter().enumerate() {
+        let mut basis = 1;
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i != j {
+                // in GF(256) subtraction is xor, so (0 - xj) / (xi - xj)
+                basis = gf_mul(basis, gf_mul(*xj, gf_inv(xi ^ xj)));
+            }
+        }
+        result ^= gf_mul(*yi, basis);
+    }
+    result
+}
+
+/// multiplication in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// a^254 is the inverse of a in GF(256)
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = gf_mul(result, a);
+    }
+    result
+}
+
+#[test]
+fn test_gf_inverse() {
+    for a in 1..=255u8 {
+        assert_eq!(gf_mul(a, gf_inv(a)), 1);
+    }
+}
+
+#[test]
+fn test_split_and_combine() {
+    let alice = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let mnemonic = Mnemonic::from(alice).unwrap();
+    let shares = split_mnemonic(&mnemonic, 3, 5).unwrap();
+    assert_eq!(shares.len(), 5);
+
+    // any three shares recover the mnemonic
+    for picked in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+        let subset: Vec<Share> = picked.iter().map(|i| shares[*i].clone()).collect();
+        assert_eq!(combine_shares(&subset).unwrap().to_string(), alice);
+    }
+
+    // two are not enough
+    assert!(combine_shares(&shares[..2]).is_err());
+
+    // shares from another split are refused
+    let mut other = split_mnemonic(&mnemonic, 3, 5).unwrap();
+    other[0].set_id = shares[0].set_id.wrapping_add(1);
+    assert!(combine_shares(&[shares[0].clone(), shares[1].clone(), other[0].clone()]).is_err());
+}
+
+#[test]
+fn test_share_words_roundtrip() {
+    let mnemonic = Mnemonic::new(&[7u8; 16]).unwrap();
+    let shares = split_mnemonic(&mnemonic, 2, 3).unwrap();
+    for s in &shares {
+        let words = s.to_words();
+        assert_eq!(&Share::from_words(&words).unwrap(), s);
+    }
+
+    // a typo fails the checksum
+    let words = shares[0].to_words();
+    let mut typo: Vec<&str> = words.split(' ').collect();
+    typo[5] = if typo[5] == "zoo" { "abandon" } else { "zoo" };
+    assert!(Share::from_words(&typo.join(" ")).is_err());
+}
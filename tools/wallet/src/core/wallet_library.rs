@@ -42,7 +42,12 @@ impl WalletLibrary {
 
     /// Constructor that instantiates a new WalletLibrary from Mnemonic
     pub fn new_from_mnemonic(mnemonic: Mnemonic) -> Self {
-        let seed = Seed::new(&mnemonic, "0L"); //////// 0L ////////
+        Self::new_from_mnemonic_with_passphrase(mnemonic, "")
+    }
+
+    /// Constructor from a Mnemonic and an optional BIP-39 style passphrase
+    pub fn new_from_mnemonic_with_passphrase(mnemonic: Mnemonic, passphrase: &str) -> Self {
+        let seed = Seed::new_with_passphrase(&mnemonic, "0L", passphrase); //////// 0L ////////
         WalletLibrary {
             mnemonic,
             key_factory: KeyFactory::new(&seed).unwrap(),
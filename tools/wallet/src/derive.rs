@@ -0,0 +1,108 @@
+//! Derive additional owner accounts from one mnemonic.
+//! Children 0-5 are reserved for the owner and validator keys of the
+//! legacy scheme, so extra accounts start at child 6.
+
+use crate::{
+    account_keys::{get_account_from_private, AccountKeys},
+    core::{key_factory::ChildNumber, mnemonic::Mnemonic, wallet_library::WalletLibrary},
+};
+use anyhow::bail;
+use libra_types::{
+    exports::{AccountAddress, Client},
+    type_extensions::client_ext::{is_api_error, ClientExt},
+};
+
+/// children 0 to 5 are used by `KeyChain`
+pub const FIRST_UNRESERVED_CHILD: u64 = 6;
+
+/// An owner-style account at a child number past the reserved children
+pub struct DerivedAccount {
+    pub child_number: u64,
+    pub keys: AccountKeys,
+    /// the address on chain, if the account exists. It differs from the
+    /// derived address if the account was created with another key and rotated.
+    pub on_chain: Option<AccountAddress>,
+}
+
+/// Derives `count` accounts starting at child `index`. The passphrase is
+/// empty unless the mnemonic was set up with one.
+pub fn derive_accounts(
+    mnemonic: &str,
+    passphrase: &str,
+    index: u64,
+    count: u64,
+) -> anyhow::Result<Vec<DerivedAccount>> {
+    if index < FIRST_UNRESERVED_CHILD {
+        bail!(
+            "children 0 to {} are reserved for the owner and validator keys, use an index of {} or more",
+            FIRST_UNRESERVED_CHILD - 1,
+            FIRST_UNRESERVED_CHILD
+        );
+    }
+    let wallet = WalletLibrary::new_from_mnemonic_with_passphrase(
+        Mnemonic::from(mnemonic.trim())?,
+        passphrase,
+    );
+    let kf = wallet.get_key_factory();
+
+    (index..index + count)
+        .map(|n| {
+            let child = kf.private_child(ChildNumber::new(n))?;
+            Ok(DerivedAccount {
+                child_number: n,
+                keys: get_account_from_private(&child.get_private_key()),
+                on_chain: None,
+            })
+        })
+        .collect()
+}
+
+/// Fills in `on_chain` for each account which exists on chain. Only a node
+/// answering that the key is not mapped falls back to the derived address,
+/// any other error is returned, so an outage doesn't look like an account
+/// which was never rotated.
+pub async fn check_on_chain(
+    client: &Client,
+    accounts: &mut [DerivedAccount],
+) -> anyhow::Result<()> {
+    for a in accounts.iter_mut() {
+        // a rotated account is found by its authentication key
+        let addr = match client.lookup_originating_address(a.keys.auth_key).await {
+            Ok(addr) => addr,
+            Err(e) if is_api_error(&e) => a.keys.account,
+            Err(e) => return Err(e.context(format!("cannot look up child {}", a.child_number))),
+        };
+        let res = client
+            .view_ext("0x1::account::exists_at", None, Some(addr.to_string()))
+            .await?;
+        let (exists,): (bool,) = serde_json::from_value(res)?;
+        a.on_chain = exists.then_some(addr);
+    }
+    Ok(())
+}
+
+#[test]
+fn derive_past_reserved() {
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    assert!(derive_accounts(alice_mnem, "", 5, 1).is_err());
+
+    let derived = derive_accounts(alice_mnem, "", 6, 3).unwrap();
+    assert_eq!(derived.len(), 3);
+    assert_eq!(derived[0].child_number, 6);
+    assert_eq!(derived[2].child_number, 8);
+
+    // none of the derived accounts collide with the reserved owner account
+    let keychain = crate::account_keys::get_keys_from_mnem(alice_mnem.to_string()).unwrap();
+    assert!(derived
+        .iter()
+        .all(|d| d.keys.account != keychain.child_0_owner.account));
+    assert_ne!(derived[0].keys.account, derived[1].keys.account);
+
+    // derivation is deterministic
+    let again = derive_accounts(alice_mnem, "", 7, 1).unwrap();
+    assert_eq!(again[0].keys.account, derived[1].keys.account);
+
+    // a passphrase derives a different tree
+    let with_pass = derive_accounts(alice_mnem, "hunter2", 6, 1).unwrap();
+    assert_ne!(with_pass[0].keys.account, derived[0].keys.account);
+}
@@ -0,0 +1,359 @@
+//! Encrypted on-disk keystore.
+//! Each profile's private key is kept in its own file under `<node_home>/keystore/`,
+//! encrypted with AES-256-GCM using a key derived from a passphrase with scrypt.
+
+use crate::utils::write_to_user_only_file;
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, bail, Context};
+use diem_crypto::{
+    ed25519::Ed25519PrivateKey, PrivateKey, ValidCryptoMaterial, ValidCryptoMaterialStringExt,
+};
+use diem_types::chain_id::NamedChain;
+use libra_types::{
+    core_types::mode_ol::MODE_0L,
+    exports::{AccountAddress, AuthenticationKey},
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the keystore directory inside the node home
+pub const KEYSTORE_DIR: &str = "keystore";
+/// Env variable read for the passphrase in TESTING mode, like $MNEM
+pub const PASSPHRASE_ENV: &str = "LIBRA_KEYSTORE_PASSPHRASE";
+
+const KEYSTORE_VERSION: u8 = 1;
+const CIPHER: &str = "aes-256-gcm";
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// scrypt cost parameters, stored with each file so they can be raised later
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            log_n: scrypt::Params::RECOMMENDED_LOG_N,
+            r: scrypt::Params::RECOMMENDED_R,
+            p: scrypt::Params::RECOMMENDED_P,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> anyhow::Result<[u8; KEY_LEN]> {
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, KEY_LEN)
+            .map_err(|e| anyhow!("invalid scrypt params: {e}"))?;
+        let mut key = [0u8; KEY_LEN];
+        scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+            .map_err(|e| anyhow!("cannot derive key: {e}"))?;
+        Ok(key)
+    }
+}
+
+/// Bytes encrypted under a passphrase, with what is needed to decrypt them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    pub cipher: String,
+    pub kdf: KdfParams,
+    #[serde(with = "hex::serde")]
+    pub salt: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub ciphertext: Vec<u8>,
+}
+
+impl Sealed {
+    /// Encrypts the bytes with a fresh salt and nonce.
+    pub fn seal(plaintext: &[u8], passphrase: &str, kdf: KdfParams) -> anyhow::Result<Self> {
+        if passphrase.is_empty() {
+            bail!("passphrase cannot be empty");
+        }
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = vec![0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let cipher = new_cipher(&kdf.derive_key(passphrase, &salt)?)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|e| anyhow!("cannot encrypt: {e}"))?;
+
+        Ok(Self {
+            cipher: CIPHER.to_string(),
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypts the bytes. A wrong passphrase fails authentication.
+    pub fn open(&self, passphrase: &str) -> anyhow::Result<Vec<u8>> {
+        if self.cipher != CIPHER {
+            bail!("unsupported keystore cipher: {}", self.cipher);
+        }
+        let cipher = new_cipher(&self.kdf.derive_key(passphrase, &self.salt)?)?;
+        cipher
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_ref())
+            .map_err(|_| anyhow!("cannot decrypt, wrong passphrase?"))
+    }
+}
+
+/// An encrypted private key for one profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystoreFile {
+    pub version: u8,
+    /// the profile nickname in libra-cli-config.yaml
+    pub profile: String,
+    pub account: AccountAddress,
+    /// authentication key of the encrypted private key, to check which key
+    /// this is without the passphrase
+    pub auth_key: AuthenticationKey,
+    #[serde(flatten)]
+    pub sealed: Sealed,
+}
+
+impl KeystoreFile {
+    /// Encrypts the private key with a fresh salt and nonce.
+    pub fn encrypt(
+        profile: &str,
+        account: AccountAddress,
+        key: &Ed25519PrivateKey,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            profile: profile.to_string(),
+            account,
+            auth_key: AuthenticationKey::ed25519(&key.public_key()),
+            sealed: Sealed::seal(&key.to_bytes(), passphrase, kdf)?,
+        })
+    }
+
+    /// Decrypts the private key. A wrong passphrase fails authentication.
+    pub fn decrypt(&self, passphrase: &str) -> anyhow::Result<Ed25519PrivateKey> {
+        let plaintext = self.sealed.open(passphrase)?;
+        let key = Ed25519PrivateKey::try_from(plaintext.as_slice())?;
+
+        if AuthenticationKey::ed25519(&key.public_key()) != self.auth_key {
+            bail!("decrypted key does not match the keystore authentication key");
+        }
+        Ok(key)
+    }
+
+    /// Re-encrypts the key under a new passphrase, with a fresh salt and nonce.
+    pub fn change_password(&mut self, old: &str, new: &str) -> anyhow::Result<()> {
+        let key = self.decrypt(old)?;
+        *self = Self::encrypt(&self.profile, self.account, &key, new, self.sealed.kdf)?;
+        Ok(())
+    }
+
+    /// Writes `<dir>/<profile>.json`, readable only by the user.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = keystore_path(dir, &self.profile)?;
+        let json = serde_json::to_vec_pretty(self)?;
+        write_to_user_only_file(&path, &self.profile, &json)?;
+        Ok(path)
+    }
+
+    /// Reads the keystore file of a profile.
+    pub fn load(dir: &Path, profile: &str) -> anyhow::Result<Self> {
+        let path = keystore_path(dir, profile)?;
+        let s = fs::read_to_string(&path)
+            .with_context(|| format!("no keystore for profile {profile} at {}", path.display()))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+
+    /// Profile names which are not valid file names never have a keystore.
+    pub fn exists(dir: &Path, profile: &str) -> bool {
+        keystore_path(dir, profile).is_ok_and(|p| p.exists())
+    }
+}
+
+/// The keystore directory for a node home, usually `~/.libra/keystore`
+pub fn keystore_dir(node_home: &Path) -> PathBuf {
+    node_home.join(KEYSTORE_DIR)
+}
+
+/// The profile name becomes the file name, so it cannot leave the directory.
+fn keystore_path(dir: &Path, profile: &str) -> anyhow::Result<PathBuf> {
+    if profile.is_empty() || profile == "." || profile == ".." || profile.contains(['/', '\\']) {
+        bail!("profile name {profile:?} cannot be used as a keystore file name");
+    }
+    Ok(dir.join(format!("{profile}.json")))
+}
+
+fn new_cipher(key: &[u8]) -> anyhow::Result<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(key).map_err(|e| anyhow!("invalid cipher key: {e}"))
+}
+
+/// Lists every keystore file in the directory, sorted by profile.
+pub fn list_keystores(dir: &Path) -> anyhow::Result<Vec<KeystoreFile>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut list = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let s = fs::read_to_string(&path)?;
+        let ks: KeystoreFile = serde_json::from_str(&s)
+            .with_context(|| format!("cannot parse keystore file {}", path.display()))?;
+        list.push(ks);
+    }
+    list.sort_by(|a, b| a.profile.cmp(&b.profile));
+    Ok(list)
+}
+
+/// If the profile has a keystore, prompts for the passphrase and unlocks it.
+/// Returns `None` when there is no keystore for the profile.
+pub fn unlock_profile_key(
+    node_home: &Path,
+    profile: &str,
+) -> anyhow::Result<Option<Ed25519PrivateKey>> {
+    let dir = keystore_dir(node_home);
+    if !KeystoreFile::exists(&dir, profile) {
+        return Ok(None);
+    }
+    let ks = KeystoreFile::load(&dir, profile)?;
+    println!("unlocking keystore for profile {profile}");
+    let passphrase = prompt_passphrase(false)?;
+    Ok(Some(ks.decrypt(&passphrase)?))
+}
+
+/// Reads a hex encoded private key, e.g. one printed by `keystore export`.
+pub fn read_private_key_file(path: &Path) -> anyhow::Result<Ed25519PrivateKey> {
+    let s = fs::read_to_string(path)
+        .with_context(|| format!("cannot read private key file {}", path.display()))?;
+    Ed25519PrivateKey::from_encoded_string(s.trim())
+        .with_context(|| format!("{} is not a hex encoded private key", path.display()))
+}
+
+/// Prompts for a passphrase, optionally asking for it twice.
+pub fn prompt_passphrase(confirm: bool) -> anyhow::Result<String> {
+    // if we are in debugging or CI mode
+    if *MODE_0L == NamedChain::TESTING {
+        if let Ok(p) = env::var(PASSPHRASE_ENV) {
+            println!("Debugging mode, using passphrase from env variable, ${PASSPHRASE_ENV}");
+            return Ok(p);
+        }
+    }
+
+    let pass = rpassword::read_password_from_tty(Some("Keystore passphrase: "))?;
+    if confirm {
+        let again = rpassword::read_password_from_tty(Some("Confirm passphrase: "))?;
+        if pass != again {
+            bail!("passphrases do not match");
+        }
+    }
+    Ok(pass)
+}
+
+#[cfg(test)]
+pub(crate) fn test_kdf() -> KdfParams {
+    // cheap params so tests don't take seconds
+    KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    }
+}
+
+#[cfg(test)]
+fn alice_keys() -> crate::account_keys::AccountKeys {
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    crate::account_keys::get_keys_from_mnem(alice_mnem.to_string())
+        .unwrap()
+        .child_0_owner
+}
+
+#[test]
+fn keystore_roundtrip() {
+    let alice = alice_keys();
+    let ks = KeystoreFile::encrypt(
+        "alice",
+        alice.account,
+        &alice.pri_key,
+        "correct horse",
+        test_kdf(),
+    )
+    .unwrap();
+    assert_eq!(ks.auth_key, alice.auth_key);
+    assert_ne!(ks.sealed.ciphertext, alice.pri_key.to_bytes().to_vec());
+
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    ks.save(dir.path()).unwrap();
+
+    let loaded = KeystoreFile::load(dir.path(), "alice").unwrap();
+    let key = loaded.decrypt("correct horse").unwrap();
+    assert_eq!(key.to_bytes(), alice.pri_key.to_bytes());
+    assert!(loaded.decrypt("wrong horse").is_err());
+
+    let list = list_keystores(dir.path()).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].account, alice.account);
+}
+
+#[test]
+fn private_key_file() {
+    let alice = alice_keys();
+    let file = diem_temppath::TempPath::new();
+    fs::write(
+        file.path(),
+        format!("{}\n", alice.pri_key.to_encoded_string().unwrap()),
+    )
+    .unwrap();
+    let key = read_private_key_file(file.path()).unwrap();
+    assert_eq!(key.to_bytes(), alice.pri_key.to_bytes());
+
+    fs::write(file.path(), "not a key").unwrap();
+    assert!(read_private_key_file(file.path()).is_err());
+}
+
+#[test]
+fn keystore_change_password() {
+    let alice = alice_keys();
+    let mut ks =
+        KeystoreFile::encrypt("alice", alice.account, &alice.pri_key, "old", test_kdf()).unwrap();
+    let old_salt = ks.sealed.salt.clone();
+
+    assert!(ks.change_password("not it", "new").is_err());
+    ks.change_password("old", "new").unwrap();
+    assert_ne!(ks.sealed.salt, old_salt);
+    assert!(ks.decrypt("old").is_err());
+    assert_eq!(
+        ks.decrypt("new").unwrap().to_bytes(),
+        alice.pri_key.to_bytes()
+    );
+}
+
+#[test]
+fn keystore_path_stays_in_dir() {
+    let dir = Path::new("keystore");
+    assert_eq!(keystore_path(dir, "alice").unwrap(), dir.join("alice.json"));
+    for bad in ["", "..", "../alice", "a/b", "a\\b", "/etc/passwd"] {
+        assert!(
+            keystore_path(dir, bad).is_err(),
+            "{bad:?} should be rejected"
+        );
+    }
+}
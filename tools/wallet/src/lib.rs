@@ -2,11 +2,17 @@
 // Unused crate dependencies is not reliable. Uncomment to run check or use `cargo machete`
 
 pub mod account_keys;
+pub mod bulk_keygen;
 pub mod core;
+pub mod derive;
 pub mod key_gen;
 pub mod keys;
+pub mod keystore;
 pub mod load_keys;
+pub mod message;
+pub mod recovery;
 pub mod utils;
+pub mod validator_audit;
 pub mod validator_files;
 pub mod wallet_cli;
 pub mod whoami;
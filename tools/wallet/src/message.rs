@@ -0,0 +1,119 @@
+//! Sign and verify arbitrary messages with an account key, to prove control
+//! of an account off-chain.
+//! Messages are prefixed before signing so a signature can never be replayed
+//! as a transaction or any other signed payload.
+
+use anyhow::{bail, Context};
+use diem_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
+    PrivateKey, Signature, SigningKey,
+};
+use libra_types::{
+    exports::{AccountAddress, AuthenticationKey, Client},
+    type_extensions::client_ext::ClientExt,
+};
+use serde::{Deserialize, Serialize};
+
+/// Domain separation for signed messages
+pub const MESSAGE_PREFIX: &[u8] = b"0L SIGNED MESSAGE:\n";
+
+/// A message, its signature, and who claims to have signed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub account: AccountAddress,
+    pub public_key: Ed25519PublicKey,
+    pub message: String,
+    pub signature: Ed25519Signature,
+}
+
+fn prefixed(message: &str) -> Vec<u8> {
+    [MESSAGE_PREFIX, message.as_bytes()].concat()
+}
+
+/// Signs the message on behalf of `account`, which may differ from the
+/// address derived from the key if the account's key was rotated.
+pub fn sign_message(
+    key: &Ed25519PrivateKey,
+    account: AccountAddress,
+    message: &str,
+) -> SignedMessage {
+    SignedMessage {
+        account,
+        public_key: key.public_key(),
+        message: message.to_string(),
+        signature: key.sign_arbitrary_message(&prefixed(message)),
+    }
+}
+
+impl SignedMessage {
+    /// Checks the signature against the included public key only. This does
+    /// not prove the key controls the account.
+    pub fn verify_signature(&self) -> anyhow::Result<()> {
+        self.signature
+            .verify_arbitrary_msg(&prefixed(&self.message), &self.public_key)
+            .context("signature is not valid for this message and public key")
+    }
+
+    /// Offline check that the key derives the claimed address. Fails for
+    /// accounts whose key was rotated, use `verify_on_chain` for those.
+    pub fn verify_offline(&self) -> anyhow::Result<()> {
+        self.verify_signature()?;
+        let derived = AuthenticationKey::ed25519(&self.public_key).derived_address();
+        if derived != self.account {
+            bail!(
+                "public key derives address {derived}, not {}. If the account key was rotated, verify against the chain",
+                self.account
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks the signature, then that the key's authentication key resolves
+    /// on chain to the claimed account, so rotated keys verify correctly.
+    pub async fn verify_on_chain(&self, client: &Client) -> anyhow::Result<()> {
+        self.verify_signature()?;
+        let auth_key = AuthenticationKey::ed25519(&self.public_key);
+        let originating = client
+            .lookup_originating_address(auth_key)
+            .await
+            .context("cannot look up the authentication key on chain")?;
+        if originating != self.account {
+            bail!(
+                "the signing key belongs to account {originating} on chain, not {}",
+                self.account
+            );
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn sign_and_verify_message() {
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let alice = crate::account_keys::get_keys_from_mnem(alice_mnem.to_string())
+        .unwrap()
+        .child_0_owner;
+
+    let signed = sign_message(&alice.pri_key, alice.account, "I am alice");
+    signed.verify_offline().unwrap();
+
+    // survives a roundtrip through json
+    let json = serde_json::to_string(&signed).unwrap();
+    let parsed: SignedMessage = serde_json::from_str(&json).unwrap();
+    parsed.verify_offline().unwrap();
+
+    let mut tampered = signed.clone();
+    tampered.message = "I am bob".to_string();
+    assert!(tampered.verify_signature().is_err());
+
+    let mut other_account = signed.clone();
+    other_account.account = AccountAddress::ONE;
+    assert!(other_account.verify_signature().is_ok());
+    assert!(other_account.verify_offline().is_err());
+
+    // the prefix keeps it from verifying as a bare signature of the message
+    assert!(signed
+        .signature
+        .verify_arbitrary_msg(b"I am alice", &signed.public_key)
+        .is_err());
+}
@@ -0,0 +1,135 @@
+//! Encrypted recovery bundles written after a key rotation.
+//! A bundle holds the new private key of a rotated account, sealed under a
+//! passphrase, so a rotation never leaves the only copy of a key in memory.
+
+use crate::{
+    keystore::{KdfParams, Sealed},
+    utils::write_to_user_only_file,
+};
+use anyhow::{bail, Context};
+use diem_crypto::{ed25519::Ed25519PrivateKey, PrivateKey, ValidCryptoMaterial};
+use libra_types::exports::{AccountAddress, AuthenticationKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the recovery directory inside the node home
+pub const RECOVERY_DIR: &str = "recovery";
+
+const RECOVERY_VERSION: u8 = 1;
+
+/// The new key of a rotated account, encrypted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryBundle {
+    pub version: u8,
+    /// the account whose key was rotated
+    pub account: AccountAddress,
+    /// authentication key before the rotation
+    pub old_auth_key: AuthenticationKey,
+    /// authentication key of the encrypted private key
+    pub new_auth_key: AuthenticationKey,
+    /// seconds since the unix epoch
+    pub created_at: u64,
+    #[serde(flatten)]
+    pub sealed: Sealed,
+}
+
+impl RecoveryBundle {
+    /// Encrypts the new private key of a rotated account.
+    pub fn new(
+        account: AccountAddress,
+        old_auth_key: AuthenticationKey,
+        new_key: &Ed25519PrivateKey,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            version: RECOVERY_VERSION,
+            account,
+            old_auth_key,
+            new_auth_key: AuthenticationKey::ed25519(&new_key.public_key()),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            sealed: Sealed::seal(&new_key.to_bytes(), passphrase, kdf)?,
+        })
+    }
+
+    /// Decrypts the private key, checking it is the one recorded.
+    pub fn decrypt(&self, passphrase: &str) -> anyhow::Result<Ed25519PrivateKey> {
+        let plaintext = self.sealed.open(passphrase)?;
+        let key = Ed25519PrivateKey::try_from(plaintext.as_slice())?;
+
+        if AuthenticationKey::ed25519(&key.public_key()) != self.new_auth_key {
+            bail!("decrypted key does not match the bundle authentication key");
+        }
+        Ok(key)
+    }
+
+    /// Writes `<dir>/<account>_<created_at>.json`, readable only by the user.
+    /// Never overwrites an earlier bundle.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!(
+            "{}_{}.json",
+            self.account.to_hex(),
+            self.created_at
+        ));
+        if path.exists() {
+            bail!("recovery bundle already exists at {}", path.display());
+        }
+        let json = serde_json::to_vec_pretty(self)?;
+        write_to_user_only_file(&path, "recovery bundle", &json)?;
+        Ok(path)
+    }
+
+    /// Reads a bundle written by `save`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("cannot read recovery bundle at {}", path.display()))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+}
+
+/// The recovery directory for a node home, usually `~/.libra/recovery`
+pub fn recovery_dir(node_home: &Path) -> PathBuf {
+    node_home.join(RECOVERY_DIR)
+}
+
+#[test]
+fn recovery_bundle_roundtrip() {
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let alice = crate::account_keys::get_keys_from_mnem(alice_mnem.to_string())
+        .unwrap()
+        .child_0_owner;
+    let (new_auth_key, _, _, new_mnem) = crate::key_gen::keygen();
+    let new_key = crate::account_keys::get_keys_from_mnem(new_mnem)
+        .unwrap()
+        .child_0_owner
+        .pri_key;
+
+    let bundle = RecoveryBundle::new(
+        alice.account,
+        alice.auth_key,
+        &new_key,
+        "rotate",
+        crate::keystore::test_kdf(),
+    )
+    .unwrap();
+    assert_eq!(bundle.new_auth_key, new_auth_key);
+
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let path = bundle.save(dir.path()).unwrap();
+    assert!(bundle.save(dir.path()).is_err());
+
+    let loaded = RecoveryBundle::load(&path).unwrap();
+    assert_eq!(loaded.account, alice.account);
+    assert_eq!(loaded.old_auth_key, alice.auth_key);
+    assert_eq!(
+        loaded.decrypt("rotate").unwrap().to_bytes(),
+        new_key.to_bytes()
+    );
+    assert!(loaded.decrypt("wrong").is_err());
+}
@@ -0,0 +1,403 @@
+//! Audit a validator's key files against what its mnemonic derives, and
+//! against the `ValidatorConfig` on chain.
+//! Each problem is reported with a suggested fix, nothing is changed.
+
+use crate::{
+    keys::{make_validator_keys, PRIVATE_KEYS_FILE, PUBLIC_KEYS_FILE, VALIDATOR_FILE, VFN_FILE},
+    utils::{from_yaml, read_from_file},
+    validator_files::{OPERATOR_FILE, OWNER_FILE},
+};
+use diem_config::config::IdentityBlob;
+use diem_crypto::{bls12381, x25519, PrivateKey};
+use diem_genesis::{
+    config::{OperatorConfiguration, OwnerConfiguration},
+    keys::{PrivateIdentity, PublicIdentity},
+};
+use diem_types::{network_address::NetworkAddress, validator_config::ValidatorConfig};
+use libra_types::{
+    exports::{AccountAddress, Client},
+    type_extensions::client_ext::{is_not_found, ClientExt},
+};
+use serde::de::DeserializeOwned;
+use std::{fmt, path::Path};
+
+const REGENERATE: &str =
+    "regenerate the files from the mnemonic with `libra config validator-init`";
+const UPDATE_ON_CHAIN: &str =
+    "update the on-chain addresses from operator.yaml with `libra txs validator update`";
+
+/// One inconsistency found by the audit
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// the file, or "on chain"
+    pub source: String,
+    pub problem: String,
+    pub fix: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}\n  fix: {}", self.source, self.problem, self.fix)
+    }
+}
+
+#[derive(Default)]
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    /// records a finding unless `ok`
+    fn check(&mut self, ok: bool, source: &str, problem: &str, fix: &str) {
+        if !ok {
+            self.0.push(Finding {
+                source: source.to_string(),
+                problem: problem.to_string(),
+                fix: fix.to_string(),
+            });
+        }
+    }
+
+    /// reads a file, recording a finding if it is missing or can't be parsed
+    fn read<T: DeserializeOwned>(&mut self, home: &Path, file: &str) -> Option<T> {
+        let path = home.join(file);
+        if !path.exists() {
+            self.check(false, file, "file is missing", REGENERATE);
+            return None;
+        }
+        let parsed = read_from_file(&path)
+            .and_then(|b| Ok(String::from_utf8(b)?))
+            .and_then(|s| from_yaml(&s));
+        match parsed {
+            Ok(t) => Some(t),
+            Err(e) => {
+                self.check(false, file, &format!("cannot be parsed: {e}"), REGENERATE);
+                None
+            }
+        }
+    }
+
+    fn check_pop(
+        &mut self,
+        file: &str,
+        key: &bls12381::PublicKey,
+        pop: &bls12381::ProofOfPossession,
+    ) {
+        self.check(
+            pop.verify(key).is_ok(),
+            file,
+            "the consensus proof of possession does not verify for the consensus key",
+            REGENERATE,
+        );
+    }
+
+    #[cfg(unix)]
+    fn check_permissions(&mut self, home: &Path, file: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(meta) = std::fs::metadata(home.join(file)) {
+            self.check(
+                meta.permissions().mode() & 0o077 == 0,
+                file,
+                "holds private keys but is readable by other users",
+                &format!("chmod 600 {}", home.join(file).display()),
+            );
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn check_permissions(&mut self, _home: &Path, _file: &str) {}
+}
+
+/// Checks the key files in `home` against the keys the mnemonic derives.
+/// Returns the derived public identity, for the on-chain audit.
+pub fn audit_files(
+    home: &Path,
+    mnem: String,
+    keep_legacy_addr: bool,
+) -> anyhow::Result<(PublicIdentity, Vec<Finding>)> {
+    let (val_blob, vfn_blob, private_id, public_id, _) =
+        make_validator_keys(Some(mnem), keep_legacy_addr)?;
+    let consensus_key = private_id.consensus_private_key.public_key();
+    let val_network_key = private_id.validator_network_private_key.public_key();
+    let vfn_network_key = private_id.full_node_network_private_key.public_key();
+
+    let mut f = Findings::default();
+
+    if let Some(p) = f.read::<PublicIdentity>(home, PUBLIC_KEYS_FILE) {
+        let file = PUBLIC_KEYS_FILE;
+        f.check(
+            p.account_address == public_id.account_address,
+            file,
+            &format!(
+                "account is {}, the mnemonic derives {}",
+                p.account_address, public_id.account_address
+            ),
+            REGENERATE,
+        );
+        f.check(
+            p.account_public_key == public_id.account_public_key,
+            file,
+            "account public key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            p.consensus_public_key.as_ref() == Some(&consensus_key),
+            file,
+            "consensus public key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            p.validator_network_public_key == Some(val_network_key),
+            file,
+            "validator network public key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            p.full_node_network_public_key == Some(vfn_network_key),
+            file,
+            "fullnode network public key does not match the mnemonic",
+            REGENERATE,
+        );
+        if let (Some(key), Some(pop)) = (&p.consensus_public_key, &p.consensus_proof_of_possession)
+        {
+            f.check_pop(file, key, pop);
+        }
+    }
+
+    if let Some(p) = f.read::<PrivateIdentity>(home, PRIVATE_KEYS_FILE) {
+        let file = PRIVATE_KEYS_FILE;
+        f.check(
+            p.consensus_private_key.public_key() == consensus_key,
+            file,
+            "consensus private key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            p.validator_network_private_key.public_key() == val_network_key,
+            file,
+            "validator network private key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            p.full_node_network_private_key.public_key() == vfn_network_key,
+            file,
+            "fullnode network private key does not match the mnemonic",
+            REGENERATE,
+        );
+        // the account key is replaced with an all-zero decoy when files are saved
+        f.check(
+            p.account_private_key.to_bytes() == [0u8; 32],
+            file,
+            "holds the account transaction key, which should not be on a validator",
+            REGENERATE,
+        );
+    }
+
+    if let Some(b) = f.read::<IdentityBlob>(home, VALIDATOR_FILE) {
+        let file = VALIDATOR_FILE;
+        f.check(
+            b.account_address == val_blob.account_address,
+            file,
+            "account does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            b.consensus_private_key.as_ref().map(|k| k.public_key()) == Some(consensus_key.clone()),
+            file,
+            "consensus key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            b.network_private_key.public_key() == val_network_key,
+            file,
+            "network key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            b.account_private_key.is_none(),
+            file,
+            "holds the account transaction key, which should not be on a validator",
+            REGENERATE,
+        );
+    }
+
+    if let Some(b) = f.read::<IdentityBlob>(home, VFN_FILE) {
+        let file = VFN_FILE;
+        f.check(
+            b.network_private_key.public_key() == vfn_network_key,
+            file,
+            "network key does not match the mnemonic",
+            REGENERATE,
+        );
+        // the VFN peer id is derived from its network key, so it doesn't self dial the validator
+        f.check(
+            b.account_address == vfn_blob.account_address,
+            file,
+            "peer id does not match the fullnode network key",
+            REGENERATE,
+        );
+        f.check(
+            b.consensus_private_key.is_none() && b.account_private_key.is_none(),
+            file,
+            "the fullnode identity should hold only a network key",
+            REGENERATE,
+        );
+    }
+
+    if let Some(o) = f.read::<OperatorConfiguration>(home, OPERATOR_FILE) {
+        let file = OPERATOR_FILE;
+        f.check(
+            o.consensus_public_key == consensus_key,
+            file,
+            "consensus public key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            o.validator_network_public_key == val_network_key,
+            file,
+            "validator network public key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check(
+            o.full_node_network_public_key == Some(vfn_network_key),
+            file,
+            "fullnode network public key does not match the mnemonic",
+            REGENERATE,
+        );
+        f.check_pop(
+            file,
+            &o.consensus_public_key,
+            &o.consensus_proof_of_possession,
+        );
+    }
+
+    if let Some(o) = f.read::<OwnerConfiguration>(home, OWNER_FILE) {
+        let owner: AccountAddress = o.owner_account_address.into();
+        f.check(
+            owner == public_id.account_address,
+            OWNER_FILE,
+            &format!(
+                "owner is {owner}, the mnemonic derives {}",
+                public_id.account_address
+            ),
+            REGENERATE,
+        );
+    }
+
+    for file in [PRIVATE_KEYS_FILE, VALIDATOR_FILE, VFN_FILE] {
+        f.check_permissions(home, file);
+    }
+
+    Ok((public_id, f.0))
+}
+
+/// Checks the `ValidatorConfig` on chain against the derived keys.
+pub async fn audit_on_chain(
+    client: &Client,
+    public_id: &PublicIdentity,
+) -> anyhow::Result<Vec<Finding>> {
+    let mut f = Findings::default();
+    let source = "on chain";
+    let account = public_id.account_address;
+
+    let config = match client.get_move_resource::<ValidatorConfig>(account).await {
+        Ok(config) => config,
+        Err(e) if is_not_found(&e) => {
+            f.check(
+                false,
+                source,
+                &format!("no ValidatorConfig for account {account}"),
+                "register the validator with `libra txs validator register`",
+            );
+            return Ok(f.0);
+        }
+        Err(e) => return Err(e.context(format!("cannot read the ValidatorConfig of {account}"))),
+    };
+
+    f.check(
+        Some(&config.consensus_public_key) == public_id.consensus_public_key.as_ref(),
+        source,
+        "consensus key differs from the one the mnemonic derives",
+        "the consensus key can't be changed after registering, use the mnemonic the validator registered with",
+    );
+
+    let val_addrs = config.validator_network_addresses().unwrap_or_default();
+    check_network_keys(
+        &mut f,
+        "validator",
+        &val_addrs,
+        public_id.validator_network_public_key,
+    );
+    let vfn_addrs = config.fullnode_network_addresses().unwrap_or_default();
+    check_network_keys(
+        &mut f,
+        "fullnode",
+        &vfn_addrs,
+        public_id.full_node_network_public_key,
+    );
+
+    Ok(f.0)
+}
+
+/// every address must carry the expected noise key
+fn check_network_keys(
+    f: &mut Findings,
+    name: &str,
+    addrs: &[NetworkAddress],
+    expected: Option<x25519::PublicKey>,
+) {
+    let source = "on chain";
+    if addrs.is_empty() {
+        f.check(
+            false,
+            source,
+            &format!("no {name} network address"),
+            UPDATE_ON_CHAIN,
+        );
+    }
+    for a in addrs {
+        f.check(
+            a.find_noise_proto() == expected,
+            source,
+            &format!(
+                "{name} network address {a} has a different network key than the mnemonic derives"
+            ),
+            UPDATE_ON_CHAIN,
+        );
+    }
+}
+
+#[test]
+fn audit_fresh_validator_files() {
+    use crate::{keys::refresh_validator_files, validator_files::SetValidatorConfiguration};
+    use std::str::FromStr;
+
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let home = dir.path().to_owned();
+
+    refresh_validator_files(Some(alice_mnem.to_string()), Some(home.clone()), false).unwrap();
+    SetValidatorConfiguration::new(
+        Some(home.clone()),
+        "alice".to_string(),
+        diem_genesis::config::HostAndPort::from_str("127.0.0.1:6180").unwrap(),
+        None,
+    )
+    .set_config_files()
+    .unwrap();
+
+    let (public_id, findings) = audit_files(&home, alice_mnem.to_string(), false).unwrap();
+    assert!(findings.is_empty(), "{findings:?}");
+
+    // files of another mnemonic don't match
+    let (_, _, _, bob_mnem) = crate::key_gen::keygen();
+    let (bob_id, findings) = audit_files(&home, bob_mnem, false).unwrap();
+    assert_ne!(bob_id.account_address, public_id.account_address);
+    assert!(findings.iter().any(|f| f.source == PUBLIC_KEYS_FILE));
+    assert!(findings.iter().any(|f| f.source == OPERATOR_FILE));
+
+    // missing files are reported
+    std::fs::remove_file(home.join(VFN_FILE)).unwrap();
+    let (_, findings) = audit_files(&home, alice_mnem.to_string(), false).unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].source, VFN_FILE);
+}
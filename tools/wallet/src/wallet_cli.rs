@@ -1,8 +1,28 @@
-use crate::{account_keys, whoami::who_am_i};
+use crate::{
+    account_keys, bulk_keygen,
+    core::{
+        mnemonic::Mnemonic,
+        shamir::{combine_shares, split_mnemonic, Share},
+        wallet_library::WalletLibrary,
+    },
+    derive,
+    keystore::{self, KdfParams, KeystoreFile},
+    load_keys::get_account_from_prompt,
+    message::{self, SignedMessage},
+    utils::{check_if_file_exists, prompt_yes_with_override},
+    validator_audit,
+    whoami::who_am_i,
+};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use diem_crypto::{PrivateKey, ValidCryptoMaterialStringExt};
+use libra_types::{
+    core_types::app_cfg::AppCfg,
+    exports::{AccountAddress, AuthenticationKey, Client},
+    global_config_dir,
+};
+use std::{path::PathBuf, str::FromStr};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -29,6 +49,157 @@ enum WalletSub {
     Legacy,
     /// use mnemonic to see what account keys are generated
     Whoami(WhoamiOpts),
+    /// Manage the encrypted keystore of profile private keys
+    Keystore(KeystoreOpts),
+    /// Derive additional accounts from the mnemonic, past the reserved children 0-5
+    Derive {
+        /// first child number to derive
+        #[clap(short, long, default_value_t = derive::FIRST_UNRESERVED_CHILD)]
+        index: u64,
+        /// how many accounts to derive
+        #[clap(short('n'), long, default_value = "1")]
+        count: u64,
+        #[clap(short, long)]
+        mnemonic: Option<String>,
+        /// optional, path to the libra cli config file, used to check the accounts on chain
+        #[clap(long)]
+        config_path: Option<PathBuf>,
+        /// prompt for the BIP-39 style passphrase the mnemonic was set up with
+        #[clap(long)]
+        passphrase: bool,
+    },
+    /// Split a mnemonic into shares, any threshold of which can recover it
+    Split {
+        /// number of shares needed to recover the mnemonic
+        #[clap(short, long)]
+        threshold: u8,
+        /// number of shares to create
+        #[clap(short('n'), long)]
+        shares: u8,
+        #[clap(short, long)]
+        mnemonic: Option<String>,
+    },
+    /// Generate many accounts in parallel and write them to a JSON or CSV manifest
+    BulkKeygen {
+        /// number of accounts to generate
+        #[clap(short('n'), long)]
+        count: usize,
+        /// manifest file, written as CSV if it ends in .csv, otherwise JSON
+        #[clap(short, long)]
+        out: PathBuf,
+        /// optional, only keep addresses starting with these hex characters
+        #[clap(short, long)]
+        prefix: Option<String>,
+        /// encrypt the mnemonics in the manifest with a passphrase
+        #[clap(short, long)]
+        encrypt: bool,
+    },
+    /// Sign a message with the account key, to prove control of the account off-chain
+    SignMessage {
+        /// the message to sign
+        #[clap(long)]
+        message: String,
+        /// optional, the account signing, if its key was rotated. Defaults to the address derived from the mnemonic
+        #[clap(short, long)]
+        account: Option<AccountAddress>,
+        #[clap(short, long)]
+        mnemonic: Option<String>,
+        /// optional, write the signed message json to a file
+        #[clap(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Verify a signed message file from `sign-message`
+    VerifyMessage {
+        /// the signed message json
+        #[clap(short, long)]
+        file: PathBuf,
+        /// optional, path to the libra cli config file, for the node to check the account against
+        #[clap(long)]
+        config_path: Option<PathBuf>,
+        /// only check the signature and that the key derives the address, without the chain
+        #[clap(long)]
+        offline: bool,
+    },
+    /// Recover a mnemonic from shares created with `split`
+    Combine {
+        /// a share, repeat for each share. Otherwise this will prompt for shares
+        #[clap(short, long)]
+        share: Vec<String>,
+    },
+    #[clap(subcommand)]
+    /// Check validator key files
+    Validator(ValidatorSub),
+}
+
+#[derive(Subcommand)]
+enum ValidatorSub {
+    /// Check the key files match the mnemonic and the on-chain validator config
+    Audit {
+        /// optional, directory of the key files. Defaults to ~/.libra
+        #[clap(long)]
+        home: Option<PathBuf>,
+        #[clap(short, long)]
+        mnemonic: Option<String>,
+        /// the account uses a legacy (v5) address
+        #[clap(short('l'), long)]
+        keep_legacy_address: bool,
+        /// optional, path to the libra cli config file, for the node to check against
+        #[clap(long)]
+        config_path: Option<PathBuf>,
+        /// only check the files, without the chain
+        #[clap(long)]
+        offline: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+struct KeystoreOpts {
+    /// optional, path to the libra cli config file
+    #[clap(short, long)]
+    config_path: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: KeystoreSub,
+}
+
+#[derive(Subcommand, Debug)]
+enum KeystoreSub {
+    /// Encrypt the key of a profile into the keystore. The key must be the
+    /// account's current key on chain, or the profile's if the account is not
+    /// on chain yet or --offline is set.
+    Add {
+        /// profile nickname or address fragment, defaults to the default profile
+        #[clap(short, long)]
+        profile: Option<String>,
+        /// optional, mnemonic to derive the key from. Otherwise this will prompt for mnemonic
+        #[clap(short, long)]
+        mnemonic: Option<String>,
+        /// optional, child number of an account from `wallet derive`. Defaults to the owner key
+        #[clap(long)]
+        child: Option<u64>,
+        /// prompt for the BIP-39 style passphrase the mnemonic was set up with
+        #[clap(long)]
+        passphrase: bool,
+        /// read the hex encoded private key from a file instead, e.g. after a key rotation
+        #[clap(long, conflicts_with_all = ["mnemonic", "child", "passphrase"])]
+        private_key_file: Option<PathBuf>,
+        /// don't reach a node, check the key against the profile's authentication key.
+        /// A rotated key will not match
+        #[clap(long)]
+        offline: bool,
+    },
+    /// List the profiles in the keystore
+    List,
+    /// Decrypt and print the private key of a profile
+    Export {
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
+    /// Re-encrypt a profile key under a new passphrase
+    ChangePassword {
+        #[clap(short, long)]
+        profile: Option<String>,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -73,6 +244,303 @@ impl WalletCli {
                     .await?
                 );
             }
+            WalletSub::Keystore(opts) => opts.run().await?,
+            WalletSub::Derive {
+                index,
+                count,
+                mnemonic,
+                config_path,
+                passphrase,
+            } => {
+                let mnem = match mnemonic {
+                    Some(m) => m.to_owned(),
+                    None => get_account_from_prompt().2.mnemonic(),
+                };
+                let passphrase = if *passphrase {
+                    rpassword::read_password_from_tty(Some("Mnemonic passphrase: "))?
+                } else {
+                    String::new()
+                };
+                let mut accounts = derive::derive_accounts(&mnem, &passphrase, *index, *count)?;
+
+                let checked = match AppCfg::load(config_path.clone()).and_then(|c| c.pick_url(None))
+                {
+                    Ok(url) => {
+                        derive::check_on_chain(&Client::new(url), &mut accounts).await?;
+                        true
+                    }
+                    Err(_) => {
+                        println!("no libra-cli-config.yaml found, not checking accounts on chain");
+                        false
+                    }
+                };
+
+                for a in accounts {
+                    let status = match (checked, a.on_chain) {
+                        (false, _) => String::new(),
+                        (true, Some(addr)) => format!(" on chain at {addr}"),
+                        (true, None) => " not on chain".to_string(),
+                    };
+                    println!("child {}: {}{}", a.child_number, a.keys.account, status);
+                }
+            }
+            WalletSub::Split {
+                threshold,
+                shares,
+                mnemonic,
+            } => {
+                let mnem = match mnemonic {
+                    Some(m) => m.to_owned(),
+                    None => get_account_from_prompt().2.mnemonic(),
+                };
+                let list = split_mnemonic(&Mnemonic::from(mnem.trim())?, *threshold, *shares)?;
+                println!(
+                    "any {threshold} of these {shares} shares recover the mnemonic. Store each one separately.\n"
+                );
+                for s in list {
+                    println!("share {}: {}\n", s.index, s.to_words());
+                }
+            }
+            WalletSub::BulkKeygen {
+                count,
+                out,
+                prefix,
+                encrypt,
+            } => {
+                check_if_file_exists(out)?;
+                let passphrase = if *encrypt {
+                    Some(keystore::prompt_passphrase(true)?)
+                } else {
+                    None
+                };
+                let mut accounts = bulk_keygen::bulk_keygen(*count, prefix.as_deref())?;
+                if let Some(p) = passphrase {
+                    bulk_keygen::encrypt_mnemonics(&mut accounts, &p)?;
+                }
+                bulk_keygen::write_manifest(out, &accounts)?;
+                println!("wrote {} accounts to {}", accounts.len(), out.display());
+                println!(
+                    "fund them with `libra txs batch-transfer --manifest {} --amount <coins>`",
+                    out.display()
+                );
+            }
+            WalletSub::SignMessage {
+                message: msg,
+                account,
+                mnemonic,
+                out,
+            } => {
+                let keys = match mnemonic {
+                    Some(m) => account_keys::get_keys_from_mnem(m.to_owned())?,
+                    None => account_keys::get_keys_from_prompt()?,
+                };
+                let owner = keys.child_0_owner;
+                let signed =
+                    message::sign_message(&owner.pri_key, account.unwrap_or(owner.account), msg);
+                let json = serde_json::to_string_pretty(&signed)?;
+                if let Some(path) = out {
+                    std::fs::write(path, &json)?;
+                    println!("signed message written to {}", path.display());
+                }
+                println!("{json}");
+            }
+            WalletSub::VerifyMessage {
+                file,
+                config_path,
+                offline,
+            } => {
+                let signed: SignedMessage = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+                if *offline {
+                    signed.verify_offline()?;
+                } else {
+                    let url = AppCfg::load(config_path.clone())?.pick_url(None)?;
+                    signed.verify_on_chain(&Client::new(url)).await?;
+                }
+                println!(
+                    "valid signature by account {} of message:\n{}",
+                    signed.account, signed.message
+                );
+            }
+            WalletSub::Combine { share } => {
+                let mut list = share
+                    .iter()
+                    .map(|s| Share::from_words(s))
+                    .collect::<Result<Vec<_>>>()?;
+                if list.is_empty() {
+                    println!("enter shares one at a time");
+                    loop {
+                        let s = rpassword::read_password_from_tty(Some("share: "))?;
+                        list.push(Share::from_words(&s)?);
+                        if list.len() >= list[0].threshold as usize {
+                            break;
+                        }
+                    }
+                }
+                let mnemonic = combine_shares(&list)?;
+                let keys = account_keys::get_keys_from_mnem(mnemonic.to_string())?;
+                println!(
+                    "recovered mnemonic for account {}",
+                    keys.child_0_owner.account
+                );
+                println!("{mnemonic}");
+            }
+            WalletSub::Validator(ValidatorSub::Audit {
+                home,
+                mnemonic,
+                keep_legacy_address,
+                config_path,
+                offline,
+            }) => {
+                let home = home.clone().unwrap_or_else(global_config_dir);
+                let mnem = match mnemonic {
+                    Some(m) => m.to_owned(),
+                    None => get_account_from_prompt().2.mnemonic(),
+                };
+                let (public_id, mut findings) =
+                    validator_audit::audit_files(&home, mnem, *keep_legacy_address)?;
+                if !*offline {
+                    let url = AppCfg::load(config_path.clone())?.pick_url(None)?;
+                    findings.extend(
+                        validator_audit::audit_on_chain(&Client::new(url), &public_id).await?,
+                    );
+                }
+
+                if findings.is_empty() {
+                    println!(
+                        "validator files in {} are consistent for account {}",
+                        home.display(),
+                        public_id.account_address
+                    );
+                } else {
+                    for f in &findings {
+                        println!("{f}\n");
+                    }
+                    bail!("found {} problems", findings.len());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KeystoreOpts {
+    async fn run(&self) -> Result<()> {
+        let app_cfg = AppCfg::load(self.config_path.clone())?;
+        let dir = keystore::keystore_dir(&app_cfg.workspace.node_home);
+
+        match &self.command {
+            KeystoreSub::Add {
+                profile,
+                mnemonic,
+                child,
+                passphrase,
+                private_key_file,
+                offline,
+            } => {
+                let profile = app_cfg.get_profile(profile.clone())?;
+                let key = match private_key_file {
+                    Some(path) => keystore::read_private_key_file(path)?,
+                    None => {
+                        let mnem = match mnemonic {
+                            Some(m) => m.to_owned(),
+                            None => get_account_from_prompt().2.mnemonic(),
+                        };
+                        let passphrase = if *passphrase {
+                            rpassword::read_password_from_tty(Some("Mnemonic passphrase: "))?
+                        } else {
+                            String::new()
+                        };
+                        match child {
+                            Some(n) => {
+                                derive::derive_accounts(&mnem, &passphrase, *n, 1)?
+                                    .remove(0)
+                                    .keys
+                                    .pri_key
+                            }
+                            None => {
+                                let wallet = WalletLibrary::new_from_mnemonic_with_passphrase(
+                                    Mnemonic::from(mnem.trim())?,
+                                    &passphrase,
+                                );
+                                account_keys::KeyChain::new(&wallet)?.child_0_owner.pri_key
+                            }
+                        }
+                    }
+                };
+
+                // rotated keys are only known on chain
+                let expected = if *offline {
+                    profile.auth_key
+                } else {
+                    let client = Client::new(app_cfg.pick_url(None)?);
+                    match client
+                        .get_account_resource(profile.account, "0x1::account::Account")
+                        .await
+                        .context("cannot reach a node to check the key, use --offline to skip")?
+                        .into_inner()
+                    {
+                        Some(account) => {
+                            let key = account.data["authentication_key"]
+                                .as_str()
+                                .context("cannot parse the account's authentication key")?;
+                            AuthenticationKey::from_str(key.trim_start_matches("0x"))?
+                        }
+                        None => profile.auth_key,
+                    }
+                };
+                if AuthenticationKey::ed25519(&key.public_key()) != expected {
+                    bail!(
+                        "key does not match the authentication key of account {} (profile {})",
+                        profile.account,
+                        profile.nickname
+                    );
+                }
+                if KeystoreFile::exists(&dir, &profile.nickname) {
+                    prompt_yes_with_override(&format!(
+                        "profile {} already has a keystore, overwrite it?",
+                        profile.nickname
+                    ))?;
+                }
+                let passphrase = keystore::prompt_passphrase(true)?;
+                let ks = KeystoreFile::encrypt(
+                    &profile.nickname,
+                    profile.account,
+                    &key,
+                    &passphrase,
+                    KdfParams::default(),
+                )?;
+                let path = ks.save(&dir)?;
+                println!("saved encrypted key to {}", path.display());
+            }
+            KeystoreSub::List => {
+                let list = keystore::list_keystores(&dir)?;
+                if list.is_empty() {
+                    println!("no keys in keystore at {}", dir.display());
+                }
+                for ks in list {
+                    println!("{}: {}", ks.profile, ks.account);
+                }
+            }
+            KeystoreSub::Export { profile } => {
+                let profile = app_cfg.get_profile(profile.clone())?;
+                let ks = KeystoreFile::load(&dir, &profile.nickname)?;
+                prompt_yes_with_override(
+                    "this will print your private key in plain text, continue?",
+                )?;
+                let key = ks.decrypt(&keystore::prompt_passphrase(false)?)?;
+                println!("{}", key.to_encoded_string()?);
+            }
+            KeystoreSub::ChangePassword { profile } => {
+                let profile = app_cfg.get_profile(profile.clone())?;
+                let mut ks = KeystoreFile::load(&dir, &profile.nickname)?;
+                println!("current passphrase");
+                let old = keystore::prompt_passphrase(false)?;
+                println!("new passphrase");
+                let new = keystore::prompt_passphrase(true)?;
+                ks.change_password(&old, &new)?;
+                ks.save(&dir)?;
+                println!("passphrase changed for profile {}", profile.nickname);
+            }
         }
         Ok(())
     }
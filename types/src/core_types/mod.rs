@@ -5,3 +5,4 @@ pub mod legacy_currency_info;
 pub mod mode_ol;
 pub mod network_playlist;
 pub mod pledge;
+pub mod supply;
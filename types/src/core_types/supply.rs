@@ -0,0 +1,40 @@
+//! Coin supply breakdown, shared by the genesis migration and chain queries.
+//! All amounts are in on-chain coin units, without decimal scaling.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Supply {
+    pub total: f64,
+    pub normal: f64,
+    pub validator: f64, // will overlap with slow wallet
+    pub slow_total: f64,
+    pub slow_locked: f64,
+    pub slow_validator_locked: f64,
+    pub slow_unlocked: f64,
+    pub donor_voice: f64,
+    pub make_whole: f64,
+    // which will compute later
+    pub split_factor: f64,
+    pub escrow_pct: f64,
+    pub epoch_reward_base_case: f64,
+    pub expected_user_balance: f64,
+    pub expected_user_ratio: f64,
+    pub expected_circulating: f64,
+    pub expected_circulating_ratio: f64,
+    // only available from a running chain
+    /// the final supply set at genesis
+    pub max_supply: f64,
+    /// unlocked coins plus community wallet credit available immediately
+    pub circulating: f64,
+    /// slow wallet unlocks and community wallet advances
+    pub all_unlocked: f64,
+    /// coins committed to future pledges
+    pub pledge: f64,
+    /// coins held in the infrastructure escrow for validator rewards
+    pub infra_escrow: f64,
+    /// lifetime coins removed from the supply
+    pub burned: f64,
+    /// lifetime coins burned and then recycled into the system
+    pub recycled: f64,
+}
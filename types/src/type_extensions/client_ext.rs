@@ -1,6 +1,8 @@
 use crate::{
-    core_types::app_cfg::AppCfg, exports::AuthenticationKey,
-    type_extensions::cli_config_ext::CliConfigExt, util::parse_function_id,
+    core_types::app_cfg::AppCfg,
+    exports::{AuthenticationKey, RestError},
+    type_extensions::cli_config_ext::CliConfigExt,
+    util::parse_function_id,
 };
 
 use anyhow::{anyhow, Context};
@@ -14,7 +16,8 @@ use diem_sdk::{
         transaction_argument::convert_txn_args,
     },
     rest_client::{
-        diem_api_types::{EntryFunctionId, MoveType, ViewRequest},
+        diem_api_types::{DiemError, DiemErrorCode, EntryFunctionId, MoveType, ViewRequest},
+        error::DiemErrorResponse,
         Account, Client,
     },
     transaction_builder::TransactionBuilder,
@@ -57,6 +60,12 @@ pub trait ClientExt {
         address: AccountAddress,
     ) -> anyhow::Result<T>;
 
+    async fn get_move_resource_at_version<T: MoveStructType + DeserializeOwned>(
+        &self,
+        address: AccountAddress,
+        version: Option<u64>,
+    ) -> anyhow::Result<T>;
+
     async fn get_account_resources_ext(&self, account: AccountAddress) -> anyhow::Result<String>;
 
     async fn get_sequence_number(&self, account: AccountAddress) -> anyhow::Result<u64>;
@@ -76,6 +85,14 @@ pub trait ClientExt {
         ty_args: Option<String>,
         args: Option<String>,
     ) -> anyhow::Result<Value>;
+
+    async fn view_ext_at_version(
+        &self,
+        function_id: &str,
+        ty_args: Option<String>,
+        args: Option<String>,
+        version: Option<u64>,
+    ) -> anyhow::Result<Value>;
 }
 
 #[async_trait]
@@ -143,12 +160,28 @@ impl ClientExt for Client {
     async fn get_move_resource<T: MoveStructType + DeserializeOwned>(
         &self,
         address: AccountAddress,
+    ) -> anyhow::Result<T> {
+        self.get_move_resource_at_version::<T>(address, None).await
+    }
+
+    /// Gets a Move resource of the specified type as it was at a given ledger version.
+    /// A `None` version reads the latest state.
+    async fn get_move_resource_at_version<T: MoveStructType + DeserializeOwned>(
+        &self,
+        address: AccountAddress,
+        version: Option<u64>,
     ) -> anyhow::Result<T> {
         let resource_type: String = format!("0x1::{}::{}", T::MODULE_NAME, T::STRUCT_NAME);
-        let res = self
-            .get_account_resource_bcs::<T>(address, &resource_type)
-            .await?
-            .into_inner();
+        let res = match version {
+            Some(v) => self
+                .get_account_resource_at_version_bcs::<T>(address, &resource_type, v)
+                .await?
+                .into_inner(),
+            None => self
+                .get_account_resource_bcs::<T>(address, &resource_type)
+                .await?
+                .into_inner(),
+        };
 
         Ok(res)
     }
@@ -226,6 +259,18 @@ impl ClientExt for Client {
         function_id: &str,
         ty_args: Option<String>,
         args: Option<String>,
+    ) -> anyhow::Result<Value> {
+        self.view_ext_at_version(function_id, ty_args, args, None).await
+    }
+
+    /// Executes a view function against the state at a given ledger version.
+    /// A `None` version uses the latest state.
+    async fn view_ext_at_version(
+        &self,
+        function_id: &str,
+        ty_args: Option<String>,
+        args: Option<String>,
+        version: Option<u64>,
     ) -> anyhow::Result<Value> {
         let entry_fuction_id = EntryFunctionId::from_str(function_id)
             .context(format!("Invalid function id: {function_id}"))?;
@@ -256,7 +301,7 @@ impl ClientExt for Client {
         };
 
         let array = self
-            .view(&request, None)
+            .view(&request, version)
             .await
             .map(|res| res.inner().to_owned())?;
         Ok(Value::Array(array))
@@ -269,6 +314,29 @@ pub struct TransactionOptions {
     pub timeout_secs: u64,
 }
 
+/// Whether the node answered with an error, e.g. a view abort, rather than
+/// not answering at all.
+pub fn is_api_error(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<RestError>(), Some(RestError::Api(_)))
+}
+
+/// Whether the node answered that the account, resource or transaction does
+/// not exist, rather than failing in any other way.
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<RestError>(),
+        Some(RestError::Api(DiemErrorResponse {
+            error: DiemError {
+                error_code: DiemErrorCode::ResourceNotFound
+                    | DiemErrorCode::AccountNotFound
+                    | DiemErrorCode::TransactionNotFound,
+                ..
+            },
+            ..
+        }))
+    )
+}
+
 pub fn entry_function_id(
     module_name: &str,
     function_name: &str,